@@ -3,9 +3,11 @@ use criterion::{criterion_group, criterion_main, Bencher, BenchmarkId, Criterion
 extern crate camillalib;
 
 use camillalib::biquad::{Biquad, BiquadCoefficients};
+use camillalib::config::RMSLimiterParameters;
 use camillalib::diffeq::DiffEq;
 use camillalib::fftconv::FftConv;
 use camillalib::filters::Filter;
+use camillalib::rms_limiter::RMSLimiter;
 use camillalib::PrcFmt;
 
 /// Bench a single convolution
@@ -61,6 +63,31 @@ fn bench_diffeq(c: &mut Criterion) {
     c.bench_function("DiffEq", |b| b.iter(|| de.process_waveform(&mut waveform)));
 }
 
-criterion_group!(benches, bench_conv, bench_biquad, bench_diffeq);
+/// Bench RMSLimiter, with a normal quiet input and with denormal input.
+/// Without flushing of denormals, the second one is much slower on many CPUs.
+fn bench_rms_limiter(c: &mut Criterion) {
+    let chunksize = 1024;
+    let mut group = c.benchmark_group("RMSLimiter");
+    for (label, value) in [("normal", 1.0e-6), ("denormal", PrcFmt::MIN_POSITIVE / 4.0)] {
+        let mut conf = RMSLimiterParameters::new(-20.0, 12.0);
+        conf.remove_dc = Some(true);
+        let mut limiter = RMSLimiter::from_config("test", conf, chunksize, 48000);
+        group.bench_function(label, |b| {
+            b.iter(|| {
+                let mut waveform = vec![value as PrcFmt; chunksize];
+                limiter.process_waveform(&mut waveform)
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_conv,
+    bench_biquad,
+    bench_diffeq,
+    bench_rms_limiter
+);
 
 criterion_main!(benches);
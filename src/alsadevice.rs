@@ -449,6 +449,7 @@ fn playback_loop_bytes(
     let mut chunk_stats = ChunkStats {
         rms: vec![0.0; params.channels],
         peak: vec![0.0; params.channels],
+        peak_index: vec![0; params.channels],
     };
     let mut buffer_avg = countertimer::Averager::new();
     let mut conversion_result;
@@ -830,6 +831,7 @@ fn capture_loop_bytes(
     let mut chunk_stats = ChunkStats {
         rms: vec![0.0; params.channels],
         peak: vec![0.0; params.channels],
+        peak_index: vec![0; params.channels],
     };
     let mut channel_mask = vec![true; params.channels];
     let thread_handle = match promote_current_thread_to_real_time(
@@ -89,6 +89,8 @@ pub struct AudioChunk {
 pub struct ChunkStats {
     pub rms: Vec<PrcFmt>,
     pub peak: Vec<PrcFmt>,
+    /// Index within the chunk of the peak sample
+    pub peak_index: Vec<usize>,
 }
 
 impl ChunkStats {
@@ -168,24 +170,35 @@ impl AudioChunk {
     }
 
     pub fn stats(&self) -> ChunkStats {
-        let rms_peak: Vec<(PrcFmt, PrcFmt)> =
-            self.waveforms.iter().map(|wf| rms_and_peak(wf)).collect();
+        let rms_peak: Vec<(PrcFmt, PrcFmt, usize)> = self
+            .waveforms
+            .iter()
+            .map(|wf| rms_peak_and_index(wf))
+            .collect();
         let rms: Vec<PrcFmt> = rms_peak.iter().map(|rp| rp.0).collect();
         let peak: Vec<PrcFmt> = rms_peak.iter().map(|rp| rp.1).collect();
-        ChunkStats { rms, peak }
+        let peak_index: Vec<usize> = rms_peak.iter().map(|rp| rp.2).collect();
+        ChunkStats {
+            rms,
+            peak,
+            peak_index,
+        }
     }
 
     pub fn update_stats(&self, stats: &mut ChunkStats) {
         stats.rms.resize(self.channels, 0.0);
         stats.peak.resize(self.channels, 0.0);
-        for (wf, (peakval, rmsval)) in self
-            .waveforms
-            .iter()
-            .zip(stats.peak.iter_mut().zip(stats.rms.iter_mut()))
-        {
-            let (rms, peak) = rms_and_peak(wf);
+        stats.peak_index.resize(self.channels, 0);
+        for (wf, (peakval, (rmsval, indexval))) in self.waveforms.iter().zip(
+            stats
+                .peak
+                .iter_mut()
+                .zip(stats.rms.iter_mut().zip(stats.peak_index.iter_mut())),
+        ) {
+            let (rms, peak, index) = rms_peak_and_index(wf);
             *peakval = peak;
             *rmsval = rms;
+            *indexval = index;
         }
         xtrace!(
             "Stats: rms {:?}, peak {:?}",
@@ -203,18 +216,31 @@ impl AudioChunk {
 
 /// Get RMS and peak value of a vector
 pub fn rms_and_peak(data: &[PrcFmt]) -> (PrcFmt, PrcFmt) {
+    let (rms, peak, _) = rms_peak_and_index(data);
+    (rms, peak)
+}
+
+/// Calculate RMS and peak, and the index of the first sample with the peak value
+pub fn rms_peak_and_index(data: &[PrcFmt]) -> (PrcFmt, PrcFmt, usize) {
     if !data.is_empty() {
-        let (squaresum, peakval) = data.iter().fold((0.0, 0.0), |(sqsum, peak), value| {
-            let newpeak = if peak > value.abs() {
-                peak
-            } else {
-                value.abs()
-            };
-            (sqsum + *value * *value, newpeak)
-        });
-        ((squaresum / data.len() as PrcFmt).sqrt(), peakval)
+        let (squaresum, peakval, peakindex) =
+            data.iter()
+                .enumerate()
+                .fold((0.0, 0.0, 0), |(sqsum, peak, index), (n, value)| {
+                    let (newpeak, newindex) = if peak >= value.abs() {
+                        (peak, index)
+                    } else {
+                        (value.abs(), n)
+                    };
+                    (sqsum + *value * *value, newpeak, newindex)
+                });
+        (
+            (squaresum / data.len() as PrcFmt).sqrt(),
+            peakval,
+            peakindex,
+        )
     } else {
-        (0.0, 0.0)
+        (0.0, 0.0, 0)
     }
 }
 
@@ -520,10 +546,9 @@ pub fn new_resampler(
 pub fn new_capture_device(conf: config::Devices) -> Box<dyn CaptureDevice> {
     // Use `capture_samplerate` from config if given, and resampling is enabled.
     // Else, use `samplerate`.
-    let capture_samplerate = if conf.capture_samplerate.is_some() && conf.resampler.is_some() {
-        conf.capture_samplerate.unwrap()
-    } else {
-        conf.samplerate
+    let capture_samplerate = match (conf.capture_samplerate, &conf.resampler) {
+        (Some(cr), Some(_)) => cr,
+        _ => conf.samplerate,
     };
     if let Some(cr) = conf.capture_samplerate {
         if cr != conf.samplerate && conf.resampler.is_none() {
@@ -730,7 +755,7 @@ pub fn new_capture_device(conf: config::Devices) -> Box<dyn CaptureDevice> {
 
 #[cfg(test)]
 mod tests {
-    use crate::audiodevice::{rms_and_peak, AudioChunk, ChunkStats};
+    use crate::audiodevice::{rms_and_peak, rms_peak_and_index, AudioChunk, ChunkStats};
 
     #[test]
     fn vec_rms_and_peak() {
@@ -751,6 +776,26 @@ mod tests {
         assert_eq!(stats.rms[1], 2.0);
         assert_eq!(stats.peak[0], 1.0);
         assert_eq!(stats.peak[1], 4.0);
+        assert_eq!(stats.peak_index, vec![0, 1]);
+    }
+
+    #[test]
+    fn peak_position_in_chunk() {
+        let mut data = vec![0.1; 64];
+        data[17] = -0.9;
+        data[40] = 0.9;
+        // The first of two equal peaks is reported
+        let (_, peak, index) = rms_peak_and_index(&data);
+        assert_eq!(peak, 0.9);
+        assert_eq!(index, 17);
+        let chunk = AudioChunk::new(vec![data.clone(), Vec::new()], 0.0, 0.0, 64, 64);
+        let mut stats = ChunkStats {
+            rms: Vec::new(),
+            peak: Vec::new(),
+            peak_index: Vec::new(),
+        };
+        chunk.update_stats(&mut stats);
+        assert_eq!(stats.peak_index, vec![17, 0]);
     }
 
     #[test]
@@ -758,6 +803,7 @@ mod tests {
         let stats = ChunkStats {
             rms: vec![0.0, 0.5],
             peak: vec![1.0],
+            peak_index: vec![0],
         };
         assert_eq!(-1000.0, stats.rms_db()[0]);
         assert_eq!(0.0, stats.peak_db()[0]);
@@ -0,0 +1,136 @@
+//! Check of the balance between two channels after processing.
+//! This is a building block for applications that use the library. It works on whole
+//! chunks before and after processing, and is not a filter that can be used in a pipeline.
+
+use crate::audiodevice::AudioChunk;
+use crate::dynamics_math::db_to_voltage_ratio;
+use crate::PrcFmt;
+
+// Largest change in dB of the rebalancing correction per chunk.
+const REBALANCE_STEP_DB: PrcFmt = 0.1;
+
+/// Checks that processing, for example heavy limiting of each channel separately,
+/// does not shift the balance between a left and a right channel.
+/// The energy ratio between the channels is compared before and after processing,
+/// and a warning is logged when it has changed by more than the tolerance.
+/// Optionally the louder channel of the output is attenuated to restore the balance.
+/// The correction changes slowly, by at most 0.1 dB per chunk.
+pub struct BalanceGuard {
+    left: usize,
+    right: usize,
+    tolerance_db: PrcFmt,
+    rebalance: bool,
+    correction_db: PrcFmt,
+    warnings: usize,
+}
+
+impl BalanceGuard {
+    pub fn new(left: usize, right: usize, tolerance_db: PrcFmt, rebalance: bool) -> Self {
+        BalanceGuard {
+            left,
+            right,
+            tolerance_db,
+            rebalance,
+            correction_db: 0.0,
+            warnings: 0,
+        }
+    }
+
+    /// Number of chunks where the balance shift exceeded the tolerance.
+    pub fn warnings(&self) -> usize {
+        self.warnings
+    }
+
+    /// Current correction in dB, positive when the left channel is attenuated
+    /// and negative when the right channel is.
+    pub fn correction_db(&self) -> PrcFmt {
+        self.correction_db
+    }
+
+    /// Compare the balance of a processed chunk to the chunk before processing,
+    /// and return the shift in dB, positive if the output has moved to the left.
+    /// Returns None if either channel is silent.
+    pub fn check(&mut self, input: &AudioChunk, output: &mut AudioChunk) -> Option<PrcFmt> {
+        let shift_db = self.balance_db(output)? - self.balance_db(input)?;
+        if shift_db.abs() > self.tolerance_db {
+            self.warnings += 1;
+            warn!(
+                "Balance between channels {} and {} shifted by {:.2} dB",
+                self.left, self.right, shift_db
+            );
+        }
+        if self.rebalance {
+            self.correction_db +=
+                (shift_db - self.correction_db).clamp(-REBALANCE_STEP_DB, REBALANCE_STEP_DB);
+            let gain = db_to_voltage_ratio(-self.correction_db.abs());
+            let channel = if self.correction_db > 0.0 {
+                self.left
+            } else {
+                self.right
+            };
+            for value in output.waveforms[channel].iter_mut() {
+                *value *= gain;
+            }
+        }
+        Some(shift_db)
+    }
+
+    /// Energy ratio in dB between the left and right channel.
+    fn balance_db(&self, chunk: &AudioChunk) -> Option<PrcFmt> {
+        let energy =
+            |channel: usize| -> PrcFmt { chunk.waveforms[channel].iter().map(|v| v * v).sum() };
+        let left = energy(self.left);
+        let right = energy(self.right);
+        if left == 0.0 || right == 0.0 {
+            return None;
+        }
+        Some(10.0 * (left / right).log10())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::audiodevice::AudioChunk;
+    use crate::balanceguard::BalanceGuard;
+    use crate::PrcFmt;
+
+    fn chunk(left: PrcFmt, right: PrcFmt) -> AudioChunk {
+        AudioChunk::new(vec![vec![left; 256], vec![right; 256]], 1.0, -1.0, 256, 256)
+    }
+
+    #[test]
+    fn warn_on_balance_shift() {
+        let mut guard = BalanceGuard::new(0, 1, 1.0, false);
+        // Both channels limited by the same amount, the balance is kept
+        let input = chunk(0.8, 0.4);
+        let mut output = chunk(0.4, 0.2);
+        let shift = guard.check(&input, &mut output).unwrap();
+        assert!(shift.abs() < 1.0e-4);
+        assert_eq!(guard.warnings(), 0);
+        // Only the left channel is limited, the output moves 6 dB to the right
+        let mut output = chunk(0.4, 0.4);
+        let shift = guard.check(&input, &mut output).unwrap();
+        assert!((shift + 6.02).abs() < 0.01);
+        assert_eq!(guard.warnings(), 1);
+        // Silence gives no result
+        let mut output = chunk(0.0, 0.4);
+        assert!(guard.check(&input, &mut output).is_none());
+    }
+
+    #[test]
+    fn gentle_rebalance() {
+        let mut guard = BalanceGuard::new(0, 1, 1.0, true);
+        let input = chunk(0.8, 0.4);
+        let mut output = chunk(0.4, 0.4);
+        guard.check(&input, &mut output);
+        // The right channel is attenuated by the first step of the correction
+        assert!((guard.correction_db() + 0.1).abs() < 1.0e-6);
+        assert_eq!(output.waveforms[0][0], 0.4);
+        assert!(output.waveforms[1][0] < 0.4);
+        for _ in 0..100 {
+            let mut output = chunk(0.4, 0.4);
+            guard.check(&input, &mut output);
+        }
+        assert!((guard.correction_db() + 6.02).abs() < 0.01);
+    }
+}
@@ -209,6 +209,10 @@ impl Filter for Volume {
         &self.name
     }
 
+    fn filter_type(&self) -> &'static str {
+        "Volume"
+    }
+
     fn process_waveform(&mut self, waveform: &mut [PrcFmt]) -> Res<()> {
         self.prepare_processing();
 
@@ -296,6 +300,14 @@ impl Filter for Gain {
         &self.name
     }
 
+    fn filter_type(&self) -> &'static str {
+        "Gain"
+    }
+
+    fn can_increase_level(&self) -> bool {
+        self.gain.abs() > 1.0
+    }
+
     fn process_waveform(&mut self, waveform: &mut [PrcFmt]) -> Res<()> {
         for item in waveform.iter_mut() {
             *item *= self.gain;
@@ -376,6 +388,18 @@ impl Filter for Delay {
         &self.name
     }
 
+    fn filter_type(&self) -> &'static str {
+        "Delay"
+    }
+
+    fn can_increase_level(&self) -> bool {
+        false
+    }
+
+    fn latency_samples(&self) -> usize {
+        self.queue.capacity()
+    }
+
     fn process_waveform(&mut self, waveform: &mut [PrcFmt]) -> Res<()> {
         for item in waveform.iter_mut() {
             // this returns the item that was popped while pushing
@@ -397,6 +421,41 @@ impl Filter for Delay {
     }
 }
 
+/// A filter that runs a closure on each waveform, for prototyping new processing
+/// without writing a full filter. It has no config, and ignores parameter updates.
+pub struct ClosureFilter {
+    pub name: String,
+    func: WaveformClosure,
+}
+
+pub type WaveformClosure = Box<dyn FnMut(&mut [PrcFmt]) + Send>;
+
+impl ClosureFilter {
+    pub fn new(name: &str, func: WaveformClosure) -> Self {
+        ClosureFilter {
+            name: name.to_string(),
+            func,
+        }
+    }
+}
+
+impl Filter for ClosureFilter {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn filter_type(&self) -> &'static str {
+        "Closure"
+    }
+
+    fn process_waveform(&mut self, waveform: &mut [PrcFmt]) -> Res<()> {
+        (self.func)(waveform);
+        Ok(())
+    }
+
+    fn update_parameters(&mut self, _conf: config::Filter) {}
+}
+
 /// Validate a Loudness config.
 pub fn validate_delay_config(conf: &config::DelayParameters) -> Res<()> {
     if conf.delay < 0.0 {
@@ -431,7 +490,7 @@ pub fn validate_gain_config(conf: &config::GainParameters) -> Res<()> {
 
 #[cfg(test)]
 mod tests {
-    use crate::basicfilters::{Delay, Gain};
+    use crate::basicfilters::{ClosureFilter, Delay, Gain};
     use crate::filters::Filter;
     use crate::PrcFmt;
 
@@ -467,6 +526,18 @@ mod tests {
         assert_eq!(waveform, waveform_ampl);
     }
 
+    #[test]
+    fn delay_flush() {
+        let mut waveform = vec![0.0, 0.0, 0.0, 0.0, 0.0, 1.0];
+        let mut delay = Delay::new("test", 44100, 3.0, false);
+        assert_eq!(delay.latency_samples(), 3);
+        delay.process_waveform(&mut waveform).unwrap();
+        assert_eq!(waveform, vec![0.0; 6]);
+        let mut tail = Vec::new();
+        delay.flush(&mut tail);
+        assert_eq!(tail, vec![0.0, 0.0, 1.0]);
+    }
+
     #[test]
     fn delay_small() {
         let mut waveform = vec![0.0, -0.5, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0];
@@ -516,4 +587,21 @@ mod tests {
         delay.process_waveform(&mut waveform).unwrap();
         assert!(compare_waveforms(waveform, waveform_delayed, 1.0e-6));
     }
+
+    #[test]
+    fn closure_gain() {
+        let mut waveform = vec![-0.5, 0.0, 0.5];
+        let expected = vec![-0.25, 0.0, 0.25];
+        let mut filter = ClosureFilter::new(
+            "test",
+            Box::new(|waveform: &mut [PrcFmt]| {
+                for item in waveform.iter_mut() {
+                    *item *= 0.5;
+                }
+            }),
+        );
+        filter.process_waveform(&mut waveform).unwrap();
+        assert_eq!(waveform, expected);
+        assert_eq!(filter.filter_type(), "Closure");
+    }
 }
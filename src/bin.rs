@@ -1167,7 +1167,7 @@ fn main_process() -> i32 {
 
         match exitstatus {
             Err(e) => {
-                error!("({}) {}", e.to_string(), e);
+                error!("({}) {}", e, e);
                 if !wait {
                     return EXIT_PROCESSING_ERROR;
                 }
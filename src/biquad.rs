@@ -486,6 +486,10 @@ impl Filter for Biquad {
         &self.name
     }
 
+    fn filter_type(&self) -> &'static str {
+        "Biquad"
+    }
+
     fn process_waveform(&mut self, waveform: &mut [PrcFmt]) -> Res<()> {
         for item in waveform.iter_mut() {
             *item = self.process_single(*item);
@@ -549,10 +553,10 @@ pub fn validate_config(samplerate: usize, parameters: &config::BiquadParameters)
         | config::BiquadParameters::Allpass(config::NotchWidth::Q { q, .. })
         | config::BiquadParameters::Highshelf(config::ShelfSteepness::Q { q, .. })
         | config::BiquadParameters::Lowshelf(config::ShelfSteepness::Q { q, .. })
-        | config::BiquadParameters::GeneralNotch(config::GeneralNotchParams { q_p: q, .. }) => {
-            if *q <= 0.0 {
-                return Err(config::ConfigError::new("Q must be > 0").into());
-            }
+        | config::BiquadParameters::GeneralNotch(config::GeneralNotchParams { q_p: q, .. })
+            if *q <= 0.0 =>
+        {
+            return Err(config::ConfigError::new("Q must be > 0").into());
         }
         _ => {}
     }
@@ -563,10 +567,10 @@ pub fn validate_config(samplerate: usize, parameters: &config::BiquadParameters)
         })
         | config::BiquadParameters::Notch(config::NotchWidth::Bandwidth { bandwidth, .. })
         | config::BiquadParameters::Bandpass(config::NotchWidth::Bandwidth { bandwidth, .. })
-        | config::BiquadParameters::Allpass(config::NotchWidth::Bandwidth { bandwidth, .. }) => {
-            if *bandwidth <= 0.0 {
-                return Err(config::ConfigError::new("Bandwidth must be > 0").into());
-            }
+        | config::BiquadParameters::Allpass(config::NotchWidth::Bandwidth { bandwidth, .. })
+            if *bandwidth <= 0.0 =>
+        {
+            return Err(config::ConfigError::new("Bandwidth must be > 0").into());
         }
         _ => {}
     }
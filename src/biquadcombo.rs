@@ -268,6 +268,10 @@ impl Filter for BiquadCombo {
         &self.name
     }
 
+    fn filter_type(&self) -> &'static str {
+        "BiquadCombo"
+    }
+
     fn process_waveform(&mut self, waveform: &mut [PrcFmt]) -> Res<()> {
         for filter in self.filters.iter_mut() {
             filter.process_waveform(waveform)?;
@@ -1,4 +1,5 @@
 use crate::compressor;
+use crate::dynamics_math;
 use crate::filters;
 use crate::mixer;
 use crate::noisegate;
@@ -778,7 +779,27 @@ pub enum Filter {
         #[serde(default)]
         description: Option<String>,
         parameters: RMSLimiterParameters,
-    }
+    },
+    FinalStage {
+        #[serde(default)]
+        description: Option<String>,
+        parameters: FinalStageParameters,
+    },
+    Normalizer {
+        #[serde(default)]
+        description: Option<String>,
+        parameters: NormalizerParameters,
+    },
+    UpwardCompressor {
+        #[serde(default)]
+        description: Option<String>,
+        parameters: UpwardCompressorParameters,
+    },
+    LfProtect {
+        #[serde(default)]
+        description: Option<String>,
+        parameters: LfProtectParameters,
+    },
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
@@ -1040,6 +1061,61 @@ pub enum LoudnessFader {
     Aux4 = 4,
 }
 
+/// Level detector used by the dynamics filters.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub enum DetectionType {
+    Rms,
+    Peak,
+    RmsOfPeaks,
+    LogRms,
+}
+
+/// How the gain of a limiter returns to unity.
+/// - `Db`: the gain rises by a fixed number of dB per second.
+/// - `Linear`: the gain rises by a fixed linear amount per second.
+///   This recovers quickly from deep gain reduction, and slows down close to unity.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub enum ReleaseDomain {
+    Db,
+    Linear,
+}
+
+/// How the gain of a limiter moves towards a lower target during the attack.
+/// - `Exponential`: a one-pole smoothing, fast at first and then slowing down.
+/// - `Linear`: a straight line, that reaches the target after the attack time
+///   whatever the depth of the reduction.
+/// - `SCurve`: like `Linear`, but starting and ending gently.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub enum AttackCurve {
+    Exponential,
+    Linear,
+    SCurve,
+}
+
+/// Shape of the soft limiter curve in the dB domain.
+/// - `Tanh`: starts bending below threshold, and approaches it asymptotically.
+/// - `Linear`: a straight line across a knee centered on the threshold,
+///   with a sudden change of slope at the knee boundaries.
+/// - `Smoothstep`: an S-shaped transition across the same knee,
+///   where the slope changes smoothly.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub enum KneeShape {
+    Tanh,
+    Linear,
+    Smoothstep,
+}
+
+/// Weighting of the samples in the rms detection window.
+/// - `Rectangular`: all samples are weighted equally.
+/// - `Hann`, `Hamming`: the rising half of the window, from the oldest sample to the newest,
+///   so that recent samples count more than old ones.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub enum WindowFunction {
+    Rectangular,
+    Hann,
+    Hamming,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 #[serde(deny_unknown_fields)]
 pub struct RMSLimiterParameters {
@@ -1047,12 +1123,445 @@ pub struct RMSLimiterParameters {
     pub decay: f32,
     pub threshold: f32,
     pub rms_samples: usize,
+    #[serde(default)]
+    pub soft: Option<bool>,
+    #[serde(default)]
+    pub wait_for_warmup: Option<bool>,
+    #[serde(default)]
+    pub auto_threshold_target_db: Option<f32>,
+    #[serde(default)]
+    pub ceiling_dbtp: Option<f32>,
+    #[serde(default)]
+    pub input_gain_db: Option<f32>,
+    #[serde(default)]
+    pub reduction_histogram: Option<bool>,
+    #[serde(default)]
+    pub curve_table: Option<Vec<(f32, f32)>>,
+    #[serde(default)]
+    pub analyze_only: Option<bool>,
+    #[serde(default)]
+    pub bypassed: Option<bool>,
+    #[serde(default)]
+    pub bypass_crossfade_ms: Option<f32>,
+    #[serde(default)]
+    pub detection_hold_ms: Option<f32>,
+    #[serde(default)]
+    pub detection: Option<DetectionType>,
+    #[serde(default)]
+    pub detection_weights: Option<Vec<(f32, f32)>>,
+    #[serde(default)]
+    pub warmth: Option<f32>,
+    #[serde(default)]
+    pub makeup_fraction: Option<f32>,
+    #[serde(default)]
+    pub channel_trim_db: Option<f32>,
+    #[serde(default)]
+    pub osc_target: Option<String>,
+    #[serde(default)]
+    pub osc_path: Option<String>,
+    #[serde(default)]
+    pub time_scale: Option<f32>,
+    #[serde(default)]
+    pub release_domain: Option<ReleaseDomain>,
+    #[serde(default)]
+    pub reset_on_silence: Option<bool>,
+    #[serde(default)]
+    pub silence_threshold_db: Option<f32>,
+    #[serde(default)]
+    pub sensitivity_db: Option<f32>,
+    #[serde(default)]
+    pub remove_dc: Option<bool>,
+    #[serde(default)]
+    pub remove_dc_from_output: Option<bool>,
+    #[serde(default)]
+    pub detection_decimation: Option<usize>,
+    #[serde(default)]
+    pub mute_on_fault: Option<bool>,
+    #[serde(default)]
+    pub release_ms: Option<f32>,
+    #[serde(default)]
+    pub release_note: Option<String>,
+    #[serde(default)]
+    pub bpm: Option<f32>,
+    #[serde(default)]
+    pub detect_from_channel: Option<usize>,
+    #[serde(default)]
+    pub knee_shape: Option<KneeShape>,
+    #[serde(default)]
+    pub active_ranges: Option<Vec<(usize, usize)>>,
+    #[serde(default)]
+    pub strict_ceiling_tolerance_db: Option<f32>,
+    #[serde(default)]
+    pub knee_db: Option<f32>,
+    #[serde(default)]
+    pub knee_offset_db: Option<f32>,
+    #[serde(default)]
+    pub slew_limit_db_per_ms: Option<f32>,
+    #[serde(default)]
+    pub calibration_ms: Option<f32>,
+    #[serde(default)]
+    pub peak_shaver: Option<bool>,
+    #[serde(default)]
+    pub detection_window: Option<WindowFunction>,
+    #[serde(default)]
+    pub max_reduction_rate_db_per_s: Option<f32>,
+    #[serde(default)]
+    pub clip_ceiling_db: Option<f32>,
+    #[serde(default)]
+    pub attack_from_window: Option<f32>,
+    #[serde(default)]
+    pub release_shelf_db: Option<f32>,
+    #[serde(default)]
+    pub trim_input_overs: Option<bool>,
+    #[serde(default)]
+    pub release_retrigger_db: Option<f32>,
+    #[serde(default)]
+    pub channels: Option<usize>,
+    #[serde(default)]
+    pub threshold_relative_db: Option<f32>,
+    #[serde(default)]
+    pub attack_curve: Option<AttackCurve>,
+    #[serde(default)]
+    pub ceiling_trim_db: Option<f32>,
+    #[serde(default)]
+    pub manual: Option<bool>,
+}
+
+impl Default for RMSLimiterParameters {
+    fn default() -> Self {
+        RMSLimiterParameters {
+            decay: default_decay(),
+            threshold: -1.0,
+            // About 43 ms at 48 kHz, the rms buffer size must be a power of two.
+            rms_samples: 2048,
+            soft: None,
+            wait_for_warmup: None,
+            auto_threshold_target_db: None,
+            ceiling_dbtp: None,
+            input_gain_db: None,
+            reduction_histogram: None,
+            curve_table: None,
+            analyze_only: None,
+            bypassed: None,
+            bypass_crossfade_ms: None,
+            detection_hold_ms: None,
+            detection: None,
+            detection_weights: None,
+            warmth: None,
+            makeup_fraction: None,
+            channel_trim_db: None,
+            osc_target: None,
+            osc_path: None,
+            time_scale: None,
+            release_domain: None,
+            reset_on_silence: None,
+            silence_threshold_db: None,
+            sensitivity_db: None,
+            remove_dc: None,
+            remove_dc_from_output: None,
+            detection_decimation: None,
+            mute_on_fault: None,
+            release_ms: None,
+            release_note: None,
+            bpm: None,
+            detect_from_channel: None,
+            knee_shape: None,
+            active_ranges: None,
+            strict_ceiling_tolerance_db: None,
+            knee_db: None,
+            knee_offset_db: None,
+            slew_limit_db_per_ms: None,
+            calibration_ms: None,
+            peak_shaver: None,
+            detection_window: None,
+            max_reduction_rate_db_per_s: None,
+            clip_ceiling_db: None,
+            attack_from_window: None,
+            release_shelf_db: None,
+            trim_input_overs: None,
+            release_retrigger_db: None,
+            channels: None,
+            threshold_relative_db: None,
+            attack_curve: None,
+            ceiling_trim_db: None,
+            manual: None,
+        }
+    }
+}
+
+/// Parse a note value, as a fraction like "1/4" or a number like "0.25",
+/// into a fraction of a whole note.
+pub fn parse_note_value(note: &str) -> Option<f32> {
+    let value = match note.split_once('/') {
+        Some((numerator, denominator)) => {
+            numerator.trim().parse::<f32>().ok()? / denominator.trim().parse::<f32>().ok()?
+        }
+        None => note.trim().parse::<f32>().ok()?,
+    };
+    (value.is_finite() && value > 0.0).then_some(value)
+}
+
+impl RMSLimiterParameters {
+    /// Parameters with the given threshold (dB) and decay (dB/s), and defaults for the rest.
+    pub fn new(threshold: f32, decay: f32) -> Self {
+        RMSLimiterParameters {
+            threshold,
+            decay,
+            ..Default::default()
+        }
+    }
+
+    pub fn soft(&self) -> bool {
+        self.soft.unwrap_or_default()
+    }
+
+    pub fn wait_for_warmup(&self) -> bool {
+        self.wait_for_warmup.unwrap_or_default()
+    }
+
+    pub fn input_gain_db(&self) -> f32 {
+        self.input_gain_db.unwrap_or_default()
+    }
+
+    pub fn reduction_histogram(&self) -> bool {
+        self.reduction_histogram.unwrap_or_default()
+    }
+
+    pub fn analyze_only(&self) -> bool {
+        self.analyze_only.unwrap_or_default()
+    }
+
+    pub fn bypassed(&self) -> bool {
+        self.bypassed.unwrap_or_default()
+    }
+
+    pub fn manual(&self) -> bool {
+        self.manual.unwrap_or_default()
+    }
+
+    pub fn bypass_crossfade_ms(&self) -> f32 {
+        self.bypass_crossfade_ms.unwrap_or(10.0)
+    }
+
+    pub fn detection_hold_ms(&self) -> f32 {
+        self.detection_hold_ms.unwrap_or_default()
+    }
+
+    pub fn detection(&self) -> DetectionType {
+        self.detection.unwrap_or(DetectionType::Rms)
+    }
+
+    pub fn warmth(&self) -> f32 {
+        self.warmth.unwrap_or_default()
+    }
+
+    /// Fraction of the gain reduction that is restored as makeup gain, 0 gives none.
+    pub fn makeup_fraction(&self) -> f32 {
+        self.makeup_fraction.unwrap_or_default()
+    }
+
+    pub fn channel_trim_db(&self) -> f32 {
+        self.channel_trim_db.unwrap_or_default()
+    }
+
+    pub fn osc_path(&self) -> String {
+        self.osc_path
+            .clone()
+            .unwrap_or_else(|| "/camilladsp/reduction".to_string())
+    }
+
+    pub fn time_scale(&self) -> f32 {
+        self.time_scale.unwrap_or(1.0)
+    }
+
+    pub fn release_domain(&self) -> ReleaseDomain {
+        self.release_domain.unwrap_or(ReleaseDomain::Db)
+    }
+
+    pub fn attack_curve(&self) -> AttackCurve {
+        self.attack_curve.unwrap_or(AttackCurve::Exponential)
+    }
+
+    pub fn knee_shape(&self) -> KneeShape {
+        self.knee_shape.unwrap_or(KneeShape::Tanh)
+    }
+
+    /// Width of the knee. The Tanh knee starts bending this far below the level
+    /// it approaches, the Linear and Smoothstep knees are centered in it.
+    pub fn knee_db(&self) -> f32 {
+        self.knee_db.unwrap_or(6.0)
+    }
+
+    /// Position of the knee relative to the threshold. This is the level that the Tanh knee
+    /// approaches, and the center of the Linear and Smoothstep knees.
+    pub fn knee_offset_db(&self) -> f32 {
+        self.knee_offset_db.unwrap_or_default()
+    }
+
+    pub fn peak_shaver(&self) -> bool {
+        self.peak_shaver.unwrap_or_default()
+    }
+
+    /// How far below the ceiling the gain is calculated,
+    /// to leave room for the overshoot between chunks.
+    pub fn ceiling_trim_db(&self) -> f32 {
+        self.ceiling_trim_db.unwrap_or(0.1)
+    }
+
+    pub fn trim_input_overs(&self) -> bool {
+        self.trim_input_overs.unwrap_or_default()
+    }
+
+    pub fn detection_window(&self) -> WindowFunction {
+        self.detection_window.unwrap_or(WindowFunction::Rectangular)
+    }
+
+    pub fn reset_on_silence(&self) -> bool {
+        self.reset_on_silence.unwrap_or_default()
+    }
+
+    pub fn silence_threshold_db(&self) -> f32 {
+        self.silence_threshold_db.unwrap_or(-100.0)
+    }
+
+    pub fn sensitivity_db(&self) -> f32 {
+        self.sensitivity_db.unwrap_or_default()
+    }
+
+    pub fn remove_dc(&self) -> bool {
+        self.remove_dc.unwrap_or_default()
+    }
+
+    pub fn remove_dc_from_output(&self) -> bool {
+        self.remove_dc_from_output.unwrap_or_default()
+    }
+
+    /// Number of channels that one limiter processes with independent states.
+    pub fn channels(&self) -> usize {
+        self.channels.unwrap_or(1)
+    }
+
+    /// True if the limiter gets all the channels of its pipeline step in one call,
+    /// instead of one limiter per channel.
+    pub fn processes_frames(&self) -> bool {
+        self.channels() > 1 || self.detect_from_channel.is_some()
+    }
+
+    /// Use only every Nth sample for level detection.
+    pub fn detection_decimation(&self) -> usize {
+        self.detection_decimation.unwrap_or(1)
+    }
+
+    /// The decay in dB/s, calculated from the release time when that is given.
+    pub fn decay_db_per_s(&self) -> f32 {
+        match self.release_time_ms() {
+            Some(ms) => dynamics_math::release_ms_to_db_per_s(ms as PrcFmt) as f32,
+            None => self.decay,
+        }
+    }
+
+    /// The release time in ms, given directly or as a note value at a tempo.
+    pub fn release_time_ms(&self) -> Option<f32> {
+        self.release_ms.or_else(|| {
+            let fraction = parse_note_value(self.release_note.as_ref()?)?;
+            Some(dynamics_math::note_to_ms(fraction as PrcFmt, self.bpm? as PrcFmt) as f32)
+        })
+    }
+
+    /// Mute the output of a chunk where the gain calculation gave a non-finite value.
+    pub fn mute_on_fault(&self) -> bool {
+        self.mute_on_fault.unwrap_or(true)
+    }
+
+    /// Number of detection samples, when covering rms_samples input samples.
+    pub fn detection_buffer_size(&self) -> usize {
+        self.rms_samples / self.detection_decimation()
+    }
+
+    /// Apply overrides given as (key, value) pairs, for example parsed from "threshold=-3".
+    /// The supported keys are `threshold`, `decay` and `rms_samples`.
+    /// Nothing is changed if any key is unknown or any value is invalid.
+    pub fn apply_overrides(&mut self, overrides: &[(String, String)]) -> Res<()> {
+        let mut updated = self.clone();
+        for (key, value) in overrides {
+            let invalid = || {
+                let msg = format!("Invalid value '{value}' for '{key}'");
+                ConfigError::new(&msg)
+            };
+            match key.as_str() {
+                "threshold" => updated.threshold = value.trim().parse().map_err(|_| invalid())?,
+                "decay" => updated.decay = value.trim().parse().map_err(|_| invalid())?,
+                "rms_samples" => {
+                    updated.rms_samples = value.trim().parse().map_err(|_| invalid())?
+                }
+                _ => {
+                    let msg = format!("Unknown parameter '{key}'");
+                    return Err(ConfigError::new(&msg).into());
+                }
+            }
+        }
+        *self = updated;
+        Ok(())
+    }
 }
 
 fn default_decay() -> f32 {
     12.0
 }
 
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct FinalStageParameters {
+    #[serde(default = "default_decay")]
+    pub decay: f32,
+    pub threshold: f32,
+    pub rms_samples: usize,
+    pub clip_ceiling: f32,
+}
+
+impl FinalStageParameters {
+    pub fn limiter_parameters(&self) -> RMSLimiterParameters {
+        RMSLimiterParameters {
+            decay: self.decay,
+            threshold: self.threshold,
+            rms_samples: self.rms_samples,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct NormalizerParameters {
+    pub target_loudness_db: f32,
+    pub limiter: RMSLimiterParameters,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct UpwardCompressorParameters {
+    pub threshold: f32,
+    pub ratio: f32,
+    pub max_boost_db: f32,
+    pub rms_samples: usize,
+    #[serde(default)]
+    pub boost_rate: Option<f32>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct LfProtectParameters {
+    pub crossover_hz: PrcFmt,
+    pub limiter: LimiterParameters,
+}
+
+impl UpwardCompressorParameters {
+    /// How fast the boost may increase, in dB/s.
+    pub fn boost_rate(&self) -> f32 {
+        self.boost_rate.unwrap_or(12.0)
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 #[serde(deny_unknown_fields)]
 pub struct LoudnessParameters {
@@ -1154,28 +1663,148 @@ pub enum TimeUnit {
 #[serde(tag = "type")]
 #[serde(deny_unknown_fields)]
 pub enum DitherParameters {
-    None { bits: usize },
-    Flat { bits: usize, amplitude: PrcFmt },
-    Highpass { bits: usize },
-    Fweighted441 { bits: usize },
-    FweightedLong441 { bits: usize },
-    FweightedShort441 { bits: usize },
-    Gesemann441 { bits: usize },
-    Gesemann48 { bits: usize },
-    Lipshitz441 { bits: usize },
-    LipshitzLong441 { bits: usize },
-    Shibata441 { bits: usize },
-    ShibataHigh441 { bits: usize },
-    ShibataLow441 { bits: usize },
-    Shibata48 { bits: usize },
-    ShibataHigh48 { bits: usize },
-    ShibataLow48 { bits: usize },
-    Shibata882 { bits: usize },
-    ShibataLow882 { bits: usize },
-    Shibata96 { bits: usize },
-    ShibataLow96 { bits: usize },
-    Shibata192 { bits: usize },
-    ShibataLow192 { bits: usize },
+    None {
+        bits: usize,
+        #[serde(default)]
+        seed: Option<u64>,
+    },
+    Flat {
+        bits: usize,
+        amplitude: PrcFmt,
+        #[serde(default)]
+        seed: Option<u64>,
+    },
+    Highpass {
+        bits: usize,
+        #[serde(default)]
+        seed: Option<u64>,
+    },
+    Fweighted441 {
+        bits: usize,
+        #[serde(default)]
+        seed: Option<u64>,
+    },
+    FweightedLong441 {
+        bits: usize,
+        #[serde(default)]
+        seed: Option<u64>,
+    },
+    FweightedShort441 {
+        bits: usize,
+        #[serde(default)]
+        seed: Option<u64>,
+    },
+    Gesemann441 {
+        bits: usize,
+        #[serde(default)]
+        seed: Option<u64>,
+    },
+    Gesemann48 {
+        bits: usize,
+        #[serde(default)]
+        seed: Option<u64>,
+    },
+    Lipshitz441 {
+        bits: usize,
+        #[serde(default)]
+        seed: Option<u64>,
+    },
+    LipshitzLong441 {
+        bits: usize,
+        #[serde(default)]
+        seed: Option<u64>,
+    },
+    Shibata441 {
+        bits: usize,
+        #[serde(default)]
+        seed: Option<u64>,
+    },
+    ShibataHigh441 {
+        bits: usize,
+        #[serde(default)]
+        seed: Option<u64>,
+    },
+    ShibataLow441 {
+        bits: usize,
+        #[serde(default)]
+        seed: Option<u64>,
+    },
+    Shibata48 {
+        bits: usize,
+        #[serde(default)]
+        seed: Option<u64>,
+    },
+    ShibataHigh48 {
+        bits: usize,
+        #[serde(default)]
+        seed: Option<u64>,
+    },
+    ShibataLow48 {
+        bits: usize,
+        #[serde(default)]
+        seed: Option<u64>,
+    },
+    Shibata882 {
+        bits: usize,
+        #[serde(default)]
+        seed: Option<u64>,
+    },
+    ShibataLow882 {
+        bits: usize,
+        #[serde(default)]
+        seed: Option<u64>,
+    },
+    Shibata96 {
+        bits: usize,
+        #[serde(default)]
+        seed: Option<u64>,
+    },
+    ShibataLow96 {
+        bits: usize,
+        #[serde(default)]
+        seed: Option<u64>,
+    },
+    Shibata192 {
+        bits: usize,
+        #[serde(default)]
+        seed: Option<u64>,
+    },
+    ShibataLow192 {
+        bits: usize,
+        #[serde(default)]
+        seed: Option<u64>,
+    },
+}
+
+impl DitherParameters {
+    /// Seed for the random number generator, for reproducible output.
+    /// Without a seed, the generator is seeded from the system entropy source.
+    pub fn seed(&self) -> Option<u64> {
+        match self {
+            DitherParameters::None { seed, .. }
+            | DitherParameters::Flat { seed, .. }
+            | DitherParameters::Highpass { seed, .. }
+            | DitherParameters::Fweighted441 { seed, .. }
+            | DitherParameters::FweightedLong441 { seed, .. }
+            | DitherParameters::FweightedShort441 { seed, .. }
+            | DitherParameters::Gesemann441 { seed, .. }
+            | DitherParameters::Gesemann48 { seed, .. }
+            | DitherParameters::Lipshitz441 { seed, .. }
+            | DitherParameters::LipshitzLong441 { seed, .. }
+            | DitherParameters::Shibata441 { seed, .. }
+            | DitherParameters::ShibataHigh441 { seed, .. }
+            | DitherParameters::ShibataLow441 { seed, .. }
+            | DitherParameters::Shibata48 { seed, .. }
+            | DitherParameters::ShibataHigh48 { seed, .. }
+            | DitherParameters::ShibataLow48 { seed, .. }
+            | DitherParameters::Shibata882 { seed, .. }
+            | DitherParameters::ShibataLow882 { seed, .. }
+            | DitherParameters::Shibata96 { seed, .. }
+            | DitherParameters::ShibataLow96 { seed, .. }
+            | DitherParameters::Shibata192 { seed, .. }
+            | DitherParameters::ShibataLow192 { seed, .. } => *seed,
+        }
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
@@ -1301,6 +1930,32 @@ pub struct CompressorParameters {
 }
 
 impl CompressorParameters {
+    /// Parameters from a named preset, for the given number of channels and threshold.
+    /// All channels are monitored and processed. Returns None for an unknown preset.
+    ///
+    /// Available presets:
+    /// - `glue`: mix bus glue with a 1.5:1 ratio, 30 ms attack and a slow 300 ms release.
+    ///   The compressor has a hard knee and a fixed release, so the preset does not
+    ///   provide the soft knee and program dependent release of some glue compressors.
+    pub fn preset(preset: &str, channels: usize, threshold: PrcFmt) -> Option<Self> {
+        let (attack, release, factor) = match preset {
+            "glue" => (0.03, 0.3, 1.5),
+            _ => return None,
+        };
+        Some(CompressorParameters {
+            channels,
+            monitor_channels: None,
+            process_channels: None,
+            attack,
+            release,
+            threshold,
+            factor,
+            makeup_gain: None,
+            soft_clip: None,
+            clip_limit: None,
+        })
+    }
+
     pub fn monitor_channels(&self) -> Vec<usize> {
         self.monitor_channels.clone().unwrap_or_default()
     }
@@ -1479,7 +2134,10 @@ pub fn write_config(filename: &str, config: &Configuration) -> Res<()> {
     let mut file = match File::create(filename) {
         Ok(f) => f,
         Err(err) => {
-            let msg = format!("Could not create config file '{}'. Error: {}", filename, err);
+            let msg = format!(
+                "Could not create config file '{}'. Error: {}",
+                filename, err
+            );
             return Err(ConfigError::new(&msg).into());
         }
     };
@@ -1805,7 +2463,7 @@ pub fn config_diff(currentconf: &Configuration, newconf: &Configuration) -> Conf
     if currentconf.mixers != newconf.mixers {
         return ConfigChange::MixerParameters;
     }
-    let mut filters = Vec::<String>::new();
+    let filters = Vec::<String>::new();
     let mut mixers = Vec::<String>::new();
     let mut processors = Vec::<String>::new();
     if let (Some(newfilters), Some(oldfilters)) = (&newconf.filters, &currentconf.filters) {
@@ -2075,7 +2733,11 @@ pub fn validate_config(conf: &mut Configuration, filename: Option<&str>) -> Res<
                                     let msg = format!("Use of missing filter '{name}'");
                                     return Err(ConfigError::new(&msg).into());
                                 }
-                                match filters::validate_filter(fs, filters.get(name).unwrap()) {
+                                match filters::validate_filter(
+                                    fs,
+                                    conf.devices.chunksize,
+                                    filters.get(name).unwrap(),
+                                ) {
                                     Ok(_) => {}
                                     Err(err) => {
                                         let msg = format!("Invalid filter '{name}'. Reason: {err}");
@@ -2087,6 +2749,11 @@ pub fn validate_config(conf: &mut Configuration, filename: Option<&str>) -> Res<
                                 return Err(ConfigError::new(&msg).into());
                             }
                         }
+                        let step_channels = step
+                            .channels
+                            .as_ref()
+                            .map_or(num_channels, |channels| channels.len());
+                        validate_frames_filters(step, conf.filters.as_ref(), step_channels)?;
                     }
                 }
                 PipelineStep::Processor(step) => {
@@ -2159,6 +2826,47 @@ pub fn validate_config(conf: &mut Configuration, filename: Option<&str>) -> Res<
     Ok(())
 }
 
+/// Check the filters of a pipeline step that get all the channels of the step in one call.
+/// Such a filter must be alone in its step, and be set up for the channels of the step.
+fn validate_frames_filters(
+    step: &PipelineStepFilter,
+    filters: Option<&HashMap<String, Filter>>,
+    step_channels: usize,
+) -> Res<()> {
+    let Some(filters) = filters else {
+        return Ok(());
+    };
+    for name in &step.names {
+        if let Some(Filter::RMSLimiter { parameters, .. }) = filters.get(name) {
+            if !parameters.processes_frames() {
+                continue;
+            }
+            if step.names.len() > 1 {
+                let msg = format!(
+                    "Multichannel limiter '{name}' must be the only filter in its pipeline step"
+                );
+                return Err(ConfigError::new(&msg).into());
+            }
+            let channels = parameters.channels();
+            if channels > 1 && channels != step_channels {
+                let msg = format!(
+                    "Limiter '{name}' has wrong number of channels. Expected {step_channels}, found {channels}."
+                );
+                return Err(ConfigError::new(&msg).into());
+            }
+            if let Some(channel) = parameters.detect_from_channel {
+                if channel >= step_channels {
+                    let msg = format!(
+                        "Limiter '{name}' detects from channel {channel}, but its pipeline step only has {step_channels} channels"
+                    );
+                    return Err(ConfigError::new(&msg).into());
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
 /// Get a vector telling which channels are actually used in the pipeline
 pub fn used_capture_channels(conf: &Configuration) -> Vec<bool> {
     if let Some(pipeline) = &conf.pipeline {
@@ -2175,3 +2883,199 @@ pub fn used_capture_channels(conf: &Configuration) -> Vec<bool> {
     let capture_channels = conf.devices.capture.channels();
     vec![true; capture_channels]
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::compressor::validate_compressor;
+    use crate::config::{
+        CompressorParameters, Filter, FinalStageParameters, LimiterParameters, RMSLimiterParameters,
+    };
+
+    #[test]
+    fn limiter_parameters_defaults() {
+        let params: LimiterParameters = serde_yaml::from_str("clip_limit: -3.0").unwrap();
+        assert_eq!(params.clip_limit, -3.0);
+        assert_eq!(params.soft_clip, None);
+        assert!(!params.soft_clip());
+        let params: LimiterParameters = serde_yaml::from_str("soft_clip: true").unwrap();
+        assert_eq!(params.clip_limit, 0.0);
+        assert!(params.soft_clip());
+    }
+
+    #[test]
+    fn limiter_parameters_roundtrip() {
+        let params: LimiterParameters =
+            serde_yaml::from_str("soft_clip: true\nclip_limit: -1.5").unwrap();
+        let yaml = serde_yaml::to_string(&params).unwrap();
+        assert_eq!(params, serde_yaml::from_str(&yaml).unwrap());
+        let json = serde_json::to_string(&params).unwrap();
+        assert_eq!(params, serde_json::from_str(&json).unwrap());
+    }
+
+    #[test]
+    fn rms_limiter_parameters_defaults() {
+        let params: RMSLimiterParameters =
+            serde_yaml::from_str("threshold: -6.0\nrms_samples: 1024").unwrap();
+        assert_eq!(params.threshold, -6.0);
+        assert_eq!(params.rms_samples, 1024);
+        assert_eq!(params.decay, 12.0);
+        assert!(!params.soft());
+        assert!(!params.wait_for_warmup());
+        assert_eq!(params.auto_threshold_target_db, None);
+        assert_eq!(params.ceiling_dbtp, None);
+        assert_eq!(params.input_gain_db(), 0.0);
+        // Required fields can't be left out
+        assert!(serde_yaml::from_str::<RMSLimiterParameters>("threshold: -6.0").is_err());
+        assert!(serde_yaml::from_str::<RMSLimiterParameters>("rms_samples: 1024").is_err());
+    }
+
+    #[test]
+    fn rms_limiter_parameters_roundtrip() {
+        let yaml = r#"
+threshold: -6.0
+rms_samples: 512
+decay: 20.0
+soft: true
+wait_for_warmup: true
+auto_threshold_target_db: -14.0
+ceiling_dbtp: -1.0
+input_gain_db: 3.0
+"#;
+        let params: RMSLimiterParameters = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(params.decay, 20.0);
+        assert!(params.soft());
+        assert!(params.wait_for_warmup());
+        assert_eq!(params.auto_threshold_target_db, Some(-14.0));
+        assert_eq!(params.ceiling_dbtp, Some(-1.0));
+        assert_eq!(params.input_gain_db(), 3.0);
+        let yaml = serde_yaml::to_string(&params).unwrap();
+        assert_eq!(params, serde_yaml::from_str(&yaml).unwrap());
+        let json = serde_json::to_string(&params).unwrap();
+        assert_eq!(params, serde_json::from_str(&json).unwrap());
+    }
+
+    #[test]
+    fn final_stage_parameters_roundtrip() {
+        let params: FinalStageParameters =
+            serde_yaml::from_str("threshold: -6.0\nrms_samples: 512\nclip_ceiling: -0.5").unwrap();
+        assert_eq!(params.decay, 12.0);
+        let yaml = serde_yaml::to_string(&params).unwrap();
+        assert_eq!(params, serde_yaml::from_str(&yaml).unwrap());
+    }
+
+    #[test]
+    fn limiter_filters_from_yaml() {
+        let yaml = r#"
+type: RMSLimiter
+parameters:
+  threshold: -6.0
+  rms_samples: 512
+"#;
+        let filter: Filter = serde_yaml::from_str(yaml).unwrap();
+        assert!(matches!(filter, Filter::RMSLimiter { .. }));
+        let yaml = r#"
+type: Limiter
+description: "clipper"
+parameters:
+  clip_limit: -1.0
+"#;
+        let filter: Filter = serde_yaml::from_str(yaml).unwrap();
+        assert!(matches!(filter, Filter::Limiter { .. }));
+        let yaml = r#"
+type: Normalizer
+parameters:
+  target_loudness_db: -16.0
+  limiter:
+    threshold: -3.0
+    rms_samples: 1024
+    ceiling_dbtp: -1.0
+"#;
+        let filter: Filter = serde_yaml::from_str(yaml).unwrap();
+        assert!(matches!(filter, Filter::Normalizer { .. }));
+        let yaml = r#"
+type: UpwardCompressor
+parameters:
+  threshold: -30.0
+  ratio: 2.0
+  max_boost_db: 12.0
+  rms_samples: 2048
+"#;
+        let filter: Filter = serde_yaml::from_str(yaml).unwrap();
+        assert!(matches!(filter, Filter::UpwardCompressor { .. }));
+        let yaml = r#"
+type: LfProtect
+parameters:
+  crossover_hz: 80.0
+  limiter:
+    clip_limit: -6.0
+"#;
+        let filter: Filter = serde_yaml::from_str(yaml).unwrap();
+        assert!(matches!(filter, Filter::LfProtect { .. }));
+    }
+
+    #[test]
+    fn reject_unknown_fields() {
+        assert!(
+            serde_yaml::from_str::<LimiterParameters>("clip_limit: -1.0\nceiling: 0.0").is_err()
+        );
+        assert!(serde_yaml::from_str::<RMSLimiterParameters>(
+            "threshold: -6.0\nrms_samples: 512\nattack: 1.0"
+        )
+        .is_err());
+        assert!(serde_yaml::from_str::<FinalStageParameters>(
+            "threshold: -6.0\nrms_samples: 512\nclip_ceiling: 0.0\nknee: 1.0"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn rms_limiter_overrides() {
+        let overrides = |pairs: &[(&str, &str)]| -> Vec<(String, String)> {
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect()
+        };
+        let mut params = RMSLimiterParameters::default();
+        params
+            .apply_overrides(&overrides(&[
+                ("threshold", "-6.5"),
+                ("decay", "20"),
+                ("rms_samples", "512"),
+            ]))
+            .unwrap();
+        assert_eq!(
+            params,
+            RMSLimiterParameters {
+                rms_samples: 512,
+                ..RMSLimiterParameters::new(-6.5, 20.0)
+            }
+        );
+
+        let expected = params.clone();
+        assert!(params
+            .apply_overrides(&overrides(&[("threshold", "-3"), ("attack", "1")]))
+            .is_err());
+        assert!(params
+            .apply_overrides(&overrides(&[("decay", "fast")]))
+            .is_err());
+        assert!(params
+            .apply_overrides(&overrides(&[("rms_samples", "-1")]))
+            .is_err());
+        assert_eq!(params, expected);
+    }
+
+    #[test]
+    fn compressor_glue_preset() {
+        let params = CompressorParameters::preset("glue", 2, -18.0).unwrap();
+        assert_eq!(params.channels, 2);
+        assert_eq!(params.threshold, -18.0);
+        assert_eq!(params.factor, 1.5);
+        assert_eq!(params.attack, 0.03);
+        assert_eq!(params.release, 0.3);
+        assert!(params.monitor_channels().is_empty());
+        assert_eq!(params.makeup_gain(), 0.0);
+        assert!(validate_compressor(&params).is_ok());
+        assert!(CompressorParameters::preset("unknown", 2, -18.0).is_none());
+    }
+}
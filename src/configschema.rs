@@ -0,0 +1,210 @@
+use serde::de::value::StrDeserializer;
+use serde::de::{self, DeserializeOwned, DeserializeSeed, IntoDeserializer, MapAccess, Visitor};
+use serde::forward_to_deserialize_any;
+use std::fmt;
+
+use crate::config;
+
+/// Description of a filter type, for example for generating a config editor.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FilterTypeInfo {
+    /// Name of the type, as used for `type` in the config.
+    pub name: &'static str,
+    pub parameters: ParameterSchema,
+}
+
+/// The parameters of a filter type.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ParameterSchema {
+    /// Names of the parameter fields.
+    Fields(Vec<&'static str>),
+    /// Names of the subtypes, for parameters that are selected by their own `type`.
+    Types(Vec<&'static str>),
+}
+
+/// The parameter names are read from the Deserialize implementations,
+/// so that they always match what the config parser accepts.
+macro_rules! filter_types {
+    ($($variant:ident => $parameters:ty),* $(,)?) => {
+        /// All filter types, with their parameters.
+        pub fn filter_types() -> Vec<FilterTypeInfo> {
+            vec![$(FilterTypeInfo {
+                name: stringify!($variant),
+                parameters: parameter_schema::<$parameters>(),
+            }),*]
+        }
+    };
+}
+
+filter_types! {
+    Conv => config::ConvParameters,
+    Biquad => config::BiquadParameters,
+    BiquadCombo => config::BiquadComboParameters,
+    Delay => config::DelayParameters,
+    Gain => config::GainParameters,
+    Volume => config::VolumeParameters,
+    Loudness => config::LoudnessParameters,
+    Dither => config::DitherParameters,
+    DiffEq => config::DiffEqParameters,
+    Limiter => config::LimiterParameters,
+    RMSLimiter => config::RMSLimiterParameters,
+    FinalStage => config::FinalStageParameters,
+    Normalizer => config::NormalizerParameters,
+    UpwardCompressor => config::UpwardCompressorParameters,
+    LfProtect => config::LfProtectParameters,
+}
+
+/// Read the parameter names of a config struct, or the subtypes of a tagged config enum.
+pub fn parameter_schema<T: DeserializeOwned>() -> ParameterSchema {
+    match T::deserialize(Probe) {
+        Err(Introspection::Fields(fields)) => ParameterSchema::Fields(fields.to_vec()),
+        Err(Introspection::Variants(variants)) => ParameterSchema::Types(variants.to_vec()),
+        _ => ParameterSchema::Fields(Vec::new()),
+    }
+}
+
+/// The result of probing a Deserialize implementation.
+#[derive(Debug)]
+enum Introspection {
+    Fields(&'static [&'static str]),
+    Variants(&'static [&'static str]),
+    Other(String),
+}
+
+impl fmt::Display for Introspection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Introspection::Fields(fields) => write!(f, "expected fields: {:?}", fields),
+            Introspection::Variants(variants) => write!(f, "expected types: {:?}", variants),
+            Introspection::Other(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for Introspection {}
+
+impl de::Error for Introspection {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Introspection::Other(msg.to_string())
+    }
+
+    fn unknown_field(_field: &str, expected: &'static [&'static str]) -> Self {
+        Introspection::Fields(expected)
+    }
+
+    fn unknown_variant(_variant: &str, expected: &'static [&'static str]) -> Self {
+        Introspection::Variants(expected)
+    }
+}
+
+// A tag value that no config type uses.
+const PROBE_TAG: &str = "\u{0}probe";
+
+/// Deserializer that fails on purpose, with an error that tells what was expected.
+/// Structs report their fields directly. Tagged enums are given a map with
+/// an unknown `type`, and report their variants when rejecting it.
+struct Probe;
+
+impl<'de> de::Deserializer<'de> for Probe {
+    type Error = Introspection;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_map(ProbeMap { done: false })
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        Err(Introspection::Fields(fields))
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        variants: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        Err(Introspection::Variants(variants))
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map identifier ignored_any
+    }
+}
+
+/// A map with only a `type` entry, holding a tag that is not known.
+struct ProbeMap {
+    done: bool,
+}
+
+impl<'de> MapAccess<'de> for ProbeMap {
+    type Error = Introspection;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Self::Error> {
+        if self.done {
+            return Ok(None);
+        }
+        self.done = true;
+        let key: StrDeserializer<Introspection> = "type".into_deserializer();
+        seed.deserialize(key).map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, Self::Error> {
+        let value: StrDeserializer<Introspection> = PROBE_TAG.into_deserializer();
+        seed.deserialize(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config;
+    use crate::configschema::{filter_types, parameter_schema, ParameterSchema};
+
+    fn fields(name: &str) -> Vec<&'static str> {
+        let info = filter_types().into_iter().find(|t| t.name == name).unwrap();
+        match info.parameters {
+            ParameterSchema::Fields(fields) => fields,
+            ParameterSchema::Types(_) => panic!("{} should have fields", name),
+        }
+    }
+
+    #[test]
+    fn all_filter_types_listed() {
+        // The list matches the variants accepted by the config parser
+        let ParameterSchema::Types(variants) = parameter_schema::<config::Filter>() else {
+            panic!("Filter should be a tagged enum");
+        };
+        let names: Vec<&str> = filter_types().iter().map(|t| t.name).collect();
+        assert_eq!(names, variants);
+    }
+
+    #[test]
+    fn limiter_parameters() {
+        assert_eq!(fields("Limiter"), vec!["soft_clip", "clip_limit"]);
+        let rms_limiter = fields("RMSLimiter");
+        for name in [
+            "threshold",
+            "rms_samples",
+            "decay",
+            "ceiling_dbtp",
+            "release_ms",
+        ] {
+            assert!(rms_limiter.contains(&name));
+        }
+        let ParameterSchema::Types(types) = parameter_schema::<config::BiquadParameters>() else {
+            panic!("Biquad parameters should have subtypes");
+        };
+        assert!(types.contains(&"Peaking"));
+    }
+}
@@ -402,6 +402,7 @@ impl PlaybackDevice for CoreaudioPlaybackDevice {
                 let mut chunk_stats = ChunkStats {
                     rms: vec![0.0; channels],
                     peak: vec![0.0; channels],
+                    peak_index: vec![0; channels],
                 };
                 let blockalign = 4 * channels;
 
@@ -790,7 +791,7 @@ impl CaptureDevice for CoreaudioCaptureDevice {
                 let mut watcher_averager = countertimer::TimeAverage::new();
                 let mut valuewatcher = countertimer::ValueWatcher::new(capture_samplerate as f32, RATE_CHANGE_THRESHOLD_VALUE, RATE_CHANGE_THRESHOLD_COUNT);
                 let mut value_range = 0.0;
-                let mut chunk_stats = ChunkStats{rms: vec![0.0; channels], peak: vec![0.0; channels]};
+                let mut chunk_stats = ChunkStats{rms: vec![0.0; channels], peak: vec![0.0; channels], peak_index: vec![0; channels]};
                 let mut rate_adjust = 0.0;
                 let mut silence_counter = countertimer::SilenceCounter::new(silence_threshold, silence_timeout, capture_samplerate, chunksize);
                 let mut state = ProcessingState::Running;
@@ -240,7 +240,7 @@ impl PlaybackDevice for CpalPlaybackDevice {
                         let buffer_fill_clone = buffer_fill.clone();
                         let mut buffer_avg = countertimer::Averager::new();
                         let mut timer = countertimer::Stopwatch::new();
-                        let mut chunk_stats = ChunkStats{rms: vec![0.0; channels], peak: vec![0.0; channels]};
+                        let mut chunk_stats = ChunkStats{rms: vec![0.0; channels], peak: vec![0.0; channels], peak_index: vec![0; channels]};
 
                         let mut rate_controller = PIRateController::new_with_default_gains(samplerate, adjust_period as f64, target_level);
 
@@ -565,7 +565,7 @@ impl CaptureDevice for CpalCaptureDevice {
                             countertimer::ValueWatcher::new(capture_samplerate as f32, RATE_CHANGE_THRESHOLD_VALUE, RATE_CHANGE_THRESHOLD_COUNT);
                         let rate_measure_interval_ms = (1000.0 * rate_measure_interval) as u64;
                         let mut value_range = 0.0;
-                        let mut chunk_stats = ChunkStats{rms: vec![0.0; channels], peak: vec![0.0; channels]};
+                        let mut chunk_stats = ChunkStats{rms: vec![0.0; channels], peak: vec![0.0; channels], peak_index: vec![0; channels]};
                         let mut rate_adjust = 0.0;
                         let mut silence_counter = countertimer::SilenceCounter::new(silence_threshold, silence_timeout, capture_samplerate, chunksize);
                         let mut state = ProcessingState::Running;
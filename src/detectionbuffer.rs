@@ -0,0 +1,155 @@
+use ringbuffer::{AllocRingBuffer, RingBuffer, RingBufferExt, RingBufferWrite};
+use std::collections::VecDeque;
+
+use crate::config::DetectionType;
+use crate::dynamics_math::{detect, weighted_rms};
+use crate::PrcFmt;
+
+/// Backing used for the detection buffer of the limiters.
+/// This is an `AllocRingBuffer`, or a `VecDequeBuffer` with the `vecdeque-buffer` feature.
+#[cfg(not(feature = "vecdeque-buffer"))]
+pub type DefaultDetectionBuffer = AllocRingBuffer<PrcFmt>;
+#[cfg(feature = "vecdeque-buffer")]
+pub type DefaultDetectionBuffer = VecDequeBuffer;
+
+/// A buffer holding the latest samples used for level detection.
+/// When full, pushing a new sample drops the oldest one.
+pub trait DetectionBuffer: Clone {
+    /// Create an empty buffer. The capacity must be a power of two.
+    fn with_capacity(capacity: usize) -> Self;
+
+    fn push(&mut self, value: PrcFmt);
+
+    fn len(&self) -> usize;
+
+    fn capacity(&self) -> usize;
+
+    /// Detect the level of the buffered samples.
+    fn level(&self, detection: DetectionType) -> PrcFmt;
+
+    /// Weighted rms of the buffered samples, where the last weight is used for the newest sample.
+    /// There must be at least as many weights as buffered samples.
+    fn weighted_level(&self, weights: &[PrcFmt]) -> PrcFmt;
+
+    /// The buffered samples, from the oldest to the newest.
+    fn to_vec(&self) -> Vec<PrcFmt>;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn is_full(&self) -> bool {
+        self.len() == self.capacity()
+    }
+}
+
+impl DetectionBuffer for AllocRingBuffer<PrcFmt> {
+    fn with_capacity(capacity: usize) -> Self {
+        AllocRingBuffer::with_capacity(capacity)
+    }
+
+    fn push(&mut self, value: PrcFmt) {
+        RingBufferWrite::push(self, value);
+    }
+
+    fn len(&self) -> usize {
+        RingBuffer::len(self)
+    }
+
+    fn capacity(&self) -> usize {
+        RingBuffer::capacity(self)
+    }
+
+    fn level(&self, detection: DetectionType) -> PrcFmt {
+        detect(RingBufferExt::iter(self), detection)
+    }
+
+    fn weighted_level(&self, weights: &[PrcFmt]) -> PrcFmt {
+        weighted_rms(RingBufferExt::iter(self), weights)
+    }
+
+    fn to_vec(&self) -> Vec<PrcFmt> {
+        RingBufferExt::to_vec(self)
+    }
+}
+
+/// Detection buffer backed by a `VecDeque` from the standard library.
+#[derive(Clone)]
+pub struct VecDequeBuffer {
+    values: VecDeque<PrcFmt>,
+    capacity: usize,
+}
+
+impl DetectionBuffer for VecDequeBuffer {
+    fn with_capacity(capacity: usize) -> Self {
+        VecDequeBuffer {
+            values: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    fn push(&mut self, value: PrcFmt) {
+        if self.values.len() == self.capacity {
+            self.values.pop_front();
+        }
+        self.values.push_back(value);
+    }
+
+    fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    fn level(&self, detection: DetectionType) -> PrcFmt {
+        detect(self.values.iter(), detection)
+    }
+
+    fn weighted_level(&self, weights: &[PrcFmt]) -> PrcFmt {
+        weighted_rms(self.values.iter(), weights)
+    }
+
+    fn to_vec(&self) -> Vec<PrcFmt> {
+        self.values.iter().copied().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::DetectionType;
+    use crate::detectionbuffer::{DetectionBuffer, VecDequeBuffer};
+    use crate::PrcFmt;
+    use ringbuffer::AllocRingBuffer;
+
+    fn fill<B: DetectionBuffer>() -> B {
+        let mut buffer = B::with_capacity(8);
+        for n in 0..11 {
+            buffer.push(n as PrcFmt);
+        }
+        buffer
+    }
+
+    #[test]
+    fn backends_are_equivalent() {
+        let ring: AllocRingBuffer<PrcFmt> = fill();
+        let deque: VecDequeBuffer = fill();
+        assert!(ring.is_full() && deque.is_full());
+        assert_eq!(ring.to_vec(), vec![3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0, 10.0]);
+        assert_eq!(ring.to_vec(), deque.to_vec());
+        for detection in [
+            DetectionType::Rms,
+            DetectionType::Peak,
+            DetectionType::RmsOfPeaks,
+            DetectionType::LogRms,
+        ] {
+            assert_eq!(ring.level(detection), deque.level(detection));
+        }
+        let weights: Vec<PrcFmt> = (1..=8).map(|n| n as PrcFmt).collect();
+        assert_eq!(
+            ring.weighted_level(&weights),
+            deque.weighted_level(&weights)
+        );
+    }
+}
@@ -99,6 +99,10 @@ impl Filter for DiffEq {
         &self.name
     }
 
+    fn filter_type(&self) -> &'static str {
+        "DiffEq"
+    }
+
     fn process_waveform(&mut self, waveform: &mut [PrcFmt]) -> Res<()> {
         for item in waveform.iter_mut() {
             *item = self.process_single(*item);
@@ -1,5 +1,6 @@
 use circular_queue::CircularQueue;
-use rand::{rngs::SmallRng, SeedableRng};
+use rand::{rngs::SmallRng, RngCore, SeedableRng};
+use rand_chacha::ChaCha8Rng;
 use rand_distr::{Distribution, Triangular, Uniform};
 
 use crate::{config, filters::Filter, NewValue, PrcFmt, Res};
@@ -472,79 +473,84 @@ impl<'a> Dither<'a> {
 
     pub fn from_config(name: &str, conf: config::DitherParameters) -> Self {
         let (bits, shaper) = match conf {
-            config::DitherParameters::None { bits } => (bits, None),
+            config::DitherParameters::None { bits, .. } => (bits, None),
             config::DitherParameters::Flat { bits, .. } => (bits, None),
-            config::DitherParameters::Highpass { bits } => (bits, None),
-            config::DitherParameters::Fweighted441 { bits } => {
+            config::DitherParameters::Highpass { bits, .. } => (bits, None),
+            config::DitherParameters::Fweighted441 { bits, .. } => {
                 (bits, Some(NoiseShaper::fweighted_441()))
             }
-            config::DitherParameters::FweightedLong441 { bits } => {
+            config::DitherParameters::FweightedLong441 { bits, .. } => {
                 (bits, Some(NoiseShaper::fweighted_long_441()))
             }
-            config::DitherParameters::FweightedShort441 { bits } => {
+            config::DitherParameters::FweightedShort441 { bits, .. } => {
                 (bits, Some(NoiseShaper::fweighted_short_441()))
             }
-            config::DitherParameters::Gesemann441 { bits } => {
+            config::DitherParameters::Gesemann441 { bits, .. } => {
                 (bits, Some(NoiseShaper::gesemann_441()))
             }
-            config::DitherParameters::Gesemann48 { bits } => {
+            config::DitherParameters::Gesemann48 { bits, .. } => {
                 (bits, Some(NoiseShaper::gesemann_48()))
             }
-            config::DitherParameters::Lipshitz441 { bits } => {
+            config::DitherParameters::Lipshitz441 { bits, .. } => {
                 (bits, Some(NoiseShaper::lipshitz_441()))
             }
-            config::DitherParameters::LipshitzLong441 { bits } => {
+            config::DitherParameters::LipshitzLong441 { bits, .. } => {
                 (bits, Some(NoiseShaper::lipshitz_long_441()))
             }
-            config::DitherParameters::Shibata441 { bits } => {
+            config::DitherParameters::Shibata441 { bits, .. } => {
                 (bits, Some(NoiseShaper::shibata_441()))
             }
-            config::DitherParameters::ShibataHigh441 { bits } => {
+            config::DitherParameters::ShibataHigh441 { bits, .. } => {
                 (bits, Some(NoiseShaper::shibata_high_441()))
             }
-            config::DitherParameters::ShibataLow441 { bits } => {
+            config::DitherParameters::ShibataLow441 { bits, .. } => {
                 (bits, Some(NoiseShaper::shibata_low_441()))
             }
-            config::DitherParameters::Shibata48 { bits } => (bits, Some(NoiseShaper::shibata_48())),
-            config::DitherParameters::ShibataHigh48 { bits } => {
+            config::DitherParameters::Shibata48 { bits, .. } => {
+                (bits, Some(NoiseShaper::shibata_48()))
+            }
+            config::DitherParameters::ShibataHigh48 { bits, .. } => {
                 (bits, Some(NoiseShaper::shibata_high_48()))
             }
-            config::DitherParameters::ShibataLow48 { bits } => {
+            config::DitherParameters::ShibataLow48 { bits, .. } => {
                 (bits, Some(NoiseShaper::shibata_low_48()))
             }
-            config::DitherParameters::Shibata882 { bits } => {
+            config::DitherParameters::Shibata882 { bits, .. } => {
                 (bits, Some(NoiseShaper::shibata_882()))
             }
-            config::DitherParameters::ShibataLow882 { bits } => {
+            config::DitherParameters::ShibataLow882 { bits, .. } => {
                 (bits, Some(NoiseShaper::shibata_low_882()))
             }
-            config::DitherParameters::Shibata96 { bits } => (bits, Some(NoiseShaper::shibata_96())),
-            config::DitherParameters::ShibataLow96 { bits } => {
+            config::DitherParameters::Shibata96 { bits, .. } => {
+                (bits, Some(NoiseShaper::shibata_96()))
+            }
+            config::DitherParameters::ShibataLow96 { bits, .. } => {
                 (bits, Some(NoiseShaper::shibata_low_96()))
             }
-            config::DitherParameters::Shibata192 { bits } => {
+            config::DitherParameters::Shibata192 { bits, .. } => {
                 (bits, Some(NoiseShaper::shibata_192()))
             }
-            config::DitherParameters::ShibataLow192 { bits } => {
+            config::DitherParameters::ShibataLow192 { bits, .. } => {
                 (bits, Some(NoiseShaper::shibata_low_192()))
             }
         };
 
+        let seed = conf.seed();
         match conf {
             config::DitherParameters::None { .. } => {
                 let noop = NoopDitherer;
                 Self::new(name, bits, noop, shaper)
             }
             config::DitherParameters::Flat { amplitude, .. } => {
-                let tpdf = <TriangularDitherer as Ditherer>::new(amplitude);
+                let tpdf = <TriangularDitherer as Ditherer>::new(amplitude, seed);
                 Self::new(name, bits, tpdf, shaper)
             }
             config::DitherParameters::Highpass { .. } => {
-                let hp_tpdf = HighpassDitherer::default();
+                let hp_tpdf = <HighpassDitherer as Ditherer>::new(2.0, seed);
                 Self::new(name, bits, hp_tpdf, shaper)
             }
             _ => {
-                let tpdf = TriangularDitherer::default();
+                let tpdf = <TriangularDitherer as Ditherer>::new(2.0, seed);
                 Self::new(name, bits, tpdf, shaper)
             }
         }
@@ -556,6 +562,10 @@ impl Filter for Dither<'_> {
         &self.name
     }
 
+    fn filter_type(&self) -> &'static str {
+        "Dither"
+    }
+
     fn process_waveform(&mut self, waveform: &mut [PrcFmt]) -> Res<()> {
         for item in waveform.iter_mut() {
             let scaled = *item * self.scalefact;
@@ -587,28 +597,28 @@ impl Filter for Dither<'_> {
 /// Validate a Dither config.
 pub fn validate_config(conf: &config::DitherParameters) -> Res<()> {
     let bits = match conf {
-        config::DitherParameters::None { bits }
+        config::DitherParameters::None { bits, .. }
         | config::DitherParameters::Flat { bits, .. }
-        | config::DitherParameters::Highpass { bits }
-        | config::DitherParameters::Fweighted441 { bits }
-        | config::DitherParameters::FweightedLong441 { bits }
-        | config::DitherParameters::FweightedShort441 { bits }
-        | config::DitherParameters::Gesemann441 { bits }
-        | config::DitherParameters::Gesemann48 { bits }
-        | config::DitherParameters::Lipshitz441 { bits }
-        | config::DitherParameters::LipshitzLong441 { bits }
-        | config::DitherParameters::Shibata441 { bits }
-        | config::DitherParameters::ShibataHigh441 { bits }
-        | config::DitherParameters::ShibataLow441 { bits }
-        | config::DitherParameters::Shibata48 { bits }
-        | config::DitherParameters::ShibataHigh48 { bits }
-        | config::DitherParameters::ShibataLow48 { bits }
-        | config::DitherParameters::Shibata882 { bits }
-        | config::DitherParameters::ShibataLow882 { bits }
-        | config::DitherParameters::Shibata96 { bits }
-        | config::DitherParameters::ShibataLow96 { bits }
-        | config::DitherParameters::Shibata192 { bits }
-        | config::DitherParameters::ShibataLow192 { bits } => bits,
+        | config::DitherParameters::Highpass { bits, .. }
+        | config::DitherParameters::Fweighted441 { bits, .. }
+        | config::DitherParameters::FweightedLong441 { bits, .. }
+        | config::DitherParameters::FweightedShort441 { bits, .. }
+        | config::DitherParameters::Gesemann441 { bits, .. }
+        | config::DitherParameters::Gesemann48 { bits, .. }
+        | config::DitherParameters::Lipshitz441 { bits, .. }
+        | config::DitherParameters::LipshitzLong441 { bits, .. }
+        | config::DitherParameters::Shibata441 { bits, .. }
+        | config::DitherParameters::ShibataHigh441 { bits, .. }
+        | config::DitherParameters::ShibataLow441 { bits, .. }
+        | config::DitherParameters::Shibata48 { bits, .. }
+        | config::DitherParameters::ShibataHigh48 { bits, .. }
+        | config::DitherParameters::ShibataLow48 { bits, .. }
+        | config::DitherParameters::Shibata882 { bits, .. }
+        | config::DitherParameters::ShibataLow882 { bits, .. }
+        | config::DitherParameters::Shibata96 { bits, .. }
+        | config::DitherParameters::ShibataLow96 { bits, .. }
+        | config::DitherParameters::Shibata192 { bits, .. }
+        | config::DitherParameters::ShibataLow192 { bits, .. } => bits,
     };
     if *bits <= 1 {
         return Err(config::ConfigError::new("Dither bit depth must be at least 2").into());
@@ -629,8 +639,8 @@ pub fn validate_config(conf: &config::DitherParameters) -> Res<()> {
 // Ditherer, TriangularDitherer, HighpassDitherer adopted from librespot,
 // which is licensed under MIT. Used with permission.
 pub trait Ditherer {
-    // `amplitude` in bits
-    fn new(amplitude: PrcFmt) -> Self
+    // `amplitude` in bits, `seed` gives a reproducible sequence
+    fn new(amplitude: PrcFmt, seed: Option<u64>) -> Self
     where
         Self: Sized;
 
@@ -640,8 +650,52 @@ pub trait Ditherer {
 // Deterministic and not cryptographically secure, but fast and with excellent
 // randomness. Must be cached not only to increase performance, but more
 // importantly: keep state and not repeat the same sequences.
-fn create_rng() -> SmallRng {
-    SmallRng::from_entropy()
+// A seed makes the sequence repeat, for reproducible renders.
+// The sequence of SmallRng depends on the platform and the version of rand,
+// so a seeded sequence uses ChaCha8 instead, which gives the same sequence everywhere.
+// Each ditherer holds a single generator, so the size difference of the variants does not matter.
+#[allow(clippy::large_enum_variant)]
+#[derive(Clone, Debug)]
+enum DitherRng {
+    Small(SmallRng),
+    Seeded(ChaCha8Rng),
+}
+
+impl RngCore for DitherRng {
+    fn next_u32(&mut self) -> u32 {
+        match self {
+            DitherRng::Small(rng) => rng.next_u32(),
+            DitherRng::Seeded(rng) => rng.next_u32(),
+        }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        match self {
+            DitherRng::Small(rng) => rng.next_u64(),
+            DitherRng::Seeded(rng) => rng.next_u64(),
+        }
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        match self {
+            DitherRng::Small(rng) => rng.fill_bytes(dest),
+            DitherRng::Seeded(rng) => rng.fill_bytes(dest),
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        match self {
+            DitherRng::Small(rng) => rng.try_fill_bytes(dest),
+            DitherRng::Seeded(rng) => rng.try_fill_bytes(dest),
+        }
+    }
+}
+
+fn create_rng(seed: Option<u64>) -> DitherRng {
+    match seed {
+        Some(seed) => DitherRng::Seeded(ChaCha8Rng::seed_from_u64(seed)),
+        None => DitherRng::Small(SmallRng::from_entropy()),
+    }
 }
 
 // Spectrally-white triangular-pdf (TPDF) dither.
@@ -651,15 +705,15 @@ fn create_rng() -> SmallRng {
 // A Theory of Non-Subtractive Dither. University of Waterloo.
 #[derive(Clone, Debug)]
 pub struct TriangularDitherer {
-    cached_rng: SmallRng,
+    cached_rng: DitherRng,
     distribution: Triangular<PrcFmt>,
 }
 
 impl Ditherer for TriangularDitherer {
-    fn new(amplitude: PrcFmt) -> Self {
+    fn new(amplitude: PrcFmt, seed: Option<u64>) -> Self {
         let amplitude = amplitude / 2.0; // negative to positive peak
         Self {
-            cached_rng: create_rng(),
+            cached_rng: create_rng(seed),
             distribution: Triangular::new(-amplitude, amplitude, 0.0).unwrap(),
         }
     }
@@ -672,7 +726,7 @@ impl Ditherer for TriangularDitherer {
 impl Default for TriangularDitherer {
     fn default() -> Self {
         // 2 LSB linearizes the response.
-        <Self as Ditherer>::new(2.0)
+        <Self as Ditherer>::new(2.0, None)
     }
 }
 
@@ -687,7 +741,7 @@ impl Default for TriangularDitherer {
 // A Theory of Non-Subtractive Dither. University of Waterloo.
 #[derive(Clone, Debug)]
 pub struct HighpassDitherer {
-    cached_rng: SmallRng,
+    cached_rng: DitherRng,
     previous_sample: PrcFmt,
 
     // optimization: makes sampling of multiple values faster
@@ -696,11 +750,11 @@ pub struct HighpassDitherer {
 }
 
 impl Ditherer for HighpassDitherer {
-    fn new(amplitude: PrcFmt) -> Self {
+    fn new(amplitude: PrcFmt, seed: Option<u64>) -> Self {
         // 2x RDPF (current - previous) makes 1x TDPF
         let amplitude = amplitude / 2.0;
         Self {
-            cached_rng: create_rng(),
+            cached_rng: create_rng(seed),
             distribution: Uniform::new_inclusive(-amplitude, amplitude),
             previous_sample: 0.0,
         }
@@ -717,7 +771,7 @@ impl Ditherer for HighpassDitherer {
 impl Default for HighpassDitherer {
     fn default() -> Self {
         // 1 LSB - 1 LSB (previous) = 2 LSB
-        <Self as Ditherer>::new(2.0)
+        <Self as Ditherer>::new(2.0, None)
     }
 }
 
@@ -728,7 +782,7 @@ impl Default for HighpassDitherer {
 pub struct NoopDitherer;
 
 impl Ditherer for NoopDitherer {
-    fn new(_amplitude: PrcFmt) -> Self {
+    fn new(_amplitude: PrcFmt, _seed: Option<u64>) -> Self {
         Self {}
     }
 
@@ -739,13 +793,19 @@ impl Ditherer for NoopDitherer {
 
 impl Default for NoopDitherer {
     fn default() -> Self {
-        <Self as Ditherer>::new(0.0)
+        <Self as Ditherer>::new(0.0, None)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{config::DitherParameters, dither::Dither, filters::Filter, PrcFmt};
+    use crate::{
+        config::DitherParameters,
+        dither::{create_rng, Dither},
+        filters::Filter,
+        PrcFmt,
+    };
+    use rand::RngCore;
 
     fn is_close(left: PrcFmt, right: PrcFmt, maxdiff: PrcFmt) -> bool {
         println!("{left} - {right}");
@@ -765,7 +825,10 @@ mod tests {
     fn test_quantize() {
         let mut waveform = vec![-1.0, -0.5, -1.0 / 3.0, 0.0, 1.0 / 3.0, 0.5, 1.0];
         let waveform2 = waveform.clone();
-        let conf = DitherParameters::None { bits: 8 };
+        let conf = DitherParameters::None {
+            bits: 8,
+            seed: None,
+        };
         let mut dith = Dither::from_config("test", conf);
         dith.process_waveform(&mut waveform).unwrap();
         assert!(compare_waveforms(waveform.clone(), waveform2, 1.0 / 128.0));
@@ -783,6 +846,7 @@ mod tests {
         let conf = DitherParameters::Flat {
             bits: 8,
             amplitude: 2.0,
+            seed: None,
         };
         let mut dith = Dither::from_config("test", conf);
         dith.process_waveform(&mut waveform).unwrap();
@@ -798,7 +862,10 @@ mod tests {
     fn test_high_pass() {
         let mut waveform = vec![-1.0, -0.5, -1.0 / 3.0, 0.0, 1.0 / 3.0, 0.5, 1.0];
         let waveform2 = waveform.clone();
-        let conf = DitherParameters::Highpass { bits: 8 };
+        let conf = DitherParameters::Highpass {
+            bits: 8,
+            seed: None,
+        };
         let mut dith = Dither::from_config("test", conf);
         dith.process_waveform(&mut waveform).unwrap();
         assert!(compare_waveforms(waveform.clone(), waveform2, 1.0 / 32.0));
@@ -813,7 +880,10 @@ mod tests {
     fn test_lip() {
         let mut waveform = vec![-1.0, -0.5, -1.0 / 3.0, 0.0, 1.0 / 3.0, 0.5, 1.0];
         let waveform2 = waveform.clone();
-        let conf = DitherParameters::Lipshitz441 { bits: 8 };
+        let conf = DitherParameters::Lipshitz441 {
+            bits: 8,
+            seed: None,
+        };
         let mut dith = Dither::from_config("test", conf);
         dith.process_waveform(&mut waveform).unwrap();
         assert!(compare_waveforms(waveform.clone(), waveform2, 1.0 / 16.0));
@@ -823,4 +893,26 @@ mod tests {
             1e-9
         ));
     }
+
+    #[test]
+    fn seeded_dither_is_reproducible() {
+        let render = |seed: u64| -> Vec<PrcFmt> {
+            let conf = DitherParameters::Shibata441 {
+                bits: 8,
+                seed: Some(seed),
+            };
+            let mut dith = Dither::from_config("test", conf);
+            let mut waveform: Vec<PrcFmt> = (0..256).map(|n| 0.01 * n as PrcFmt).collect();
+            dith.process_waveform(&mut waveform).unwrap();
+            waveform
+        };
+        assert_eq!(render(1234), render(1234));
+        assert_ne!(render(1234), render(4321));
+    }
+
+    #[test]
+    fn seeded_sequence_is_fixed() {
+        let mut rng = create_rng(Some(1234));
+        assert_eq!(rng.next_u64(), 7127326097029154973);
+    }
 }
@@ -0,0 +1,557 @@
+use crate::config::{DetectionType, WindowFunction};
+use crate::PrcFmt;
+
+// Oversampling factor used for true peak estimation.
+pub const TRUE_PEAK_OVERSAMPLING: usize = 4;
+// Number of samples on each side of an interpolated point used by the interpolator.
+pub const TRUE_PEAK_HALF_TAPS: isize = 8;
+// Length of the blocks whose peaks are combined by the RmsOfPeaks detector.
+const PEAK_BLOCK_SIZE: usize = 32;
+// Gain change in dB that a release time refers to.
+const RELEASE_REFERENCE_DB: PrcFmt = 10.0;
+// Fraction of the ceiling where the soft clipper starts rounding off the peaks.
+const SOFT_CLIP_KNEE: PrcFmt = 0.7;
+// Values below this are flushed to zero. Low enough to be inaudible,
+// and high enough that also its square is a normal number with 32-bit floats.
+const DENORMAL_LIMIT: PrcFmt = 1.0e-18;
+
+/// Flush a tiny value to zero, to avoid denormal numbers that are very slow on some CPUs.
+#[inline]
+pub fn flush_denormal(value: PrcFmt) -> PrcFmt {
+    if value.abs() < DENORMAL_LIMIT {
+        0.0
+    } else {
+        value
+    }
+}
+
+/// Convert a level in dB to a voltage ratio.
+pub fn db_to_voltage_ratio(db: PrcFmt) -> PrcFmt {
+    (10.0 as PrcFmt).powf(db / 20.0)
+}
+
+/// Convert a voltage ratio to a level in dB.
+pub fn voltage_ratio_to_db(voltage_ratio: PrcFmt) -> PrcFmt {
+    20.0 * voltage_ratio.log10()
+}
+
+/// Duration of a chunk in seconds.
+pub fn chunk_duration(chunksize: usize, samplerate: usize) -> PrcFmt {
+    chunksize as PrcFmt / samplerate as PrcFmt
+}
+
+/// Coefficient for a one-pole smoother with the given time constant, updated once per chunk.
+pub fn chunk_coeff(time_constant: PrcFmt, chunksize: usize, samplerate: usize) -> PrcFmt {
+    (-chunk_duration(chunksize, samplerate) / time_constant).exp()
+}
+
+/// Gain factor to apply once per chunk, for a gain that changes by `db_per_s` dB per second.
+pub fn decay_coeff(db_per_s: PrcFmt, chunksize: usize, samplerate: usize) -> PrcFmt {
+    db_to_voltage_ratio(db_per_s * chunk_duration(chunksize, samplerate))
+}
+
+/// Convert a release time in milliseconds to a decay in dB per second.
+/// The release time is the time needed to recover from 10 dB of gain reduction.
+pub fn release_ms_to_db_per_s(release_ms: PrcFmt) -> PrcFmt {
+    1000.0 * RELEASE_REFERENCE_DB / release_ms
+}
+
+/// Convert a decay in dB per second to a release time in milliseconds,
+/// the inverse of `release_ms_to_db_per_s`.
+pub fn db_per_s_to_release_ms(db_per_s: PrcFmt) -> PrcFmt {
+    1000.0 * RELEASE_REFERENCE_DB / db_per_s
+}
+
+/// Convert a note value, as a fraction of a whole note, to a time in milliseconds
+/// at a tempo in beats per minute, where a beat is a quarter note.
+pub fn note_to_ms(fraction: PrcFmt, bpm: PrcFmt) -> PrcFmt {
+    4.0 * fraction * 60000.0 / bpm
+}
+
+/// Detect the level of a sequence of samples as a voltage ratio.
+/// - `Rms`: root mean square of all samples.
+/// - `Peak`: largest absolute sample value.
+/// - `RmsOfPeaks`: root mean square of the peaks of consecutive blocks of samples.
+/// - `LogRms`: root mean square, with the squares accumulated in the log domain.
+///
+/// An empty sequence gives a level of zero.
+pub fn detect<'a>(values: impl Iterator<Item = &'a PrcFmt>, detection: DetectionType) -> PrcFmt {
+    match detection {
+        DetectionType::Rms => rms(values),
+        DetectionType::LogRms => log_rms(values),
+        DetectionType::Peak => values.fold(0.0, |acc: PrcFmt, val| acc.max(val.abs())),
+        DetectionType::RmsOfPeaks => {
+            let mut squared_sum: PrcFmt = 0.0;
+            let mut blocks: usize = 0;
+            let mut block_peak: PrcFmt = 0.0;
+            for (n, val) in values.enumerate() {
+                block_peak = block_peak.max(val.abs());
+                if (n + 1) % PEAK_BLOCK_SIZE == 0 {
+                    squared_sum += block_peak * block_peak;
+                    blocks += 1;
+                    block_peak = 0.0;
+                }
+            }
+            if block_peak > 0.0 {
+                squared_sum += block_peak * block_peak;
+                blocks += 1;
+            }
+            if blocks == 0 {
+                return 0.0;
+            }
+            (squared_sum / blocks as PrcFmt).sqrt()
+        }
+    }
+}
+
+/// Root mean square of a sequence of samples, zero if there are none.
+pub fn rms<'a>(values: impl Iterator<Item = &'a PrcFmt>) -> PrcFmt {
+    let mut squared_sum: PrcFmt = 0.0;
+    let mut count: usize = 0;
+    for val in values {
+        squared_sum += val * val;
+        count += 1;
+    }
+    if count == 0 {
+        return 0.0;
+    }
+    (squared_sum / count as PrcFmt).sqrt()
+}
+
+/// Root mean square of a sequence of samples, zero if there are none.
+/// The sum of squares is accumulated as a logarithm, so that very quiet samples
+/// keep their resolution even when their squares would underflow.
+/// This is slower than `rms`, and is meant for detection at low levels.
+pub fn log_rms<'a>(values: impl Iterator<Item = &'a PrcFmt>) -> PrcFmt {
+    let mut log_sum = PrcFmt::NEG_INFINITY;
+    let mut count: usize = 0;
+    for val in values {
+        count += 1;
+        if *val == 0.0 {
+            continue;
+        }
+        let log_square = 2.0 * val.abs().ln();
+        // ln(exp(a) + exp(b)), evaluated relative to the larger of the terms
+        let (larger, smaller) = if log_square > log_sum {
+            (log_square, log_sum)
+        } else {
+            (log_sum, log_square)
+        };
+        log_sum = larger + (smaller - larger).exp().ln_1p();
+    }
+    if count == 0 || log_sum == PrcFmt::NEG_INFINITY {
+        return 0.0;
+    }
+    (0.5 * (log_sum - (count as PrcFmt).ln())).exp()
+}
+
+/// Coefficients of a detection window of `len` samples, from the oldest sample to the newest.
+/// The Hann and Hamming windows use their rising half, reaching one at the newest sample.
+pub fn window_coefficients(window: WindowFunction, len: usize) -> Vec<PrcFmt> {
+    let (a0, a1) = match window {
+        WindowFunction::Rectangular => return vec![1.0; len],
+        WindowFunction::Hann => (0.5, 0.5),
+        WindowFunction::Hamming => (0.54, 0.46),
+    };
+    (0..len)
+        .map(|n| {
+            a0 - a1 * (std::f64::consts::PI as PrcFmt * (n + 1) as PrcFmt / len as PrcFmt).cos()
+        })
+        .collect()
+}
+
+/// Weighted root mean square of a sequence of samples, zero if there are none.
+/// The weights are aligned to the end of the sequence,
+/// so that the last weight is used for the last sample.
+/// There must be at least as many weights as samples.
+pub fn weighted_rms<'a>(
+    values: impl ExactSizeIterator<Item = &'a PrcFmt>,
+    weights: &[PrcFmt],
+) -> PrcFmt {
+    let weights = &weights[weights.len() - values.len()..];
+    let mut squared_sum: PrcFmt = 0.0;
+    let mut weight_sum: PrcFmt = 0.0;
+    for (val, weight) in values.zip(weights.iter()) {
+        squared_sum += weight * val * val;
+        weight_sum += weight;
+    }
+    if weight_sum <= 0.0 {
+        return 0.0;
+    }
+    (squared_sum / weight_sum).sqrt()
+}
+
+/// Calculate a gain envelope that keeps every sample of the waveform within the ceiling,
+/// with as little gain reduction as possible.
+/// Each sample first gets the gain that brings it exactly to the ceiling.
+/// The envelope then ramps linearly from unity down to that gain over the `lookahead`
+/// samples before the peak, and the lowest of all overlapping ramps is used.
+/// Compared to holding the lowest gain of the whole look-ahead window,
+/// the gain only goes as far down as needed at each point in time.
+/// This is a helper for a single slice, there is no delay line and nothing is carried
+/// over between calls. A peak near the start of the slice therefore gets a shorter ramp,
+/// or none at all. The release after a peak is left to the caller.
+pub fn lookahead_gain_envelope(
+    waveform: &[PrcFmt],
+    ceiling: PrcFmt,
+    lookahead: usize,
+) -> Vec<PrcFmt> {
+    let required: Vec<PrcFmt> = waveform
+        .iter()
+        .map(|val| {
+            if val.abs() > ceiling {
+                ceiling / val.abs()
+            } else {
+                1.0
+            }
+        })
+        .collect();
+    let mut envelope = required.clone();
+    // Smooth backwards, each peak pulls down the gain of the samples before it
+    for (n, gain) in required.iter().enumerate() {
+        if *gain >= 1.0 {
+            continue;
+        }
+        for distance in 1..=lookahead.min(n) {
+            let ramp = gain + (1.0 - gain) * distance as PrcFmt / (lookahead + 1) as PrcFmt;
+            let value = &mut envelope[n - distance];
+            if ramp < *value {
+                *value = ramp;
+            }
+        }
+    }
+    envelope
+}
+
+/// Scale down the samples that exceed the ceiling, and leave all other samples untouched.
+/// The gain of each shaved sample is the lowest of the linear ramps, over `smoothing` samples
+/// on both sides, towards the gains needed by the peaks around it.
+/// This rounds off the shaved peaks instead of clipping them flat.
+/// Like `lookahead_gain_envelope`, only the samples of the given slice are considered.
+/// The gains are calculated in `gains`, which is reused to avoid allocating for each call.
+pub fn shave_peaks(
+    waveform: &mut [PrcFmt],
+    ceiling: PrcFmt,
+    smoothing: usize,
+    gains: &mut Vec<PrcFmt>,
+) {
+    let required = |val: PrcFmt| {
+        if val.abs() > ceiling {
+            ceiling / val.abs()
+        } else {
+            1.0
+        }
+    };
+    let len = waveform.len();
+    gains.clear();
+    gains.extend(waveform.iter().map(|val| required(*val)));
+    for (n, val) in waveform.iter().enumerate() {
+        // The gains are lowered by the ramps, so the required gain is taken from the waveform
+        let gain = required(*val);
+        if gain >= 1.0 {
+            continue;
+        }
+        for distance in 1..=smoothing {
+            let ramp = gain + (1.0 - gain) * distance as PrcFmt / (smoothing + 1) as PrcFmt;
+            for idx in [
+                n.checked_sub(distance),
+                Some(n + distance).filter(|idx| *idx < len),
+            ]
+            .into_iter()
+            .flatten()
+            {
+                if ramp < gains[idx] {
+                    gains[idx] = ramp;
+                }
+            }
+        }
+    }
+    for (value, gain) in waveform.iter_mut().zip(gains.iter()) {
+        if value.abs() > ceiling {
+            *value *= gain;
+        }
+    }
+}
+
+/// Rough number of multiply-adds per sample for a true peak estimate at an oversampling factor.
+pub fn true_peak_cost(oversampling: usize) -> f64 {
+    oversampling.saturating_sub(1) as f64 * 2.0 * TRUE_PEAK_HALF_TAPS as f64
+}
+
+/// Soft clip a sample at a ceiling.
+/// Values up to 70% of the ceiling are passed unchanged, and louder values
+/// follow a tanh curve that approaches the ceiling without reaching it.
+pub fn soft_clip(value: PrcFmt, ceiling: PrcFmt) -> PrcFmt {
+    let knee = SOFT_CLIP_KNEE * ceiling;
+    let magnitude = value.abs();
+    if magnitude <= knee {
+        return value;
+    }
+    let range = ceiling - knee;
+    let clipped = knee + range * ((magnitude - knee) / range).tanh();
+    clipped.copysign(value)
+}
+
+/// Estimate the true (inter-sample) peak of a waveform as a voltage ratio.
+/// The waveform is oversampled four times with a Hann-windowed sinc interpolator.
+/// Points near the ends of the slice are interpolated using only the available samples.
+pub fn true_peak(waveform: &[PrcFmt]) -> PrcFmt {
+    let mut coeffs = Vec::with_capacity(TRUE_PEAK_OVERSAMPLING - 1);
+    for phase in 1..TRUE_PEAK_OVERSAMPLING {
+        let fraction = phase as PrcFmt / TRUE_PEAK_OVERSAMPLING as PrcFmt;
+        let phase_coeffs: Vec<PrcFmt> = (-TRUE_PEAK_HALF_TAPS + 1..=TRUE_PEAK_HALF_TAPS)
+            .map(|tap| {
+                let x = tap as PrcFmt - fraction;
+                let window = 0.5
+                    * (1.0
+                        + (std::f64::consts::PI as PrcFmt * x / TRUE_PEAK_HALF_TAPS as PrcFmt)
+                            .cos());
+                let px = std::f64::consts::PI as PrcFmt * x;
+                window * px.sin() / px
+            })
+            .collect();
+        coeffs.push(phase_coeffs);
+    }
+    let len = waveform.len() as isize;
+    let mut peak = waveform
+        .iter()
+        .fold(0.0, |acc: PrcFmt, val| acc.max(val.abs()));
+    for n in 0..(len - 1) {
+        for phase_coeffs in coeffs.iter() {
+            let mut value = 0.0;
+            for (tap, coeff) in (-TRUE_PEAK_HALF_TAPS + 1..=TRUE_PEAK_HALF_TAPS).zip(phase_coeffs) {
+                let idx = n + tap;
+                if idx >= 0 && idx < len {
+                    value += waveform[idx as usize] * coeff;
+                }
+            }
+            peak = peak.max(value.abs());
+        }
+    }
+    peak
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::DetectionType;
+    use crate::dynamics_math::{
+        db_per_s_to_release_ms, db_to_voltage_ratio, decay_coeff, detect, flush_denormal, log_rms,
+        lookahead_gain_envelope, release_ms_to_db_per_s, rms, shave_peaks, true_peak,
+        voltage_ratio_to_db,
+    };
+    use crate::PrcFmt;
+
+    fn is_close(left: PrcFmt, right: PrcFmt, maxdiff: PrcFmt) -> bool {
+        println!("{left} - {right}");
+        (left - right).abs() < maxdiff
+    }
+
+    #[test]
+    fn db_ratio_roundtrip() {
+        assert!(is_close(db_to_voltage_ratio(0.0), 1.0, 1.0e-9));
+        assert!(is_close(db_to_voltage_ratio(-20.0), 0.1, 1.0e-9));
+        assert!(is_close(db_to_voltage_ratio(6.0), 1.9953, 1.0e-4));
+        for db in [-120.0, -40.5, -3.0, 0.0, 12.0] {
+            assert!(is_close(
+                voltage_ratio_to_db(db_to_voltage_ratio(db)),
+                db,
+                1.0e-6
+            ));
+        }
+    }
+
+    #[test]
+    fn known_decay_coeffs() {
+        // 20 dB/s with chunks of 0.1 s gives 2 dB per chunk
+        assert!(is_close(decay_coeff(20.0, 4800, 48000), 1.2589, 1.0e-4));
+        // 40 dB/s with chunks of 0.5 s gives 20 dB per chunk
+        assert!(is_close(decay_coeff(40.0, 22050, 44100), 10.0, 1.0e-6));
+        assert!(is_close(decay_coeff(0.0, 1024, 44100), 1.0, 1.0e-9));
+    }
+
+    #[test]
+    fn release_time_conversions() {
+        // Recovering 10 dB in 500 ms is 20 dB/s
+        assert!(is_close(release_ms_to_db_per_s(500.0), 20.0, 1.0e-6));
+        assert!(is_close(db_per_s_to_release_ms(12.0), 833.333, 1.0e-3));
+        assert!(is_close(db_per_s_to_release_ms(100.0), 100.0, 1.0e-6));
+        for ms in [1.0, 50.0, 333.0, 2500.0] {
+            assert!(is_close(
+                db_per_s_to_release_ms(release_ms_to_db_per_s(ms)),
+                ms,
+                1.0e-3
+            ));
+        }
+    }
+
+    #[test]
+    fn true_peak_between_samples() {
+        // A sine at fs/4 with 45 degree phase has all samples at +-0.707, but a peak of 1.0
+        let waveform: Vec<PrcFmt> = (0..256)
+            .map(|n| (std::f64::consts::PI as PrcFmt * (0.5 * n as PrcFmt + 0.25)).sin())
+            .collect();
+        let sample_peak = waveform
+            .iter()
+            .fold(0.0, |acc: PrcFmt, val| acc.max(val.abs()));
+        assert!(is_close(
+            sample_peak,
+            std::f64::consts::FRAC_1_SQRT_2 as PrcFmt,
+            1.0e-4
+        ));
+        assert!(is_close(true_peak(&waveform), 1.0, 0.02));
+    }
+
+    #[test]
+    fn detection_types() {
+        // Square wave with amplitude 0.5
+        let square: Vec<PrcFmt> = (0..128)
+            .map(|n| if n % 2 == 0 { 0.5 } else { -0.5 })
+            .collect();
+        assert!(is_close(
+            detect(square.iter(), DetectionType::Rms),
+            0.5,
+            1.0e-9
+        ));
+        assert!(is_close(
+            detect(square.iter(), DetectionType::Peak),
+            0.5,
+            1.0e-9
+        ));
+        assert!(is_close(
+            detect(square.iter(), DetectionType::RmsOfPeaks),
+            0.5,
+            1.0e-9
+        ));
+
+        // One block of 32 samples with a single click, three silent blocks
+        let mut clicks = vec![0.0; 128];
+        clicks[10] = 1.0;
+        assert!(is_close(
+            detect(clicks.iter(), DetectionType::Rms),
+            (1.0 / 128.0 as PrcFmt).sqrt(),
+            1.0e-9
+        ));
+        assert!(is_close(
+            detect(clicks.iter(), DetectionType::Peak),
+            1.0,
+            1.0e-9
+        ));
+        assert!(is_close(
+            detect(clicks.iter(), DetectionType::RmsOfPeaks),
+            0.5,
+            1.0e-9
+        ));
+
+        let empty: Vec<PrcFmt> = Vec::new();
+        for detection in [
+            DetectionType::Rms,
+            DetectionType::Peak,
+            DetectionType::RmsOfPeaks,
+            DetectionType::LogRms,
+        ] {
+            assert_eq!(detect(empty.iter(), detection), 0.0);
+        }
+    }
+
+    #[test]
+    fn lookahead_envelope_minimal_reduction() {
+        let mut waveform: Vec<PrcFmt> =
+            (0..512).map(|n| 0.5 * (0.05 * n as PrcFmt).sin()).collect();
+        waveform[100] = 2.0;
+        waveform[300] = -1.5;
+        waveform[310] = 1.2;
+        let lookahead = 32;
+        let envelope = lookahead_gain_envelope(&waveform, 1.0, lookahead);
+
+        // Naive look-ahead, holding the lowest required gain within the window
+        let required: Vec<PrcFmt> = waveform
+            .iter()
+            .map(|v| PrcFmt::min(1.0, 1.0 / v.abs()))
+            .collect();
+        let naive: Vec<PrcFmt> = (0..waveform.len())
+            .map(|n| {
+                required[n..(n + lookahead + 1).min(waveform.len())]
+                    .iter()
+                    .fold(1.0, |acc: PrcFmt, g| acc.min(*g))
+            })
+            .collect();
+
+        for ((val, gain), naive_gain) in waveform.iter().zip(envelope.iter()).zip(naive.iter()) {
+            assert!((val * gain).abs() <= 1.0 + 1.0e-6);
+            assert!((val * naive_gain).abs() <= 1.0 + 1.0e-6);
+            assert!(gain >= naive_gain);
+        }
+        // Peaks are brought exactly to the ceiling
+        assert!(is_close(waveform[100] * envelope[100], 1.0, 1.0e-6));
+        let reduction: PrcFmt = envelope.iter().map(|g| 1.0 - g).sum();
+        let naive_reduction: PrcFmt = naive.iter().map(|g| 1.0 - g).sum();
+        assert!(reduction < 0.7 * naive_reduction);
+    }
+
+    #[test]
+    fn shaved_peaks_are_smoothed_from_both_sides() {
+        let mut waveform: Vec<PrcFmt> =
+            (0..256).map(|n| 0.5 * (0.05 * n as PrcFmt).sin()).collect();
+        waveform[3] = 1.5;
+        waveform[100] = 2.0;
+        waveform[104] = -1.8;
+        waveform[250] = 1.2;
+        let smoothing = 8;
+        let before = lookahead_gain_envelope(&waveform, 1.0, smoothing);
+        let reversed: Vec<PrcFmt> = waveform.iter().rev().copied().collect();
+        let after = lookahead_gain_envelope(&reversed, 1.0, smoothing);
+        let len = waveform.len();
+        let expected: Vec<PrcFmt> = waveform
+            .iter()
+            .enumerate()
+            .map(|(n, val)| {
+                if val.abs() > 1.0 {
+                    val * before[n].min(after[len - 1 - n])
+                } else {
+                    *val
+                }
+            })
+            .collect();
+        let mut gains = Vec::with_capacity(len);
+        shave_peaks(&mut waveform, 1.0, smoothing, &mut gains);
+        assert_eq!(gains.capacity(), len);
+        for (val, exp) in waveform.iter().zip(expected.iter()) {
+            assert!(is_close(*val, *exp, 1.0e-9));
+        }
+    }
+
+    #[test]
+    fn flush_denormals() {
+        let denormal = PrcFmt::MIN_POSITIVE / 4.0;
+        assert!(denormal > 0.0 && !denormal.is_normal());
+        assert_eq!(flush_denormal(denormal), 0.0);
+        assert_eq!(flush_denormal(-1.0e-20), 0.0);
+        assert_eq!(flush_denormal(1.0e-6), 1.0e-6);
+        assert_eq!(flush_denormal(-0.5), -0.5);
+        assert!((1.0e-18 as PrcFmt).powi(2).is_normal());
+    }
+
+    #[test]
+    fn log_rms_at_low_levels() {
+        let sine = |amplitude: PrcFmt| -> Vec<PrcFmt> {
+            (0..1024)
+                .map(|n| amplitude * (0.05 * n as PrcFmt).sin())
+                .collect()
+        };
+        let expected = |amplitude: PrcFmt| amplitude * rms(sine(1.0).iter());
+        // Both agree at normal levels
+        let normal = sine(0.5);
+        assert!(is_close(
+            detect(normal.iter(), DetectionType::LogRms),
+            detect(normal.iter(), DetectionType::Rms),
+            1.0e-4
+        ));
+        // The squares of these samples underflow
+        let amplitude = 1.0e-9 * PrcFmt::MIN_POSITIVE.sqrt();
+        let quiet = sine(amplitude);
+        let linear_error = (rms(quiet.iter()) - expected(amplitude)).abs() / expected(amplitude);
+        let log_error = (log_rms(quiet.iter()) - expected(amplitude)).abs() / expected(amplitude);
+        assert!(linear_error > 0.5);
+        assert!(log_error < 1.0e-3);
+    }
+}
@@ -0,0 +1,159 @@
+use std::io::{self, Read, Write};
+
+use crate::PrcFmt;
+use crate::Res;
+
+const MAGIC: &[u8] = "CDSPENV1".as_bytes();
+
+/// A gain envelope, downsampled for storage.
+#[derive(Debug, PartialEq)]
+pub struct Envelope {
+    /// Samplerate of the original, not downsampled, envelope.
+    pub samplerate: u32,
+    /// Number of original samples for each stored value.
+    pub decimation: u32,
+    pub values: Vec<f32>,
+}
+
+impl Envelope {
+    /// Downsample a per-sample gain envelope, keeping the lowest gain of each block
+    /// of `decimation` samples so that no gain reduction is hidden.
+    /// The gains are consumed one by one, so that a long envelope never has to be stored
+    /// at the full rate.
+    // The casts are needed when PrcFmt is f64
+    #[allow(clippy::unnecessary_cast)]
+    pub fn from_gains(
+        gains: impl IntoIterator<Item = PrcFmt>,
+        samplerate: usize,
+        decimation: usize,
+    ) -> Self {
+        let decimation = decimation.max(1);
+        let mut values = Vec::new();
+        let mut block_min = PrcFmt::INFINITY;
+        let mut block_len = 0;
+        for gain in gains {
+            block_min = block_min.min(gain);
+            block_len += 1;
+            if block_len == decimation {
+                values.push(block_min as f32);
+                block_min = PrcFmt::INFINITY;
+                block_len = 0;
+            }
+        }
+        if block_len > 0 {
+            values.push(block_min as f32);
+        }
+        Envelope {
+            samplerate: samplerate as u32,
+            decimation: decimation as u32,
+            values,
+        }
+    }
+}
+
+/// Write an envelope in a compact binary format.
+/// The header holds an 8 byte identifier, the samplerate and decimation as u32,
+/// and the number of values as u64, followed by the values as f32.
+/// All numbers are little endian.
+pub fn write_envelope<W: Write>(writer: &mut W, envelope: &Envelope) -> Res<()> {
+    writer.write_all(MAGIC)?;
+    writer.write_all(&envelope.samplerate.to_le_bytes())?;
+    writer.write_all(&envelope.decimation.to_le_bytes())?;
+    writer.write_all(&(envelope.values.len() as u64).to_le_bytes())?;
+    for value in envelope.values.iter() {
+        writer.write_all(&value.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+fn invalid_data(msg: &str) -> Box<dyn std::error::Error> {
+    io::Error::new(io::ErrorKind::InvalidData, msg).into()
+}
+
+/// Read an envelope written by `write_envelope`.
+/// A file with another format fails with an `InvalidData` IO error.
+pub fn read_envelope<R: Read>(reader: &mut R) -> Res<Envelope> {
+    let mut magic = [0; 8];
+    reader.read_exact(&mut magic)?;
+    if magic != MAGIC {
+        return Err(invalid_data("Not an envelope file"));
+    }
+    let mut word = [0; 4];
+    reader.read_exact(&mut word)?;
+    let samplerate = u32::from_le_bytes(word);
+    reader.read_exact(&mut word)?;
+    let decimation = u32::from_le_bytes(word);
+    let mut long_word = [0; 8];
+    reader.read_exact(&mut long_word)?;
+    let length = u64::from_le_bytes(long_word);
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+    let expected_len = usize::try_from(length)
+        .ok()
+        .and_then(|length| length.checked_mul(4));
+    if expected_len != Some(data.len()) {
+        let msg = format!(
+            "Envelope file should contain {} values, found {} bytes",
+            length,
+            data.len()
+        );
+        return Err(invalid_data(&msg));
+    }
+    let values = data
+        .chunks_exact(4)
+        .map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .collect();
+    Ok(Envelope {
+        samplerate,
+        decimation,
+        values,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::envelopefile::{read_envelope, write_envelope, Envelope};
+    use crate::PrcFmt;
+    use std::io;
+
+    #[test]
+    fn envelope_roundtrip() {
+        let gains: Vec<PrcFmt> = (0..1000)
+            .map(|n| 0.5 + 0.5 * (n as PrcFmt * 0.01).cos())
+            .collect();
+        let envelope = Envelope::from_gains(gains.iter().copied(), 48000, 64);
+        assert_eq!(envelope.values.len(), 16);
+        let mut data = Vec::new();
+        write_envelope(&mut data, &envelope).unwrap();
+        assert_eq!(data.len(), 24 + 4 * 16);
+        let loaded = read_envelope(&mut data.as_slice()).unwrap();
+        assert_eq!(loaded, envelope);
+        assert_eq!(loaded.samplerate, 48000);
+        assert_eq!(loaded.decimation, 64);
+        // Each value is the lowest gain of its block
+        for (block, value) in gains.chunks(64).zip(loaded.values.iter()) {
+            for gain in block {
+                assert!(*value as PrcFmt <= *gain + 1.0e-6);
+            }
+            assert!(block.iter().any(|g| (*g - *value as PrcFmt).abs() < 1.0e-6));
+        }
+    }
+
+    #[test]
+    fn reject_truncated_envelope() {
+        let envelope = Envelope::from_gains([1.0, 0.5, 0.25], 44100, 1);
+        let mut data = Vec::new();
+        write_envelope(&mut data, &envelope).unwrap();
+        data.pop();
+        let err = read_envelope(&mut data.as_slice()).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<io::Error>().unwrap().kind(),
+            io::ErrorKind::InvalidData
+        );
+        assert!(read_envelope(&mut &b"RIFF0000"[..]).is_err());
+        // A length that would overflow is rejected
+        let mut data = data[..16].to_vec();
+        data.extend_from_slice(&u64::MAX.to_le_bytes());
+        assert!(read_envelope(&mut data.as_slice()).is_err());
+    }
+}
@@ -104,6 +104,10 @@ impl Filter for FftConv {
         &self.name
     }
 
+    fn filter_type(&self) -> &'static str {
+        "Conv"
+    }
+
     /// Process a waveform by FT, then multiply transform with transform of filter, and then transform back.
     fn process_waveform(&mut self, waveform: &mut [PrcFmt]) -> Res<()> {
         // Copy to inut buffer and clear overlap area
@@ -141,6 +141,7 @@ impl PlaybackDevice for FilePlaybackDevice {
                         let mut chunk_stats = ChunkStats {
                             rms: vec![0.0; channels],
                             peak: vec![0.0; channels],
+                            peak_index: vec![0; channels],
                         };
                         barrier.wait();
                         debug!("starting playback loop");
@@ -290,6 +291,7 @@ fn capture_loop(
     let mut chunk_stats = ChunkStats {
         rms: vec![0.0; params.channels],
         peak: vec![0.0; params.channels],
+        peak_index: vec![0; params.channels],
     };
     let mut value_range = 0.0;
     let mut rate_adjust = 0.0;
@@ -557,7 +559,9 @@ impl CaptureDevice for FileCaptureDevice {
         let mut read_bytes = self.read_bytes;
         let sample_format = match &self.source {
             CaptureSource::Filename(fname) => {
-                if self.sample_format.is_none() {
+                if let Some(sample_format) = self.sample_format {
+                    sample_format
+                } else {
                     // No format was given, try to get from the file.
                     // Only works if the file is in wav format.
                     // Also update channels and read & skip bytes.
@@ -566,8 +570,6 @@ impl CaptureDevice for FileCaptureDevice {
                     read_bytes = wav_info.data_length;
                     channels = wav_info.channels;
                     wav_info.sample_format
-                } else {
-                    self.sample_format.unwrap()
                 }
             }
             _ => self.sample_format.unwrap(),
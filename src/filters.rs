@@ -8,13 +8,20 @@ use crate::conversions;
 use crate::diffeq;
 use crate::dither;
 use crate::fftconv;
+use crate::finalstage;
+use crate::lfprotect;
 use crate::limiter;
-use crate::rms_limiter;
+use crate::limiterbus;
 use crate::loudness;
 use crate::mixer;
 use crate::noisegate;
+use crate::normalizer;
+use crate::rms_limiter;
+use crate::upwardcompressor;
 use rawsample::SampleReader;
 use std::collections::HashMap;
+use std::error;
+use std::fmt;
 use std::fs::File;
 use std::io::BufReader;
 use std::io::{BufRead, Seek, SeekFrom};
@@ -29,13 +36,78 @@ use crate::Res;
 
 use crate::wavtools::find_data_in_wav;
 
+/// An error while processing audio, for problems that are only found at runtime.
+#[derive(Debug)]
+pub struct ProcessingError {
+    desc: String,
+}
+
+impl fmt::Display for ProcessingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.desc)
+    }
+}
+
+impl error::Error for ProcessingError {
+    fn description(&self) -> &str {
+        &self.desc
+    }
+}
+
+impl ProcessingError {
+    pub fn new(desc: &str) -> Self {
+        ProcessingError {
+            desc: desc.to_owned(),
+        }
+    }
+}
+
 pub trait Filter {
     // Filter a Vec
     fn process_waveform(&mut self, waveform: &mut [PrcFmt]) -> Res<()>;
 
+    // Filter several channels in one call, for filters that work across channels.
+    // The default processes the channels one after the other with process_waveform.
+    // That is only right for filters without state, filters that keep a state or count
+    // samples must override it.
+    fn process_frames(&mut self, frames: &mut [&mut [PrcFmt]]) -> Res<()> {
+        for waveform in frames.iter_mut() {
+            self.process_waveform(waveform)?;
+        }
+        Ok(())
+    }
+
     fn update_parameters(&mut self, config: config::Filter);
 
     fn name(&self) -> &str;
+
+    // Type of the filter, matching the name of the config variant
+    fn filter_type(&self) -> &'static str;
+
+    // True if the output can be louder than the input, for example because of makeup gain
+    // or a boost. The default assumes that it can, filters that never raise the level
+    // override this.
+    fn can_increase_level(&self) -> bool {
+        true
+    }
+
+    // Delay in samples between input and output
+    fn latency_samples(&self) -> usize {
+        0
+    }
+
+    // Drain the samples still held in delay buffers at the end of a stream,
+    // by pushing zeros through the filter and appending the result to `out`.
+    // Does nothing for filters without latency.
+    fn flush(&mut self, out: &mut Vec<PrcFmt>) {
+        let latency = self.latency_samples();
+        if latency > 0 {
+            let mut tail = vec![0.0; latency];
+            if self.process_waveform(&mut tail).is_ok() {
+                out.extend_from_slice(&tail);
+            }
+        }
+    }
 }
 
 pub trait Processor {
@@ -47,6 +119,26 @@ pub trait Processor {
     fn name(&self) -> &str;
 }
 
+/// List the name and type of each filter in a slice of filters.
+pub fn list_filters<F: Filter + ?Sized>(filters: &[Box<F>]) -> Vec<(String, &'static str)> {
+    filters
+        .iter()
+        .map(|f| (f.name().to_string(), f.filter_type()))
+        .collect()
+}
+
+/// Convert a latency in samples at the processing samplerate to another samplerate,
+/// for example the capture samplerate when a resampler is used before the filters.
+/// The result is rounded to the nearest sample.
+pub fn convert_latency(
+    latency_samples: usize,
+    from_samplerate: usize,
+    to_samplerate: usize,
+) -> usize {
+    ((latency_samples as u64 * to_samplerate as u64 + from_samplerate as u64 / 2)
+        / from_samplerate as u64) as usize
+}
+
 pub fn pad_vector(values: &[PrcFmt], length: usize) -> Vec<PrcFmt> {
     let new_len = if values.len() > length {
         values.len()
@@ -171,9 +263,37 @@ pub fn read_wav(filename: &str, channel: usize) -> Res<Vec<PrcFmt>> {
     Ok(data)
 }
 
+/// Accumulated processing times of a filter.
+#[cfg(feature = "profiling")]
+#[derive(Clone, Debug, Default)]
+pub struct TimingStats {
+    pub count: u64,
+    pub total: std::time::Duration,
+    pub max: std::time::Duration,
+}
+
+#[cfg(feature = "profiling")]
+impl TimingStats {
+    fn record(&mut self, duration: std::time::Duration) {
+        self.count += 1;
+        self.total += duration;
+        self.max = self.max.max(duration);
+    }
+
+    /// Average processing time per chunk.
+    pub fn average(&self) -> std::time::Duration {
+        if self.count == 0 {
+            return std::time::Duration::ZERO;
+        }
+        self.total / self.count as u32
+    }
+}
+
 pub struct FilterGroup {
     channel: usize,
     filters: Vec<Box<dyn Filter + Send>>,
+    #[cfg(feature = "profiling")]
+    timings: Vec<TimingStats>,
 }
 
 impl FilterGroup {
@@ -220,19 +340,43 @@ impl FilterGroup {
                         ))
                     }
                     config::Filter::Limiter { parameters, .. } => {
-                        Box::new(limiter::Limiter::from_config(
+                        Box::new(limiter::Limiter::from_config(name, parameters))
+                    }
+                    config::Filter::RMSLimiter { parameters, .. } => {
+                        Box::new(rms_limiter::RMSLimiter::from_config(
                             name,
                             parameters,
+                            waveform_length,
+                            sample_freq,
                         ))
                     }
-                    config::Filter::RMSLimiter { parameters, .. } => {
-                        Box::new(rms_limiter::RMSLimiter::from_config(
+                    config::Filter::FinalStage { parameters, .. } => {
+                        Box::new(finalstage::FinalStage::from_config(
+                            name,
+                            parameters,
+                            waveform_length,
+                            sample_freq,
+                        ))
+                    }
+                    config::Filter::Normalizer { parameters, .. } => {
+                        Box::new(normalizer::Normalizer::from_config(
                             name,
                             parameters,
                             waveform_length,
                             sample_freq,
                         ))
                     }
+                    config::Filter::UpwardCompressor { parameters, .. } => {
+                        Box::new(upwardcompressor::UpwardCompressor::from_config(
+                            name,
+                            parameters,
+                            waveform_length,
+                            sample_freq,
+                        ))
+                    }
+                    config::Filter::LfProtect { parameters, .. } => Box::new(
+                        lfprotect::LfProtect::from_config(name, parameters, sample_freq),
+                    ),
                     config::Filter::Loudness { parameters, .. } => {
                         Box::new(loudness::Loudness::from_config(
                             name,
@@ -250,7 +394,27 @@ impl FilterGroup {
                 };
             filters.push(filter);
         }
-        FilterGroup { channel, filters }
+        FilterGroup {
+            channel,
+            #[cfg(feature = "profiling")]
+            timings: vec![TimingStats::default(); filters.len()],
+            filters,
+        }
+    }
+
+    /// Processing times of all filters in the group, together with the filter names.
+    #[cfg(feature = "profiling")]
+    pub fn timing_stats(&self) -> Vec<(String, TimingStats)> {
+        self.filters
+            .iter()
+            .zip(self.timings.iter())
+            .map(|(f, t)| (f.name().to_string(), t.clone()))
+            .collect()
+    }
+
+    /// List the name and type of all filters in the group.
+    pub fn list_filters(&self) -> Vec<(String, &'static str)> {
+        list_filters(&self.filters)
     }
 
     pub fn update_parameters(
@@ -292,9 +456,16 @@ impl FilterGroup {
                     "xorpd xmm15, xmm15"
                 )
             }
+            #[cfg(not(feature = "profiling"))]
             for filter in &mut self.filters {
                 filter.process_waveform(&mut input.waveforms[self.channel])?;
             }
+            #[cfg(feature = "profiling")]
+            for (filter, timing) in self.filters.iter_mut().zip(self.timings.iter_mut()) {
+                let start = Instant::now();
+                filter.process_waveform(&mut input.waveforms[self.channel])?;
+                timing.record(start.elapsed());
+            }
         }
         Ok(())
     }
@@ -304,6 +475,79 @@ pub struct ParallelFilters {
     filters: Vec<Vec<Box<dyn Filter + Send>>>,
 }
 
+/// A filter that gets all the channels of its pipeline step in one call,
+/// for filters that work across channels.
+pub struct FramesFilter {
+    channels: Vec<usize>,
+    filter: Box<dyn Filter + Send>,
+}
+
+impl FramesFilter {
+    pub fn new(mut channels: Vec<usize>, filter: Box<dyn Filter + Send>) -> Self {
+        channels.sort_unstable();
+        FramesFilter { channels, filter }
+    }
+
+    /// Create the filter of a step that must get all the channels of the step in one call.
+    /// Returns None if the step consists of filters that process one channel at a time.
+    pub fn from_config(
+        channels: Vec<usize>,
+        names: &[String],
+        filter_configs: &HashMap<String, config::Filter>,
+        waveform_length: usize,
+        sample_freq: usize,
+    ) -> Option<Self> {
+        let [name] = names else {
+            return None;
+        };
+        let filter: Box<dyn Filter + Send> = match &filter_configs[name] {
+            config::Filter::RMSLimiter { parameters, .. } if parameters.channels() > 1 => {
+                Box::new(limiterbus::IndependentLimiters::from_config(
+                    name,
+                    parameters.clone(),
+                    waveform_length,
+                    sample_freq,
+                ))
+            }
+            // The detection channel is counted among the channels of the step
+            config::Filter::RMSLimiter { parameters, .. } if parameters.processes_frames() => {
+                Box::new(rms_limiter::RMSLimiter::from_config(
+                    name,
+                    parameters.clone(),
+                    waveform_length,
+                    sample_freq,
+                ))
+            }
+            _ => return None,
+        };
+        Some(FramesFilter::new(channels, filter))
+    }
+
+    pub fn update_parameters(
+        &mut self,
+        filterconfigs: HashMap<String, config::Filter>,
+        changed: &[String],
+    ) {
+        if changed.iter().any(|n| n == self.filter.name()) {
+            self.filter
+                .update_parameters(filterconfigs[self.filter.name()].clone());
+        }
+    }
+
+    /// Apply the filter to the channels of the step of an AudioChunk, in ascending order.
+    fn process_chunk(&mut self, input: &mut AudioChunk) -> Res<()> {
+        let channels = &self.channels;
+        let mut frames: Vec<&mut [PrcFmt]> = input
+            .waveforms
+            .iter_mut()
+            .enumerate()
+            .filter(|(channel, _)| channels.contains(channel))
+            .map(|(_, waveform)| waveform.as_mut_slice())
+            .collect();
+        self.filter.process_frames(&mut frames)
+    }
+}
+
 impl ParallelFilters {
     pub fn update_parameters(
         &mut self,
@@ -340,6 +584,7 @@ pub enum PipelineStep {
     MixerStep(mixer::Mixer),
     FilterStep(FilterGroup),
     ParallelFiltersStep(ParallelFilters),
+    FramesStep(FramesFilter),
     ProcessorStep(Box<dyn Processor>),
 }
 
@@ -391,16 +636,31 @@ impl Pipeline {
                             );
                             Box::new(0..num_channels) as Box<dyn Iterator<Item = usize>>
                         };
-                        for channel in channels_iter {
-                            let fltgrp = FilterGroup::from_config(
-                                channel,
-                                &step.names,
-                                conf.filters.as_ref().unwrap().clone(),
-                                conf.devices.chunksize,
-                                conf.devices.samplerate,
-                                processing_params.clone(),
+                        let channels: Vec<usize> = channels_iter.collect();
+                        if let Some(frames_filter) = FramesFilter::from_config(
+                            channels.clone(),
+                            &step.names,
+                            conf.filters.as_ref().unwrap(),
+                            conf.devices.chunksize,
+                            conf.devices.samplerate,
+                        ) {
+                            debug!(
+                                "Process channels {:?} with filter {:?} in one call",
+                                channels, step.names
                             );
-                            steps.push(PipelineStep::FilterStep(fltgrp));
+                            steps.push(PipelineStep::FramesStep(frames_filter));
+                        } else {
+                            for channel in channels {
+                                let fltgrp = FilterGroup::from_config(
+                                    channel,
+                                    &step.names,
+                                    conf.filters.as_ref().unwrap().clone(),
+                                    conf.devices.chunksize,
+                                    conf.devices.samplerate,
+                                    processing_params.clone(),
+                                );
+                                steps.push(PipelineStep::FilterStep(fltgrp));
+                            }
                         }
                     }
                 }
@@ -479,6 +739,9 @@ impl Pipeline {
                 PipelineStep::ParallelFiltersStep(flt) => {
                     flt.update_parameters(conf.filters.as_ref().unwrap().clone(), filters);
                 }
+                PipelineStep::FramesStep(flt) => {
+                    flt.update_parameters(conf.filters.as_ref().unwrap().clone(), filters);
+                }
                 PipelineStep::ProcessorStep(proc) => {
                     if processors.iter().any(|n| n == proc.name()) {
                         proc.update_parameters(
@@ -505,6 +768,9 @@ impl Pipeline {
                 PipelineStep::ParallelFiltersStep(flt) => {
                     flt.process_chunk(&mut chunk).unwrap();
                 }
+                PipelineStep::FramesStep(flt) => {
+                    flt.process_chunk(&mut chunk).unwrap();
+                }
                 PipelineStep::ProcessorStep(comp) => {
                     comp.process_chunk(&mut chunk).unwrap();
                 }
@@ -536,7 +802,7 @@ fn parallelize_filters(steps: &mut Vec<PipelineStep>, nbr_channels: usize) -> Ve
                 debug!("Append mixer step to pipeline");
                 new_steps.push(step);
             }
-            PipelineStep::ProcessorStep(_) => {
+            PipelineStep::ProcessorStep(_) | PipelineStep::FramesStep(_) => {
                 if parfilt.is_some() {
                     debug!("Append parallel filter step to pipeline");
                     new_steps.push(PipelineStep::ParallelFiltersStep(parfilt.take().unwrap()));
@@ -580,7 +846,7 @@ fn parallelize_filters(steps: &mut Vec<PipelineStep>, nbr_channels: usize) -> Ve
 }
 
 /// Validate the filter config, to give a helpful message intead of a panic.
-pub fn validate_filter(fs: usize, filter_config: &config::Filter) -> Res<()> {
+pub fn validate_filter(fs: usize, chunksize: usize, filter_config: &config::Filter) -> Res<()> {
     match filter_config {
         config::Filter::Conv { parameters, .. } => fftconv::validate_config(parameters),
         config::Filter::Biquad { parameters, .. } => biquad::validate_config(fs, parameters),
@@ -591,7 +857,19 @@ pub fn validate_filter(fs: usize, filter_config: &config::Filter) -> Res<()> {
         config::Filter::Volume { parameters, .. } => {
             basicfilters::validate_volume_config(parameters)
         }
-        config::Filter::RMSLimiter { parameters, .. } => rms_limiter::validate_config(parameters),
+        config::Filter::RMSLimiter { parameters, .. } => {
+            rms_limiter::validate_config(fs, chunksize, parameters)
+        }
+        config::Filter::FinalStage { parameters, .. } => {
+            finalstage::validate_config(fs, chunksize, parameters)
+        }
+        config::Filter::Normalizer { parameters, .. } => {
+            normalizer::validate_config(fs, chunksize, parameters)
+        }
+        config::Filter::UpwardCompressor { parameters, .. } => {
+            upwardcompressor::validate_config(parameters)
+        }
+        config::Filter::LfProtect { parameters, .. } => lfprotect::validate_config(fs, parameters),
         config::Filter::Loudness { parameters, .. } => loudness::validate_config(parameters),
         config::Filter::BiquadCombo { parameters, .. } => {
             biquadcombo::validate_config(fs, parameters)
@@ -600,12 +878,49 @@ pub fn validate_filter(fs: usize, filter_config: &config::Filter) -> Res<()> {
     }
 }
 
+/// Validate all filter configs at once, and collect the errors of every invalid filter
+/// instead of stopping at the first one. The errors are sorted by filter name.
+pub fn validate_pipeline(
+    fs: usize,
+    chunksize: usize,
+    filters: &HashMap<String, config::Filter>,
+) -> Result<(), Vec<config::ConfigError>> {
+    let mut names: Vec<&String> = filters.keys().collect();
+    names.sort();
+    let errors: Vec<config::ConfigError> = names
+        .into_iter()
+        .filter_map(|name| {
+            validate_filter(fs, chunksize, &filters[name])
+                .err()
+                .map(|err| {
+                    let msg = format!("Invalid filter '{name}'. Reason: {err}");
+                    config::ConfigError::new(&msg)
+                })
+        })
+        .collect();
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use crate::audiodevice::AudioChunk;
+    use crate::basicfilters::Delay;
+    use crate::config;
     use crate::config::FileFormat;
     use crate::filters::read_wav;
-    use crate::filters::{pad_vector, read_coeff_file};
+    use crate::filters::{
+        convert_latency, list_filters, pad_vector, read_coeff_file, validate_pipeline, Filter,
+        Pipeline,
+    };
+    use crate::limiter::Limiter;
+    use crate::rms_limiter::RMSLimiter;
     use crate::PrcFmt;
+    use crate::ProcessingParameters;
+    use std::sync::Arc;
 
     fn is_close(left: PrcFmt, right: PrcFmt, maxdiff: PrcFmt) -> bool {
         println!("{} - {} = {}", left, right, left - right);
@@ -757,4 +1072,236 @@ mod tests {
         let bad = read_wav("testdata/int32.wav", 1);
         assert!(bad.is_err());
     }
+
+    #[test]
+    fn list_limiter_types() {
+        let limiter = Limiter::from_config(
+            "clipper",
+            config::LimiterParameters {
+                soft_clip: None,
+                clip_limit: -1.0,
+            },
+        );
+        let rms_limiter = RMSLimiter::from_config(
+            "rms",
+            config::RMSLimiterParameters {
+                rms_samples: 128,
+                ..config::RMSLimiterParameters::new(-3.0, 12.0)
+            },
+            1024,
+            44100,
+        );
+        assert_eq!(limiter.filter_type(), "Limiter");
+        assert_eq!(rms_limiter.filter_type(), "RMSLimiter");
+        let filters: Vec<Box<dyn Filter + Send>> = vec![Box::new(limiter), Box::new(rms_limiter)];
+        let listed = list_filters(&filters);
+        assert_eq!(
+            listed,
+            vec![
+                ("clipper".to_string(), "Limiter"),
+                ("rms".to_string(), "RMSLimiter")
+            ]
+        );
+    }
+
+    #[test]
+    fn latency_between_samplerates() {
+        // 5 ms lookahead at 96 kHz processing rate, seen from a 48 kHz capture rate
+        let delay = Delay::new("lookahead", 96000, 480.0, false);
+        assert_eq!(convert_latency(delay.latency_samples(), 96000, 48000), 240);
+        assert_eq!(convert_latency(480, 48000, 96000), 960);
+        assert_eq!(convert_latency(441, 44100, 48000), 480);
+        assert_eq!(convert_latency(3, 96000, 44100), 1);
+    }
+
+    #[test]
+    fn validate_pipeline_reports_all_errors() {
+        let gain = |gain| config::Filter::Gain {
+            description: None,
+            parameters: config::GainParameters {
+                gain,
+                inverted: None,
+                mute: None,
+                scale: None,
+            },
+        };
+        let mut filters = std::collections::HashMap::new();
+        filters.insert("good".to_string(), gain(-3.0));
+        filters.insert("loud".to_string(), gain(200.0));
+        filters.insert(
+            "limiter".to_string(),
+            config::Filter::RMSLimiter {
+                description: None,
+                parameters: config::RMSLimiterParameters {
+                    rms_samples: 1000,
+                    ..Default::default()
+                },
+            },
+        );
+        let errors = validate_pipeline(44100, 1024, &filters).unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0]
+            .to_string()
+            .starts_with("Invalid filter 'limiter'"));
+        assert!(errors[1].to_string().starts_with("Invalid filter 'loud'"));
+        filters.remove("loud");
+        filters.remove("limiter");
+        assert!(validate_pipeline(44100, 1024, &filters).is_ok());
+    }
+
+    #[cfg(feature = "profiling")]
+    #[test]
+    fn filter_group_timing() {
+        use crate::audiodevice::AudioChunk;
+        use crate::filters::FilterGroup;
+        use std::collections::HashMap;
+        use std::sync::Arc;
+
+        let mut filter_configs = HashMap::new();
+        filter_configs.insert(
+            "limiter".to_string(),
+            config::Filter::RMSLimiter {
+                description: None,
+                parameters: config::RMSLimiterParameters {
+                    rms_samples: 1024,
+                    ..config::RMSLimiterParameters::new(-3.0, 12.0)
+                },
+            },
+        );
+        let mut group = FilterGroup::from_config(
+            0,
+            &["limiter".to_string()],
+            filter_configs,
+            1024,
+            44100,
+            Arc::new(crate::ProcessingParameters::default()),
+        );
+        let mut chunk = AudioChunk::new(vec![vec![0.5; 1024]], 0.5, 0.5, 1024, 1024);
+        for _ in 0..3 {
+            group.process_chunk(&mut chunk).unwrap();
+        }
+        let stats = group.timing_stats();
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].0, "limiter");
+        assert_eq!(stats[0].1.count, 3);
+        assert!(stats[0].1.max > std::time::Duration::ZERO);
+        assert!(stats[0].1.average() <= stats[0].1.max);
+    }
+
+    #[test]
+    fn default_process_frames() {
+        let conf = config::LimiterParameters {
+            soft_clip: Some(true),
+            clip_limit: -6.0,
+        };
+        let mut left: Vec<PrcFmt> = (0..64).map(|n| (n as PrcFmt * 0.1).sin()).collect();
+        let mut right: Vec<PrcFmt> = (0..64).map(|n| 0.3 * (n as PrcFmt * 0.2).cos()).collect();
+
+        let mut expected_left = left.clone();
+        let mut expected_right = right.clone();
+        let mut filter = Limiter::from_config("test", conf.clone());
+        filter.process_waveform(&mut expected_left).unwrap();
+        filter.process_waveform(&mut expected_right).unwrap();
+
+        let mut filter = Limiter::from_config("test", conf);
+        filter
+            .process_frames(&mut [left.as_mut_slice(), right.as_mut_slice()])
+            .unwrap();
+        assert_eq!(left, expected_left);
+        assert_eq!(right, expected_right);
+    }
+
+    /// A stereo configuration with a single filter step using the given RMSLimiter parameters.
+    fn limiter_pipeline_config(parameters: &str) -> config::Configuration {
+        let yaml = format!(
+            r#"
+devices:
+  samplerate: 48000
+  chunksize: 1024
+  capture:
+    type: Stdin
+    channels: 2
+    format: S16LE
+  playback:
+    type: Stdout
+    channels: 2
+    format: S16LE
+filters:
+  limiter:
+    type: RMSLimiter
+    parameters:
+      threshold: -12.0
+      rms_samples: 1024
+{parameters}
+pipeline:
+  - type: Filter
+    names:
+      - limiter
+"#
+        );
+        let mut conf: config::Configuration = serde_yaml::from_str(&yaml).unwrap();
+        config::validate_config(&mut conf, None).unwrap();
+        conf
+    }
+
+    fn process_stereo(pipeline: &mut Pipeline, left: PrcFmt, right: PrcFmt) -> AudioChunk {
+        let chunk = AudioChunk::new(
+            vec![vec![left; 1024], vec![right; 1024]],
+            1.0,
+            -1.0,
+            1024,
+            1024,
+        );
+        pipeline.process_chunk(chunk)
+    }
+
+    #[test]
+    fn multichannel_limiter_step() {
+        let conf = limiter_pipeline_config("      channels: 2");
+        let mut pipeline = Pipeline::from_config(conf, Arc::new(ProcessingParameters::default()));
+        // Each channel has its own limiter, so only the loud one is limited
+        let mut output = process_stereo(&mut pipeline, 0.9, 0.01);
+        for _ in 0..3 {
+            output = process_stereo(&mut pipeline, 0.9, 0.01);
+        }
+        assert!(output.waveforms[0][1023] < 0.3);
+        assert!(is_close(output.waveforms[1][1023], 0.01, 1.0e-6));
+        // The first channel is still releasing when the second one gets loud
+        let output = process_stereo(&mut pipeline, 0.01, 0.9);
+        assert!(output.waveforms[0][1023] < 0.01);
+        assert!(output.waveforms[1][1023] < 0.3);
+
+        // A multichannel limiter must get as many channels as it has limiters
+        let mut conf = limiter_pipeline_config("      channels: 2");
+        conf.pipeline.as_mut().unwrap()[0] =
+            config::PipelineStep::Filter(config::PipelineStepFilter {
+                channels: Some(vec![1]),
+                names: vec!["limiter".to_string()],
+                description: None,
+                bypassed: None,
+            });
+        assert!(config::validate_config(&mut conf, None).is_err());
+    }
+
+    #[test]
+    fn limiter_detecting_from_one_channel() {
+        let conf = limiter_pipeline_config("      detect_from_channel: 1");
+        let mut pipeline = Pipeline::from_config(conf, Arc::new(ProcessingParameters::default()));
+        // The loud second channel sets the gain of the quiet first one
+        let output = process_stereo(&mut pipeline, 0.01, 0.9);
+        let gain = output.waveforms[1][1023] / 0.9;
+        assert!(gain < 0.3);
+        assert!(is_close(output.waveforms[0][1023], 0.01 * gain, 1.0e-6));
+
+        // The detection channel must be one of the channels of the step
+        let mut conf = limiter_pipeline_config("      detect_from_channel: 1");
+        conf.pipeline.as_mut().unwrap()[0] =
+            config::PipelineStep::Filter(config::PipelineStepFilter {
+                channels: Some(vec![1]),
+                names: vec!["limiter".to_string()],
+                description: None,
+                bypassed: None,
+            });
+        assert!(config::validate_config(&mut conf, None).is_err());
+    }
 }
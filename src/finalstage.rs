@@ -0,0 +1,171 @@
+use crate::config;
+use crate::dynamics_math::db_to_voltage_ratio;
+use crate::filters::Filter;
+use crate::rms_limiter;
+use crate::rms_limiter::RMSLimiter;
+use crate::PrcFmt;
+use crate::Res;
+
+/// Output protection filter, combining a RMS limiter with a hard clipper
+/// that catches anything the limiter lets through.
+pub struct FinalStage {
+    pub name: String,
+    limiter: RMSLimiter,
+    clip_ceiling: PrcFmt,
+    clipped_samples: usize,
+    clip_latched: bool,
+}
+
+impl FinalStage {
+    /// Creates a FinalStage filter from a config struct
+    pub fn from_config(
+        name: &str,
+        conf: config::FinalStageParameters,
+        chunksize: usize,
+        samplerate: usize,
+    ) -> Self {
+        let clip_ceiling = db_to_voltage_ratio(conf.clip_ceiling as PrcFmt);
+        debug!(
+            "Creating final stage '{}', threshold: {}, decay: {}, rms_samples: {}, clip_ceiling: {}",
+            name, conf.threshold, conf.decay, conf.rms_samples, conf.clip_ceiling
+        );
+        let limiter =
+            RMSLimiter::from_config(name, conf.limiter_parameters(), chunksize, samplerate);
+        FinalStage {
+            name: name.to_string(),
+            limiter,
+            clip_ceiling,
+            clipped_samples: 0,
+            clip_latched: false,
+        }
+    }
+
+    /// Number of samples that have been hard clipped since the filter was created.
+    pub fn clipped_samples(&self) -> usize {
+        self.clipped_samples
+    }
+
+    /// True if any sample has been clipped since the latch was last cleared.
+    pub fn clip_latched(&self) -> bool {
+        self.clip_latched
+    }
+
+    /// Clear the clip latch.
+    pub fn clear_clip(&mut self) {
+        self.clip_latched = false;
+    }
+}
+
+impl Filter for FinalStage {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn filter_type(&self) -> &'static str {
+        "FinalStage"
+    }
+
+    fn can_increase_level(&self) -> bool {
+        self.limiter.can_increase_level()
+    }
+
+    fn process_waveform(&mut self, waveform: &mut [PrcFmt]) -> Res<()> {
+        self.limiter.process_waveform(waveform)?;
+        for item in waveform.iter_mut() {
+            if item.abs() > self.clip_ceiling {
+                *item = item.clamp(-self.clip_ceiling, self.clip_ceiling);
+                self.clipped_samples += 1;
+                self.clip_latched = true;
+            }
+        }
+        Ok(())
+    }
+
+    fn update_parameters(&mut self, conf: config::Filter) {
+        if let config::Filter::FinalStage {
+            parameters: conf, ..
+        } = conf
+        {
+            self.clip_ceiling = db_to_voltage_ratio(conf.clip_ceiling as PrcFmt);
+            self.limiter.update_parameters(config::Filter::RMSLimiter {
+                description: None,
+                parameters: conf.limiter_parameters(),
+            });
+            debug!(
+                "Updated final stage '{}', threshold: {}, decay: {}, rms_samples: {}, clip_ceiling: {}",
+                self.name, conf.threshold, conf.decay, conf.rms_samples, conf.clip_ceiling
+            );
+        } else {
+            // This should never happen unless there is a bug somewhere else
+            panic!("Invalid config change!");
+        }
+    }
+}
+
+/// Validate a FinalStage config.
+pub fn validate_config(
+    samplerate: usize,
+    chunksize: usize,
+    conf: &config::FinalStageParameters,
+) -> Res<()> {
+    rms_limiter::validate_config(samplerate, chunksize, &conf.limiter_parameters())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config;
+    use crate::dynamics_math::db_to_voltage_ratio;
+    use crate::filters::Filter;
+    use crate::finalstage::FinalStage;
+    use crate::PrcFmt;
+
+    #[test]
+    fn limit_and_clip() {
+        let conf = config::FinalStageParameters {
+            decay: 12.0,
+            threshold: -12.0,
+            rms_samples: 1024,
+            clip_ceiling: -1.0,
+        };
+        let mut filter = FinalStage::from_config("test", conf, 1024, 48000);
+        let ceiling = db_to_voltage_ratio(-1.0);
+        // A quiet signal with a single large peak.
+        // The rms stays below threshold, only the peak gets clipped.
+        let mut waveform: Vec<PrcFmt> = vec![0.1; 1024];
+        waveform[100] = 2.0;
+        filter.process_waveform(&mut waveform).unwrap();
+        assert_eq!(waveform[0], 0.1);
+        assert_eq!(waveform[100], ceiling);
+        assert_eq!(filter.clipped_samples(), 1);
+        // A loud signal gets smoothly limited to the threshold, without clipping
+        let mut waveform: Vec<PrcFmt> = vec![0.9; 1024];
+        filter.process_waveform(&mut waveform).unwrap();
+        assert!((waveform[0] - db_to_voltage_ratio(-12.0)).abs() < 1.0e-6);
+        assert_eq!(filter.clipped_samples(), 1);
+    }
+
+    #[test]
+    fn clip_latch() {
+        let conf = config::FinalStageParameters {
+            decay: 12.0,
+            threshold: -12.0,
+            rms_samples: 1024,
+            clip_ceiling: -1.0,
+        };
+        let mut filter = FinalStage::from_config("test", conf, 1024, 48000);
+        let mut waveform: Vec<PrcFmt> = vec![0.1; 1024];
+        filter.process_waveform(&mut waveform).unwrap();
+        assert!(!filter.clip_latched());
+        waveform[100] = 2.0;
+        filter.process_waveform(&mut waveform).unwrap();
+        assert!(filter.clip_latched());
+        for _ in 0..100 {
+            let mut waveform: Vec<PrcFmt> = vec![0.1; 1024];
+            filter.process_waveform(&mut waveform).unwrap();
+        }
+        assert!(filter.clip_latched());
+        filter.clear_clip();
+        assert!(!filter.clip_latched());
+        assert_eq!(filter.clipped_samples(), 1);
+    }
+}
@@ -0,0 +1,60 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+
+use crate::dynamics_math::voltage_ratio_to_db;
+use crate::PrcFmt;
+
+/// Shared slot for automatic gain staging between filters.
+/// One filter publishes the peak of its output, and a later filter in the pipeline
+/// reads it to set its input trim. Clones share the same slot.
+/// The peak is stored in dB as the bits of an `f32`, so that no mutex is needed.
+#[derive(Clone, Debug)]
+pub struct GainStagingBus {
+    peak_db: Arc<AtomicU32>,
+}
+
+impl Default for GainStagingBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GainStagingBus {
+    pub fn new() -> Self {
+        GainStagingBus {
+            peak_db: Arc::new(AtomicU32::new(f32::NAN.to_bits())),
+        }
+    }
+
+    /// Publish the peak of a processed waveform, as a voltage ratio.
+    pub fn publish_peak(&self, waveform: &[PrcFmt]) {
+        let peak = waveform
+            .iter()
+            .fold(0.0, |acc: PrcFmt, val| acc.max(val.abs()));
+        let peak_db = voltage_ratio_to_db(peak) as f32;
+        self.peak_db.store(peak_db.to_bits(), Ordering::Relaxed);
+    }
+
+    /// The latest published peak in dB, none if nothing was published yet
+    /// or the published waveform was silent.
+    pub fn peak_db(&self) -> Option<PrcFmt> {
+        let peak_db = f32::from_bits(self.peak_db.load(Ordering::Relaxed));
+        peak_db.is_finite().then_some(peak_db as PrcFmt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::gainstaging::GainStagingBus;
+
+    #[test]
+    fn clones_share_the_peak() {
+        let bus = GainStagingBus::new();
+        let reader = bus.clone();
+        assert_eq!(reader.peak_db(), None);
+        bus.publish_peak(&[0.1, -0.5, 0.25]);
+        assert!((reader.peak_db().unwrap() + 6.0206).abs() < 1.0e-3);
+        bus.publish_peak(&[0.0, 0.0]);
+        assert_eq!(reader.peak_db(), None);
+    }
+}
@@ -123,6 +123,7 @@ fn capture_loop(params: GeneratorParams, msg_channels: CaptureChannels) {
     let mut chunk_stats = ChunkStats {
         rms: vec![0.0; params.channels],
         peak: vec![0.0; params.channels],
+        peak_index: vec![0; params.channels],
     };
     let mut sine_gen;
     let mut square_gen;
@@ -0,0 +1,168 @@
+use crate::biquadcombo;
+use crate::biquadcombo::BiquadCombo;
+use crate::config;
+use crate::filters::Filter;
+use crate::limiter;
+use crate::limiter::Limiter;
+use crate::PrcFmt;
+use crate::Res;
+
+// Order of the Linkwitz-Riley crossover between the limited lows and the untouched highs.
+const CROSSOVER_ORDER: usize = 4;
+
+/// Low-frequency protection, for example for subwoofers.
+/// The lows are split off with a crossover and limited,
+/// and then summed back with the highs that are passed on untouched.
+pub struct LfProtect {
+    pub name: String,
+    samplerate: usize,
+    crossover_hz: PrcFmt,
+    lowpass: BiquadCombo,
+    highpass: BiquadCombo,
+    limiter: Limiter,
+    highs: Vec<PrcFmt>,
+}
+
+impl LfProtect {
+    /// Creates an LfProtect filter from a config struct
+    pub fn from_config(name: &str, conf: config::LfProtectParameters, samplerate: usize) -> Self {
+        debug!(
+            "Creating low-frequency protection '{}', crossover: {} Hz, clip limit: {}",
+            name, conf.crossover_hz, conf.limiter.clip_limit
+        );
+        let (lowpass, highpass) = Self::crossover(conf.crossover_hz, samplerate);
+        LfProtect {
+            name: name.to_string(),
+            samplerate,
+            crossover_hz: conf.crossover_hz,
+            lowpass,
+            highpass,
+            limiter: Limiter::from_config(name, conf.limiter),
+            highs: Vec::new(),
+        }
+    }
+
+    fn crossover(freq: PrcFmt, samplerate: usize) -> (BiquadCombo, BiquadCombo) {
+        let lowpass = config::BiquadComboParameters::LinkwitzRileyLowpass {
+            freq,
+            order: CROSSOVER_ORDER,
+        };
+        let highpass = config::BiquadComboParameters::LinkwitzRileyHighpass {
+            freq,
+            order: CROSSOVER_ORDER,
+        };
+        (
+            BiquadCombo::from_config("lf_protect_lowpass", samplerate, lowpass),
+            BiquadCombo::from_config("lf_protect_highpass", samplerate, highpass),
+        )
+    }
+}
+
+impl Filter for LfProtect {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn filter_type(&self) -> &'static str {
+        "LfProtect"
+    }
+
+    fn process_waveform(&mut self, waveform: &mut [PrcFmt]) -> Res<()> {
+        self.highs.clear();
+        self.highs.extend_from_slice(waveform);
+        self.highpass.process_waveform(&mut self.highs)?;
+        self.lowpass.process_waveform(waveform)?;
+        self.limiter.apply_clip(waveform);
+        for (item, high) in waveform.iter_mut().zip(self.highs.iter()) {
+            *item += high;
+        }
+        Ok(())
+    }
+
+    fn update_parameters(&mut self, conf: config::Filter) {
+        if let config::Filter::LfProtect {
+            parameters: conf, ..
+        } = conf
+        {
+            // Keep the crossover states unless the frequency was changed
+            if conf.crossover_hz != self.crossover_hz {
+                (self.lowpass, self.highpass) = Self::crossover(conf.crossover_hz, self.samplerate);
+                self.crossover_hz = conf.crossover_hz;
+            }
+            self.limiter.update_parameters(config::Filter::Limiter {
+                description: None,
+                parameters: conf.limiter,
+            });
+            debug!(
+                "Updated low-frequency protection '{}', crossover: {} Hz",
+                self.name, self.crossover_hz
+            );
+        } else {
+            // This should never happen unless there is a bug somewhere else
+            panic!("Invalid config change!");
+        }
+    }
+}
+
+/// Validate an LfProtect config.
+pub fn validate_config(samplerate: usize, conf: &config::LfProtectParameters) -> Res<()> {
+    biquadcombo::validate_config(
+        samplerate,
+        &config::BiquadComboParameters::LinkwitzRileyLowpass {
+            freq: conf.crossover_hz,
+            order: CROSSOVER_ORDER,
+        },
+    )?;
+    limiter::validate_config(&conf.limiter)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config;
+    use crate::dynamics_math::db_to_voltage_ratio;
+    use crate::filters::Filter;
+    use crate::lfprotect::{validate_config, LfProtect};
+    use crate::PrcFmt;
+
+    fn sine(amplitude: PrcFmt, freq: PrcFmt, len: usize) -> Vec<PrcFmt> {
+        (0..len)
+            .map(|n| {
+                amplitude
+                    * (2.0 * std::f64::consts::PI as PrcFmt * freq * n as PrcFmt / 48000.0).sin()
+            })
+            .collect()
+    }
+
+    fn peak_after_settling(filter: &mut LfProtect, mut waveform: Vec<PrcFmt>) -> PrcFmt {
+        filter.process_waveform(&mut waveform).unwrap();
+        waveform
+            .iter()
+            .skip(waveform.len() / 2)
+            .fold(0.0, |acc: PrcFmt, val| acc.max(val.abs()))
+    }
+
+    #[test]
+    fn limit_only_lows() {
+        let conf = config::LfProtectParameters {
+            crossover_hz: 200.0,
+            limiter: config::LimiterParameters {
+                soft_clip: None,
+                clip_limit: -12.0,
+            },
+        };
+        assert!(validate_config(48000, &conf).is_ok());
+        let clip_limit = db_to_voltage_ratio(-12.0);
+        // Loud highs pass unchanged, apart from the phase shift of the crossover
+        let mut filter = LfProtect::from_config("test", conf.clone(), 48000);
+        let peak = peak_after_settling(&mut filter, sine(0.8, 5000.0, 48000));
+        assert!((peak - 0.8).abs() < 0.01);
+        // Loud lows are limited
+        let mut filter = LfProtect::from_config("test", conf.clone(), 48000);
+        let peak = peak_after_settling(&mut filter, sine(0.8, 30.0, 48000));
+        assert!(peak < clip_limit * 1.05);
+
+        let mut conf = conf;
+        conf.crossover_hz = 30000.0;
+        assert!(validate_config(48000, &conf).is_err());
+    }
+}
@@ -107,19 +107,24 @@ pub mod alsadevice_buffermanager;
 #[cfg(target_os = "linux")]
 pub mod alsadevice_utils;
 pub mod audiodevice;
+pub mod balanceguard;
 pub mod basicfilters;
 pub mod biquad;
 pub mod biquadcombo;
 pub mod compressor;
 pub mod config;
+pub mod configschema;
 pub mod conversions;
 #[cfg(target_os = "macos")]
 pub mod coreaudiodevice;
 pub mod countertimer;
 #[cfg(feature = "cpal-backend")]
 pub mod cpaldevice;
+pub mod detectionbuffer;
 pub mod diffeq;
 pub mod dither;
+pub mod dynamics_math;
+pub mod envelopefile;
 pub mod fftconv;
 pub mod filedevice;
 #[cfg(all(target_os = "linux", feature = "bluez-backend"))]
@@ -129,21 +134,31 @@ pub mod filereader;
 #[cfg(target_os = "linux")]
 pub mod filereader_nonblock;
 pub mod filters;
+pub mod finalstage;
+pub mod gainstaging;
 pub mod generatordevice;
 pub mod helpers;
+pub mod lfprotect;
 pub mod limiter;
+pub mod limiterbus;
 pub mod rms_limiter;
 pub mod loudness;
 pub mod mixer;
 pub mod noisegate;
+pub mod normalizer;
+#[cfg(feature = "osc")]
+pub mod oscsender;
 pub mod processing;
 #[cfg(feature = "pulse-backend")]
 pub mod pulsedevice;
 #[cfg(feature = "websocket")]
 pub mod socketserver;
 pub mod statefile;
+pub mod upwardcompressor;
 #[cfg(target_os = "windows")]
 pub mod wasapidevice;
+#[cfg(test)]
+pub mod wavrender;
 pub mod wavtools;
 
 pub enum StatusMessage {
@@ -1,4 +1,5 @@
 use crate::config;
+use crate::dynamics_math::db_to_voltage_ratio;
 use crate::filters::Filter;
 use crate::PrcFmt;
 use crate::Res;
@@ -15,7 +16,7 @@ pub struct Limiter {
 impl Limiter {
     /// Creates a Compressor from a config struct
     pub fn from_config(name: &str, config: config::LimiterParameters) -> Self {
-        let clip_limit = (10.0 as PrcFmt).powf(config.clip_limit / 20.0);
+        let clip_limit = db_to_voltage_ratio(config.clip_limit);
 
         debug!(
             "Creating limiter '{}', soft_clip: {}, clip_limit dB: {}, linear: {}",
@@ -61,6 +62,14 @@ impl Filter for Limiter {
         &self.name
     }
 
+    fn filter_type(&self) -> &'static str {
+        "Limiter"
+    }
+
+    fn can_increase_level(&self) -> bool {
+        false
+    }
+
     /// Apply a Compressor to an AudioChunk, modifying it in-place.
     fn process_waveform(&mut self, waveform: &mut [PrcFmt]) -> Res<()> {
         self.apply_clip(waveform);
@@ -72,7 +81,7 @@ impl Filter for Limiter {
             parameters: config, ..
         } = config
         {
-            let clip_limit = (10.0 as PrcFmt).powf(config.clip_limit / 20.0);
+            let clip_limit = db_to_voltage_ratio(config.clip_limit);
 
             self.soft_clip = config.soft_clip();
             self.clip_limit = clip_limit;
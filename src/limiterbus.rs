@@ -0,0 +1,451 @@
+use crate::config;
+use crate::filters::{Filter, ProcessingError};
+use crate::rms_limiter::{self, LinkedDetection, RMSLimiter};
+use crate::PrcFmt;
+use crate::Res;
+
+/// A group of channels limited together by one RMSLimiter.
+/// The detection uses the largest sample of the member channels at each instant,
+/// and all members get the same gain, so that the balance between them is kept.
+pub struct LimiterBus {
+    pub name: String,
+    limiter: RMSLimiter,
+    channels: Vec<usize>,
+}
+
+impl LimiterBus {
+    /// Create a bus without any member channels.
+    pub fn new(
+        name: &str,
+        conf: config::RMSLimiterParameters,
+        chunksize: usize,
+        samplerate: usize,
+    ) -> Self {
+        LimiterBus {
+            name: name.to_string(),
+            limiter: RMSLimiter::from_config(name, conf, chunksize, samplerate),
+            channels: Vec::new(),
+        }
+    }
+
+    /// Add a channel to the bus. Adding a channel that is already a member does nothing.
+    pub fn add_channel(&mut self, channel: usize) {
+        if !self.channels.contains(&channel) {
+            self.channels.push(channel);
+            self.channels.sort_unstable();
+        }
+    }
+
+    /// Remove a channel from the bus. Returns false if the channel was not a member.
+    pub fn remove_channel(&mut self, channel: usize) -> bool {
+        let len = self.channels.len();
+        self.channels.retain(|member| *member != channel);
+        self.channels.len() != len
+    }
+
+    /// The member channels, in ascending order.
+    pub fn channels(&self) -> &[usize] {
+        &self.channels
+    }
+
+    /// Update the parameters shared by all members.
+    pub fn set_parameters(&mut self, conf: config::RMSLimiterParameters) {
+        self.limiter.update_parameters(config::Filter::RMSLimiter {
+            description: None,
+            parameters: conf,
+        });
+    }
+
+    /// The limiter shared by the members, for example for reading its meters.
+    pub fn limiter(&self) -> &RMSLimiter {
+        &self.limiter
+    }
+
+    /// Process one chunk of a multichannel frame.
+    /// Channels that are not members of the bus are left untouched.
+    pub fn process_frames(&mut self, frames: &mut [&mut [PrcFmt]]) -> Res<()> {
+        if let Some(channel) = self.channels.iter().find(|ch| **ch >= frames.len()) {
+            let msg = format!(
+                "Limiter bus {} has channel {}, but only got {} channels",
+                self.name,
+                channel,
+                frames.len()
+            );
+            return Err(ProcessingError::new(&msg).into());
+        }
+        // Link the members by detecting on the largest sample at each instant
+        let channels = &self.channels;
+        let mut members: Vec<&mut [PrcFmt]> = frames
+            .iter_mut()
+            .enumerate()
+            .filter(|(channel, _)| channels.contains(channel))
+            .map(|(_, waveform)| &mut **waveform)
+            .collect();
+        self.limiter
+            .process_linked(&mut members, LinkedDetection::Loudest)
+    }
+}
+
+/// Validate the parameters of a limiter bus. On top of the checks of a single limiter,
+/// the DC offset cannot be removed from the output, since it is tracked on the linked
+/// detection signal and not on the channels.
+pub fn validate_config(
+    samplerate: usize,
+    chunksize: usize,
+    conf: &config::RMSLimiterParameters,
+) -> Res<()> {
+    rms_limiter::validate_config(samplerate, chunksize, conf)?;
+    if conf.remove_dc_from_output() {
+        let msg = "Removing DC from the output cannot be used in a limiter bus";
+        return Err(config::ConfigError::new(msg).into());
+    }
+    Ok(())
+}
+
+/// One RMSLimiter per channel, for the independent multichannel case.
+/// This is the filter used for an RMSLimiter config with `channels` larger than one,
+/// and it gets all the channels of its pipeline step in one call.
+/// No state is shared between the channels, so with the `parallel-limiter` feature
+/// they can be processed on separate threads with the same result as sequentially.
+pub struct IndependentLimiters {
+    pub name: String,
+    limiters: Vec<RMSLimiter>,
+    chunksize: usize,
+    samplerate: usize,
+}
+
+impl IndependentLimiters {
+    pub fn new(
+        name: &str,
+        conf: config::RMSLimiterParameters,
+        channels: usize,
+        chunksize: usize,
+        samplerate: usize,
+    ) -> Self {
+        IndependentLimiters {
+            name: name.to_string(),
+            limiters: Self::limiters(name, &conf, channels, chunksize, samplerate),
+            chunksize,
+            samplerate,
+        }
+    }
+
+    /// Create the limiters for the number of channels given by the config.
+    pub fn from_config(
+        name: &str,
+        conf: config::RMSLimiterParameters,
+        chunksize: usize,
+        samplerate: usize,
+    ) -> Self {
+        let channels = conf.channels();
+        Self::new(name, conf, channels, chunksize, samplerate)
+    }
+
+    fn limiters(
+        name: &str,
+        conf: &config::RMSLimiterParameters,
+        channels: usize,
+        chunksize: usize,
+        samplerate: usize,
+    ) -> Vec<RMSLimiter> {
+        (0..channels)
+            .map(|_| RMSLimiter::from_config(name, Self::channel_conf(conf), chunksize, samplerate))
+            .collect()
+    }
+
+    fn channel_conf(conf: &config::RMSLimiterParameters) -> config::RMSLimiterParameters {
+        config::RMSLimiterParameters {
+            channels: None,
+            ..conf.clone()
+        }
+    }
+
+    /// The limiter of a channel, for example for reading its meters.
+    pub fn limiter(&self, channel: usize) -> Option<&RMSLimiter> {
+        self.limiters.get(channel)
+    }
+
+    fn check_channels(&self, frames: &[&mut [PrcFmt]]) -> Res<()> {
+        if frames.len() != self.limiters.len() {
+            let msg = format!(
+                "Limiters {} are set up for {} channels, but got {}",
+                self.name,
+                self.limiters.len(),
+                frames.len()
+            );
+            return Err(ProcessingError::new(&msg).into());
+        }
+        Ok(())
+    }
+
+    /// Process one chunk of a multichannel frame, one channel at a time.
+    pub fn process_frames(&mut self, frames: &mut [&mut [PrcFmt]]) -> Res<()> {
+        self.check_channels(frames)?;
+        for (limiter, waveform) in self.limiters.iter_mut().zip(frames.iter_mut()) {
+            limiter.process_waveform(waveform)?;
+        }
+        Ok(())
+    }
+
+    /// Process one chunk of a multichannel frame, with the channels in parallel.
+    #[cfg(feature = "parallel-limiter")]
+    pub fn process_frames_parallel(&mut self, frames: &mut [&mut [PrcFmt]]) -> Res<()> {
+        use rayon::prelude::*;
+
+        self.check_channels(frames)?;
+        // The errors are converted on the worker threads, since a boxed error can't be sent
+        self.limiters
+            .par_iter_mut()
+            .zip(frames.par_iter_mut())
+            .try_for_each(|(limiter, waveform)| {
+                limiter
+                    .process_waveform(waveform)
+                    .map_err(|err| ProcessingError::new(&err.to_string()))
+            })?;
+        Ok(())
+    }
+}
+
+impl Filter for IndependentLimiters {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn filter_type(&self) -> &'static str {
+        "RMSLimiter"
+    }
+
+    fn can_increase_level(&self) -> bool {
+        self.limiters
+            .iter()
+            .any(|limiter| limiter.can_increase_level())
+    }
+
+    /// A single waveform is processed as the first channel.
+    fn process_waveform(&mut self, waveform: &mut [PrcFmt]) -> Res<()> {
+        match self.limiters.first_mut() {
+            Some(limiter) => limiter.process_waveform(waveform),
+            None => Ok(()),
+        }
+    }
+
+    #[cfg(not(feature = "parallel-limiter"))]
+    fn process_frames(&mut self, frames: &mut [&mut [PrcFmt]]) -> Res<()> {
+        IndependentLimiters::process_frames(self, frames)
+    }
+
+    #[cfg(feature = "parallel-limiter")]
+    fn process_frames(&mut self, frames: &mut [&mut [PrcFmt]]) -> Res<()> {
+        self.process_frames_parallel(frames)
+    }
+
+    fn update_parameters(&mut self, conf: config::Filter) {
+        if let config::Filter::RMSLimiter {
+            parameters: conf, ..
+        } = conf
+        {
+            // Keep the states of the channels unless the number of channels was changed
+            if self.limiters.len() != conf.channels() {
+                self.limiters = Self::limiters(
+                    &self.name,
+                    &conf,
+                    conf.channels(),
+                    self.chunksize,
+                    self.samplerate,
+                );
+            } else {
+                for limiter in self.limiters.iter_mut() {
+                    limiter.update_parameters(config::Filter::RMSLimiter {
+                        description: None,
+                        parameters: Self::channel_conf(&conf),
+                    });
+                }
+            }
+        } else {
+            // This should never happen unless there is a bug somewhere else
+            panic!("Invalid config change!");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config;
+    use crate::dynamics_math::{db_to_voltage_ratio, rms, true_peak};
+    use crate::filters::{Filter, ProcessingError};
+    use crate::limiterbus::{validate_config, IndependentLimiters, LimiterBus};
+    use crate::rms_limiter::RMSLimiter;
+    use crate::PrcFmt;
+
+    fn sine(amplitude: PrcFmt, start: usize, len: usize) -> Vec<PrcFmt> {
+        (start..start + len)
+            .map(|n| amplitude * (n as PrcFmt * 0.07).sin())
+            .collect()
+    }
+
+    #[test]
+    fn uniform_gain_on_bus() {
+        let mut conf = config::RMSLimiterParameters::new(-20.0, 12.0);
+        conf.rms_samples = 1024;
+        let mut bus = LimiterBus::new("test", conf.clone(), 1024, 48000);
+        for channel in [4, 0, 1, 3, 1] {
+            bus.add_channel(channel);
+        }
+        assert_eq!(bus.channels(), &[0, 1, 3, 4]);
+        assert!(bus.remove_channel(3));
+        assert!(!bus.remove_channel(3));
+        bus.add_channel(3);
+
+        let amplitudes = [0.8, 0.4, 0.2, 0.1, 0.05];
+        let mut ratios = Vec::new();
+        for chunk in 0..20 {
+            let inputs: Vec<Vec<PrcFmt>> = amplitudes
+                .iter()
+                .map(|amplitude| sine(*amplitude, chunk * 1024, 1024))
+                .collect();
+            let mut outputs = inputs.clone();
+            let mut frames: Vec<&mut [PrcFmt]> =
+                outputs.iter_mut().map(|w| w.as_mut_slice()).collect();
+            bus.process_frames(&mut frames).unwrap();
+            ratios = inputs
+                .iter()
+                .zip(outputs.iter())
+                .map(|(input, output)| rms(output.iter()) / rms(input.iter()))
+                .collect();
+        }
+        // The loud channel limits all members by the same amount
+        assert!(ratios[0] < 0.5);
+        for channel in [1, 3, 4] {
+            assert!((ratios[channel] - ratios[0]).abs() < 1.0e-6);
+        }
+        // Channel 2 is not a member
+        assert_eq!(ratios[2], 1.0);
+
+        // Shared parameters are set once for the whole bus
+        conf.threshold = 0.0;
+        bus.set_parameters(conf);
+        let mut waveforms = vec![vec![0.0; 1024]; 3];
+        let mut frames: Vec<&mut [PrcFmt]> =
+            waveforms.iter_mut().map(|w| w.as_mut_slice()).collect();
+        let err = bus.process_frames(&mut frames).unwrap_err();
+        assert!(err.is::<ProcessingError>());
+    }
+
+    #[test]
+    fn bus_applies_the_ceiling_to_all_members() {
+        let mut conf = config::RMSLimiterParameters::new(-20.0, 12.0);
+        conf.rms_samples = 1024;
+        conf.ceiling_dbtp = Some(-1.0);
+        conf.strict_ceiling_tolerance_db = Some(0.0);
+        assert!(validate_config(48000, 1024, &conf).is_ok());
+        let mut bus = LimiterBus::new("test", conf.clone(), 1024, 48000);
+        bus.add_channel(0);
+        bus.add_channel(1);
+        for chunk in 0..5 {
+            let mut waveforms = [sine(0.9, chunk * 1024, 1024), sine(1.2, chunk * 1024, 1024)];
+            let mut frames: Vec<&mut [PrcFmt]> =
+                waveforms.iter_mut().map(|w| w.as_mut_slice()).collect();
+            bus.process_frames(&mut frames).unwrap();
+            for waveform in waveforms.iter() {
+                assert!(true_peak(waveform) <= db_to_voltage_ratio(-1.0) * 1.000001);
+            }
+        }
+        // The overs of the members are counted once per chunk
+        assert_eq!(bus.limiter().input_overs(), 5);
+
+        conf.remove_dc = Some(true);
+        conf.remove_dc_from_output = Some(true);
+        assert!(validate_config(48000, 1024, &conf).is_err());
+    }
+
+    #[test]
+    fn independent_limiters() {
+        let mut conf = config::RMSLimiterParameters::new(-20.0, 12.0);
+        conf.rms_samples = 1024;
+        let mut limiters = IndependentLimiters::new("test", conf, 2, 1024, 48000);
+        for chunk in 0..20 {
+            let mut waveforms = [
+                sine(0.8, chunk * 1024, 1024),
+                sine(0.05, chunk * 1024, 1024),
+            ];
+            let mut frames: Vec<&mut [PrcFmt]> =
+                waveforms.iter_mut().map(|w| w.as_mut_slice()).collect();
+            limiters.process_frames(&mut frames).unwrap();
+        }
+        // Only the loud channel is limited
+        assert!(limiters.limiter(0).unwrap().metered_reduction_db() > 1.0);
+        assert_eq!(limiters.limiter(1).unwrap().metered_reduction_db(), 0.0);
+        let mut waveforms = vec![vec![0.0; 1024]; 3];
+        let mut frames: Vec<&mut [PrcFmt]> =
+            waveforms.iter_mut().map(|w| w.as_mut_slice()).collect();
+        let err = Filter::process_frames(&mut limiters, &mut frames).unwrap_err();
+        assert!(err.is::<ProcessingError>());
+    }
+
+    #[test]
+    fn independent_channel_states() {
+        let mut conf = config::RMSLimiterParameters::new(-20.0, 12.0);
+        conf.rms_samples = 1024;
+        conf.channels = Some(3);
+        let mut limiters = IndependentLimiters::from_config("test", conf.clone(), 1024, 48000);
+        let mut references: Vec<RMSLimiter> = (0..3)
+            .map(|_| {
+                let mut reference = config::RMSLimiterParameters::new(-20.0, 12.0);
+                reference.rms_samples = 1024;
+                RMSLimiter::from_config("test", reference, 1024, 48000)
+            })
+            .collect();
+        let levels = [0.8, 0.02, 0.3];
+        for _ in 0..5 {
+            let mut waveforms: Vec<Vec<PrcFmt>> =
+                levels.iter().map(|level| vec![*level; 1024]).collect();
+            let mut expected = waveforms.clone();
+            let mut frames: Vec<&mut [PrcFmt]> =
+                waveforms.iter_mut().map(|w| w.as_mut_slice()).collect();
+            Filter::process_frames(&mut limiters, &mut frames).unwrap();
+            for (reference, waveform) in references.iter_mut().zip(expected.iter_mut()) {
+                reference.process_waveform(waveform).unwrap();
+            }
+            assert_eq!(waveforms, expected);
+        }
+        // Only the quiet channel is left untouched
+        assert!(limiters.limiter(0).unwrap().metered_reduction_db() > 1.0);
+        assert_eq!(limiters.limiter(1).unwrap().metered_reduction_db(), 0.0);
+        assert!(limiters.limiter(2).unwrap().metered_reduction_db() > 1.0);
+
+        // Changing the number of channels makes new limiters
+        conf.channels = Some(2);
+        limiters.update_parameters(config::Filter::RMSLimiter {
+            description: None,
+            parameters: conf,
+        });
+        let mut waveforms = vec![vec![0.0; 1024]; 3];
+        let mut frames: Vec<&mut [PrcFmt]> =
+            waveforms.iter_mut().map(|w| w.as_mut_slice()).collect();
+        assert!(Filter::process_frames(&mut limiters, &mut frames).is_err());
+    }
+
+    #[cfg(feature = "parallel-limiter")]
+    #[test]
+    fn parallel_matches_sequential() {
+        let mut conf = config::RMSLimiterParameters::new(-20.0, 12.0);
+        conf.rms_samples = 1024;
+        let mut sequential = IndependentLimiters::new("test", conf.clone(), 32, 1024, 48000);
+        let mut parallel = IndependentLimiters::new("test", conf, 32, 1024, 48000);
+        for chunk in 0..20 {
+            let inputs: Vec<Vec<PrcFmt>> = (0..32)
+                .map(|channel| sine(0.03 * channel as PrcFmt, chunk * 1024, 1024))
+                .collect();
+            let mut first = inputs.clone();
+            let mut second = inputs;
+            let mut frames: Vec<&mut [PrcFmt]> =
+                first.iter_mut().map(|w| w.as_mut_slice()).collect();
+            sequential.process_frames(&mut frames).unwrap();
+            let mut frames: Vec<&mut [PrcFmt]> =
+                second.iter_mut().map(|w| w.as_mut_slice()).collect();
+            // The Filter trait takes the parallel path when the feature is enabled
+            Filter::process_frames(&mut parallel, &mut frames).unwrap();
+            assert_eq!(first, second);
+        }
+    }
+}
@@ -90,6 +90,10 @@ impl Filter for Loudness {
         &self.name
     }
 
+    fn filter_type(&self) -> &'static str {
+        "Loudness"
+    }
+
     fn process_waveform(&mut self, waveform: &mut [PrcFmt]) -> Res<()> {
         let shared_vol = self.processing_params.current_volume(self.fader);
 
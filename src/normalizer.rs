@@ -0,0 +1,180 @@
+use crate::config;
+use crate::dynamics_math::{db_to_voltage_ratio, rms, voltage_ratio_to_db};
+use crate::filters::Filter;
+use crate::rms_limiter;
+use crate::rms_limiter::RMSLimiter;
+use crate::PrcFmt;
+use crate::Res;
+
+// Chunks quieter than this are treated as silence, and do not change the measured loudness.
+const LOUDNESS_GATE_DB: PrcFmt = -70.0;
+// Largest boost or cut applied by the normalizing gain.
+const MAX_NORMALIZE_GAIN_DB: PrcFmt = 20.0;
+
+/// Program loudness normalizer, for example for podcasts and streaming.
+/// A slow gain brings the loudness of the input towards a target,
+/// and a limiter keeps the result below its threshold and ceiling.
+pub struct Normalizer {
+    pub name: String,
+    limiter: RMSLimiter,
+    target_loudness_db: PrcFmt,
+    loudness_db: PrcFmt,
+    gain: PrcFmt,
+}
+
+impl Normalizer {
+    /// Creates a Normalizer filter from a config struct
+    pub fn from_config(
+        name: &str,
+        conf: config::NormalizerParameters,
+        chunksize: usize,
+        samplerate: usize,
+    ) -> Self {
+        debug!(
+            "Creating normalizer '{}', target loudness: {}, limiter threshold: {}",
+            name, conf.target_loudness_db, conf.limiter.threshold
+        );
+        let target_loudness_db = conf.target_loudness_db as PrcFmt;
+        let limiter = RMSLimiter::from_config(name, conf.limiter, chunksize, samplerate);
+        Normalizer {
+            name: name.to_string(),
+            limiter,
+            target_loudness_db,
+            loudness_db: target_loudness_db,
+            gain: 1.0,
+        }
+    }
+
+    /// The normalizing gain in dB currently applied before the limiter.
+    pub fn normalize_gain_db(&self) -> PrcFmt {
+        voltage_ratio_to_db(self.gain)
+    }
+}
+
+impl Filter for Normalizer {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn filter_type(&self) -> &'static str {
+        "Normalizer"
+    }
+
+    fn process_waveform(&mut self, waveform: &mut [PrcFmt]) -> Res<()> {
+        if waveform.is_empty() {
+            return Ok(());
+        }
+        let chunk_db = voltage_ratio_to_db(rms(waveform.iter()));
+        // Measure the loudness with the same slow integrator as the automatic threshold
+        if chunk_db > LOUDNESS_GATE_DB {
+            self.loudness_db = self.limiter.integrate_loudness(self.loudness_db, chunk_db);
+        }
+        let gain_db = (self.target_loudness_db - self.loudness_db)
+            .clamp(-MAX_NORMALIZE_GAIN_DB, MAX_NORMALIZE_GAIN_DB);
+        let target_gain = db_to_voltage_ratio(gain_db);
+
+        // Ramp the gain over the chunk to avoid steps between chunks
+        let step = (target_gain - self.gain) / waveform.len() as PrcFmt;
+        for item in waveform.iter_mut() {
+            self.gain += step;
+            *item *= self.gain;
+        }
+        self.gain = target_gain;
+
+        self.limiter.process_waveform(waveform)
+    }
+
+    fn update_parameters(&mut self, conf: config::Filter) {
+        if let config::Filter::Normalizer {
+            parameters: conf, ..
+        } = conf
+        {
+            self.target_loudness_db = conf.target_loudness_db as PrcFmt;
+            self.limiter.update_parameters(config::Filter::RMSLimiter {
+                description: None,
+                parameters: conf.limiter,
+            });
+            debug!(
+                "Updated normalizer '{}', target loudness: {}",
+                self.name, self.target_loudness_db
+            );
+        } else {
+            // This should never happen unless there is a bug somewhere else
+            panic!("Invalid config change!");
+        }
+    }
+}
+
+/// Validate a Normalizer config.
+pub fn validate_config(
+    samplerate: usize,
+    chunksize: usize,
+    conf: &config::NormalizerParameters,
+) -> Res<()> {
+    if conf.target_loudness_db > 0.0 {
+        let msg = "Target loudness must be below 0 dB";
+        return Err(config::ConfigError::new(msg).into());
+    }
+    rms_limiter::validate_config(samplerate, chunksize, &conf.limiter)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config;
+    use crate::dynamics_math::{db_to_voltage_ratio, rms, voltage_ratio_to_db};
+    use crate::filters::Filter;
+    use crate::normalizer::{validate_config, Normalizer};
+    use crate::PrcFmt;
+
+    fn sine(amplitude: PrcFmt, start: usize, len: usize) -> Vec<PrcFmt> {
+        (start..start + len)
+            .map(|n| amplitude * (n as PrcFmt * 0.07).sin())
+            .collect()
+    }
+
+    #[test]
+    fn normalize_and_limit() {
+        let mut limiter = config::RMSLimiterParameters::new(-6.0, 12.0);
+        limiter.rms_samples = 1024;
+        limiter.ceiling_dbtp = Some(-1.0);
+        let conf = config::NormalizerParameters {
+            target_loudness_db: -20.0,
+            limiter,
+        };
+        let mut filter = Normalizer::from_config("test", conf, 1200, 12000);
+        let ceiling = db_to_voltage_ratio(-1.0);
+        // Segments of 20 s, first quiet and then loud
+        for amplitude in [0.05, 0.8] {
+            let mut level_db = 0.0;
+            for chunk in 0..200 {
+                let mut waveform = sine(amplitude, chunk * 1200, 1200);
+                filter.process_waveform(&mut waveform).unwrap();
+                assert!(waveform.iter().all(|v| v.abs() <= ceiling));
+                level_db = voltage_ratio_to_db(rms(waveform.iter()));
+            }
+            // The output has converged to the target
+            assert!((level_db + 20.0).abs() < 0.1);
+        }
+        assert!(filter.normalize_gain_db() < 0.0);
+    }
+
+    #[test]
+    fn gain_is_limited() {
+        let mut limiter = config::RMSLimiterParameters::new(-6.0, 12.0);
+        limiter.rms_samples = 1024;
+        let conf = config::NormalizerParameters {
+            target_loudness_db: -20.0,
+            limiter,
+        };
+        assert!(validate_config(48000, 1024, &conf).is_ok());
+        let mut filter = Normalizer::from_config("test", conf.clone(), 1024, 48000);
+        for chunk in 0..470 {
+            let mut waveform = sine(0.001, chunk * 1024, 1024);
+            filter.process_waveform(&mut waveform).unwrap();
+        }
+        assert!((filter.normalize_gain_db() - 20.0).abs() < 1.0e-3);
+        let mut conf = conf;
+        conf.target_loudness_db = 3.0;
+        assert!(validate_config(48000, 1024, &conf).is_err());
+    }
+}
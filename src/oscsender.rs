@@ -0,0 +1,65 @@
+use std::net::UdpSocket;
+use std::sync::Arc;
+
+use crate::PrcFmt;
+use crate::Res;
+
+/// Sends values as OSC messages over UDP. Clones share the socket.
+#[derive(Clone)]
+pub struct OscSender {
+    socket: Arc<UdpSocket>,
+    path: String,
+}
+
+impl OscSender {
+    pub fn new(target: &str, path: &str) -> Res<Self> {
+        let socket = UdpSocket::bind("0.0.0.0:0")?;
+        socket.connect(target)?;
+        socket.set_nonblocking(true)?;
+        Ok(OscSender {
+            socket: Arc::new(socket),
+            path: path.to_string(),
+        })
+    }
+
+    /// Send a value, without waiting. A message that cannot be sent is dropped.
+    // The cast is needed when PrcFmt is f64
+    #[allow(clippy::unnecessary_cast)]
+    pub fn send(&self, value: PrcFmt) {
+        let message = encode_float_message(&self.path, value as f32);
+        if let Err(err) = self.socket.send(&message) {
+            debug!("Unable to send OSC message to {}: {}", self.path, err);
+        }
+    }
+}
+
+/// Encode an OSC message with a single float argument.
+pub fn encode_float_message(path: &str, value: f32) -> Vec<u8> {
+    let mut message = Vec::new();
+    push_padded_string(&mut message, path);
+    push_padded_string(&mut message, ",f");
+    message.extend_from_slice(&value.to_be_bytes());
+    message
+}
+
+/// OSC strings are null terminated and padded with nulls to a multiple of four bytes.
+fn push_padded_string(message: &mut Vec<u8>, value: &str) {
+    message.extend_from_slice(value.as_bytes());
+    let padding = 4 - value.len() % 4;
+    message.resize(message.len() + padding, 0);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::oscsender::encode_float_message;
+
+    #[test]
+    fn encode_message() {
+        let message = encode_float_message("/gain", 1.0);
+        let mut expected = b"/gain\0\0\0,f\0\0".to_vec();
+        expected.extend_from_slice(&[0x3f, 0x80, 0, 0]);
+        assert_eq!(message, expected);
+        // A path of four bytes still needs a terminating null
+        assert_eq!(encode_float_message("/abc", 0.0).len(), 16);
+    }
+}
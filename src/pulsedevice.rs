@@ -162,6 +162,7 @@ impl PlaybackDevice for PulsePlaybackDevice {
                         let mut chunk_stats = ChunkStats {
                             rms: vec![0.0; channels],
                             peak: vec![0.0; channels],
+                            peak_index: vec![0; channels],
                         };
                         let bytes_per_frame = channels * store_bytes_per_sample;
                         barrier.wait();
@@ -315,7 +316,7 @@ impl CaptureDevice for PulseCaptureDevice {
                         let mut value_range = 0.0;
                         let mut rate_adjust = 0.0;
                         let mut state = ProcessingState::Running;
-                        let mut chunk_stats = ChunkStats{rms: vec![0.0; channels], peak: vec![0.0; channels]};
+                        let mut chunk_stats = ChunkStats{rms: vec![0.0; channels], peak: vec![0.0; channels], peak_index: vec![0; channels]};
                         let bytes_per_frame = channels * store_bytes_per_sample;
                         let mut channel_mask = vec![true; channels];
                         let mut last_instant = Instant::now();
@@ -1,18 +1,326 @@
+use crate::biquadcombo::BiquadCombo;
 use crate::config;
-use crate::filters::Filter;
-use ringbuffer::{AllocRingBuffer, RingBuffer, RingBufferExt, RingBufferWrite};
+use crate::detectionbuffer::{DefaultDetectionBuffer, DetectionBuffer};
+use crate::dynamics_math::{
+    chunk_coeff, chunk_duration, db_to_voltage_ratio, decay_coeff, flush_denormal, rms,
+    shave_peaks, soft_clip, true_peak, true_peak_cost, voltage_ratio_to_db, window_coefficients,
+    TRUE_PEAK_HALF_TAPS, TRUE_PEAK_OVERSAMPLING,
+};
+use crate::envelopefile::{write_envelope, Envelope};
+use crate::filters::{Filter, ProcessingError};
+use crate::gainstaging::GainStagingBus;
+#[cfg(feature = "osc")]
+use crate::oscsender::OscSender;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
 
 use crate::PrcFmt;
 use crate::Res;
 
-pub struct RMSLimiter {
+// Time constant in seconds for the output loudness measurement used by the auto threshold.
+const AUTO_THRESHOLD_TIME: PrcFmt = 3.0;
+
+// Attack and release time constants in seconds of the gain reduction meter.
+const METER_ATTACK_TIME: PrcFmt = 0.01;
+const METER_RELEASE_TIME: PrcFmt = 1.5;
+
+// Number of 1 dB wide bins in the gain reduction histogram.
+// The last bin also counts all larger reductions.
+pub const HISTOGRAM_BINS: usize = 24;
+
+// Time constant in seconds of the running mean used to track a DC offset.
+const DC_TRACKING_TIME: PrcFmt = 0.1;
+
+// Time in seconds to blend from the old to the new detection window when rms_samples changes.
+const WINDOW_TRANSITION_TIME: PrcFmt = 0.1;
+
+// Identifier at the start of a saved limiter state.
+const STATE_MAGIC: &[u8] = "CDSPLIM1".as_bytes();
+// Largest relative difference of the gain coefficients tolerated when loading a state.
+const STATE_COEFF_TOLERANCE: PrcFmt = 1.0e-6;
+
+// Headroom in dB above the measured rms level for the threshold suggested by a calibration.
+const CALIBRATION_HEADROOM_DB: PrcFmt = 3.0;
+
+// Length in seconds of the gain ramps around the peaks in peak shaver mode.
+const PEAK_SHAVER_SMOOTHING_TIME: PrcFmt = 0.0005;
+
+// Largest input trim in dB, up or down, set by automatic gain staging.
+const MAX_STAGING_TRIM_DB: PrcFmt = 24.0;
+
+// Rough cost in multiply-adds per sample of a biquad, a tanh and the other per sample steps,
+// used by the cost estimate.
+const BIQUAD_COST: f64 = 5.0;
+const TANH_COST: f64 = 10.0;
+const SAMPLE_COST: f64 = 3.0;
+
+// Headroom in dB below full scale that input overs are trimmed to.
+const INPUT_OVER_HEADROOM_DB: PrcFmt = 0.1;
+
+// Drive of the warmth saturation at full warmth and full gain reduction.
+const WARMTH_MAX_DRIVE: PrcFmt = 4.0;
+
+// Range and resolution in dB of the lookup table built from a custom gain curve.
+const CURVE_TABLE_START_DB: PrcFmt = -120.0;
+const CURVE_TABLE_END_DB: PrcFmt = 30.0;
+const CURVE_TABLE_STEP_DB: PrcFmt = 0.1;
+
+// Order of the Linkwitz-Riley crossovers between the bands of a weighted detection.
+const WEIGHTING_CROSSOVER_ORDER: usize = 4;
+
+/// Filter bank for frequency weighted detection.
+/// The signal is split into one band per weight, with crossovers halfway between
+/// the weight frequencies on a log scale, and the bands are summed with their weights.
+#[derive(Clone)]
+struct DetectionWeighting {
+    weights_conf: Vec<(f32, f32)>,
+    lowpasses: Vec<BiquadCombo>,
+    highpasses: Vec<BiquadCombo>,
+    weights: Vec<PrcFmt>,
+    band: Vec<PrcFmt>,
+    rest: Vec<PrcFmt>,
+    output: Vec<PrcFmt>,
+}
+
+impl DetectionWeighting {
+    fn from_weights(weights_conf: &[(f32, f32)], samplerate: usize) -> Self {
+        let crossover = |pair: &[(f32, f32)]| (pair[0].0 as PrcFmt * pair[1].0 as PrcFmt).sqrt();
+        let lowpasses = weights_conf
+            .windows(2)
+            .map(|pair| {
+                let parameters = config::BiquadComboParameters::LinkwitzRileyLowpass {
+                    freq: crossover(pair),
+                    order: WEIGHTING_CROSSOVER_ORDER,
+                };
+                BiquadCombo::from_config("detection_weighting", samplerate, parameters)
+            })
+            .collect();
+        let highpasses = weights_conf
+            .windows(2)
+            .map(|pair| {
+                let parameters = config::BiquadComboParameters::LinkwitzRileyHighpass {
+                    freq: crossover(pair),
+                    order: WEIGHTING_CROSSOVER_ORDER,
+                };
+                BiquadCombo::from_config("detection_weighting", samplerate, parameters)
+            })
+            .collect();
+        DetectionWeighting {
+            weights_conf: weights_conf.to_vec(),
+            lowpasses,
+            highpasses,
+            weights: weights_conf.iter().map(|(_, w)| *w as PrcFmt).collect(),
+            band: Vec::new(),
+            rest: Vec::new(),
+            output: Vec::new(),
+        }
+    }
+
+    /// Calculate the weighted signal of a chunk, leaving the result in `output`.
+    fn process(&mut self, waveform: &[PrcFmt]) {
+        self.rest.clear();
+        self.rest.extend_from_slice(waveform);
+        self.output.clear();
+        self.output.resize(waveform.len(), 0.0);
+        for (n, (lowpass, highpass)) in self
+            .lowpasses
+            .iter_mut()
+            .zip(self.highpasses.iter_mut())
+            .enumerate()
+        {
+            // Split off the lowest remaining band
+            self.band.clear();
+            self.band.extend_from_slice(&self.rest);
+            // The crossover filters cannot fail
+            let _ = lowpass.process_waveform(&mut self.band);
+            let _ = highpass.process_waveform(&mut self.rest);
+            for (out, value) in self.output.iter_mut().zip(self.band.iter()) {
+                *out += self.weights[n] * value;
+            }
+        }
+        let last = self.weights[self.weights.len() - 1];
+        for (out, value) in self.output.iter_mut().zip(self.rest.iter()) {
+            *out += last * value;
+        }
+    }
+}
+
+/// Dense lookup table of gains in dB for input levels in dB,
+/// built by interpolating a user supplied curve.
+#[derive(Clone)]
+struct GainTable {
+    gains_db: Vec<PrcFmt>,
+}
+
+impl GainTable {
+    fn from_curve(curve: &[(f32, f32)]) -> Self {
+        let curve: Vec<(PrcFmt, PrcFmt)> = curve
+            .iter()
+            .map(|(input, gain)| (*input as PrcFmt, *gain as PrcFmt))
+            .collect();
+        let nbr_values = ((CURVE_TABLE_END_DB - CURVE_TABLE_START_DB) / CURVE_TABLE_STEP_DB).round()
+            as usize
+            + 1;
+        let gains_db = (0..nbr_values)
+            .map(|n| {
+                let level_db = CURVE_TABLE_START_DB + n as PrcFmt * CURVE_TABLE_STEP_DB;
+                interpolate_linear(&curve, level_db)
+            })
+            .collect();
+        GainTable { gains_db }
+    }
+
+    /// Look up the linear gain for an input level in dB.
+    fn gain(&self, level_db: PrcFmt) -> PrcFmt {
+        let last = self.gains_db.len() - 1;
+        let position =
+            ((level_db - CURVE_TABLE_START_DB) / CURVE_TABLE_STEP_DB).clamp(0.0, last as PrcFmt);
+        let idx = position.floor() as usize;
+        let gain_db = if idx >= last {
+            self.gains_db[last]
+        } else {
+            let fraction = position - idx as PrcFmt;
+            self.gains_db[idx] + fraction * (self.gains_db[idx + 1] - self.gains_db[idx])
+        };
+        db_to_voltage_ratio(gain_db)
+    }
+}
+
+/// What the gain of the limiter did in the latest chunk.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Phase {
+    /// At unity gain, not limiting.
+    Idle,
+    /// The gain is dropping.
+    Attack,
+    /// The gain is pinned below unity.
+    Hold,
+    /// The gain is rising back towards unity.
+    Release,
+}
+
+/// A parameter change that is scheduled with `RMSLimiter::schedule_change`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ParamChange {
+    /// New threshold in dB.
+    Threshold(PrcFmt),
+    /// New input gain in dB.
+    InputGain(PrcFmt),
+}
+
+/// Where the level is detected when several channels share one gain.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum LinkedDetection {
+    /// From one of the channels.
+    Channel(usize),
+    /// From the largest sample of all the channels at each instant.
+    Loudest,
+}
+
+#[derive(Clone)]
+pub struct RMSLimiter<B: DetectionBuffer = DefaultDetectionBuffer> {
     pub name: String,
     samplerate: usize,
     chunksize: usize,
-    rms_buffer: AllocRingBuffer<PrcFmt>,
+    rms_buffer: B,
     threshold_voltage_ratio: PrcFmt,
     decay_per_chunk: PrcFmt,
     current_gain: PrcFmt,
+    soft: bool,
+    knee: Knee,
+    wait_for_warmup: bool,
+    max_threshold_db: PrcFmt,
+    auto_threshold_target_db: Option<PrcFmt>,
+    auto_threshold_coeff: PrcFmt,
+    output_loudness_db: PrcFmt,
+    threshold_relative_db: Option<PrcFmt>,
+    program_level_db: PrcFmt,
+    meter_attack_coeff: PrcFmt,
+    meter_release_coeff: PrcFmt,
+    metered_reduction_db: PrcFmt,
+    true_peak_ceiling: Option<PrcFmt>,
+    ceiling_trim: PrcFmt,
+    // The end of the previous output chunk of each channel,
+    // for measuring the peaks across the chunk boundary
+    output_tails: Vec<Vec<PrcFmt>>,
+    max_overshoot_db: Option<PrcFmt>,
+    hold: bool,
+    input_gain: PrcFmt,
+    histogram: Option<[u32; HISTOGRAM_BINS]>,
+    gain_table: Option<GainTable>,
+    analyze_only: bool,
+    bypassed: bool,
+    manual: bool,
+    bypass_mix: PrcFmt,
+    bypass_step: PrcFmt,
+    detection_history: VecDeque<PrcFmt>,
+    detection_hold_chunks: usize,
+    detection: config::DetectionType,
+    weighting: Option<DetectionWeighting>,
+    warmth: PrcFmt,
+    makeup_fraction: PrcFmt,
+    channel_trim: PrcFmt,
+    linear_release: bool,
+    silence_level: Option<PrcFmt>,
+    sensitivity: PrcFmt,
+    remove_dc: bool,
+    remove_dc_from_output: bool,
+    dc_coeff: PrcFmt,
+    dc_offset: PrcFmt,
+    // The DC offset at each sample of the latest measured chunk, when it is removed from the output
+    output_dc: Vec<PrcFmt>,
+    detection_decimation: usize,
+    decimation_phase: usize,
+    recorded_schedule: Option<Vec<PrcFmt>>,
+    // The number of samples that each recorded gain was applied to
+    recorded_lengths: Vec<usize>,
+    replayed_schedule: VecDeque<PrcFmt>,
+    scheduled_changes: VecDeque<(usize, ParamChange)>,
+    mute_on_fault: bool,
+    gain_fault: bool,
+    detect_from_channel: Option<usize>,
+    active_ranges: Option<Vec<(usize, usize)>>,
+    sample_position: usize,
+    in_active_range: bool,
+    strict_ceiling_tolerance_db: Option<PrcFmt>,
+    max_output_over_ceiling_db: Option<PrcFmt>,
+    previous_buffer: Option<B>,
+    transition_chunks: usize,
+    transition_left: usize,
+    detected_level: PrcFmt,
+    phase: Phase,
+    max_gain_step_db: Option<PrcFmt>,
+    max_reduction_step_db: Option<PrcFmt>,
+    clip_ceiling: Option<PrcFmt>,
+    attack_time: Option<PrcFmt>,
+    attack_curve: config::AttackCurve,
+    // The gain at the start of a linear or S-curve attack, and the time since then
+    attack_start_gain: Option<PrcFmt>,
+    attack_elapsed: PrcFmt,
+    release_shelf: Option<PrcFmt>,
+    release_retrigger: Option<PrcFmt>,
+    calibration_samples_left: usize,
+    calibration_squared_sum: PrcFmt,
+    calibration_count: usize,
+    calibration_peak: PrcFmt,
+    calibration_result: Option<PrcFmt>,
+    peak_shaver: bool,
+    // Scratch buffer for the gains of the peak shaver
+    shave_gains: Vec<PrcFmt>,
+    release_start_gain: PrcFmt,
+    staging_output: Option<GainStagingBus>,
+    staging_input: Option<(GainStagingBus, PrcFmt)>,
+    staging_trim: PrcFmt,
+    trim_input_overs: bool,
+    over_trim: PrcFmt,
+    input_overs: usize,
+    max_input_peak: PrcFmt,
+    detection_window: config::WindowFunction,
+    window: Option<Vec<PrcFmt>>,
+    previous_window: Option<Vec<PrcFmt>>,
+    #[cfg(feature = "osc")]
+    osc_sender: Option<OscSender>,
 }
 
 impl RMSLimiter {
@@ -22,9 +330,21 @@ impl RMSLimiter {
         chunksize: usize,
         samplerate: usize,
     ) -> Self {
-        let decay_per_chunk = RMSLimiter::decay_per_chunk(chunksize, samplerate, &conf);
-        let threshold_voltage_ratio = RMSLimiter::db_to_voltage_ratio(conf.threshold as PrcFmt);
-        let rms_buffer = AllocRingBuffer::with_capacity(conf.rms_samples);
+        Self::with_detection_buffer(name, conf, chunksize, samplerate)
+    }
+}
+
+impl<B: DetectionBuffer> RMSLimiter<B> {
+    /// Create a limiter using a given backing for the detection buffer.
+    pub fn with_detection_buffer(
+        name: &str,
+        conf: config::RMSLimiterParameters,
+        chunksize: usize,
+        samplerate: usize,
+    ) -> Self {
+        let decay_per_chunk = Self::decay_per_chunk(chunksize, samplerate, &conf);
+        let threshold_voltage_ratio = db_to_voltage_ratio(conf.threshold as PrcFmt);
+        let rms_buffer = B::with_capacity(conf.detection_buffer_size());
 
         RMSLimiter {
             name: name.to_string(),
@@ -34,98 +354,3752 @@ impl RMSLimiter {
             threshold_voltage_ratio,
             current_gain: 1.0,
             decay_per_chunk,
+            soft: conf.soft(),
+            knee: Knee::from_config(&conf),
+            wait_for_warmup: conf.wait_for_warmup(),
+            max_threshold_db: conf.threshold as PrcFmt,
+            auto_threshold_target_db: conf.auto_threshold_target_db.map(|t| t as PrcFmt),
+            auto_threshold_coeff: chunk_coeff(AUTO_THRESHOLD_TIME, chunksize, samplerate),
+            output_loudness_db: conf.threshold as PrcFmt,
+            threshold_relative_db: conf.threshold_relative_db.map(|t| t as PrcFmt),
+            // Start from the level where the relative threshold equals the configured one
+            program_level_db: (conf.threshold - conf.threshold_relative_db.unwrap_or_default())
+                as PrcFmt,
+            meter_attack_coeff: chunk_coeff(METER_ATTACK_TIME, chunksize, samplerate),
+            meter_release_coeff: chunk_coeff(METER_RELEASE_TIME, chunksize, samplerate),
+            metered_reduction_db: 0.0,
+            true_peak_ceiling: conf.ceiling_dbtp.map(|c| db_to_voltage_ratio(c as PrcFmt)),
+            ceiling_trim: db_to_voltage_ratio(-conf.ceiling_trim_db() as PrcFmt),
+            output_tails: Vec::new(),
+            max_overshoot_db: None,
+            hold: conf.decay_db_per_s() == 0.0,
+            input_gain: db_to_voltage_ratio(conf.input_gain_db() as PrcFmt),
+            histogram: conf.reduction_histogram().then_some([0; HISTOGRAM_BINS]),
+            gain_table: conf.curve_table.as_ref().map(|c| GainTable::from_curve(c)),
+            weighting: conf
+                .detection_weights
+                .as_ref()
+                .map(|w| DetectionWeighting::from_weights(w, samplerate)),
+            analyze_only: conf.analyze_only(),
+            bypassed: conf.bypassed(),
+            manual: conf.manual(),
+            bypass_mix: if conf.bypassed() || !Self::is_active_at(&conf.active_ranges, 0) {
+                0.0
+            } else {
+                1.0
+            },
+            bypass_step: Self::bypass_step(samplerate, &conf),
+            detection_history: VecDeque::new(),
+            detection_hold_chunks: Self::detection_hold_chunks(chunksize, samplerate, &conf),
+            detection: conf.detection(),
+            warmth: conf.warmth() as PrcFmt,
+            makeup_fraction: conf.makeup_fraction() as PrcFmt,
+            channel_trim: db_to_voltage_ratio(conf.channel_trim_db() as PrcFmt),
+            linear_release: conf.release_domain() == config::ReleaseDomain::Linear,
+            silence_level: Self::silence_level(&conf),
+            sensitivity: db_to_voltage_ratio(conf.sensitivity_db() as PrcFmt),
+            remove_dc: conf.remove_dc(),
+            remove_dc_from_output: conf.remove_dc_from_output(),
+            dc_coeff: 1.0 - (-1.0 / (DC_TRACKING_TIME * samplerate as PrcFmt)).exp(),
+            dc_offset: 0.0,
+            output_dc: Vec::new(),
+            detection_decimation: conf.detection_decimation(),
+            decimation_phase: 0,
+            recorded_schedule: None,
+            recorded_lengths: Vec::new(),
+            replayed_schedule: VecDeque::new(),
+            scheduled_changes: VecDeque::new(),
+            mute_on_fault: conf.mute_on_fault(),
+            gain_fault: false,
+            detect_from_channel: conf.detect_from_channel,
+            in_active_range: Self::is_active_at(&conf.active_ranges, 0),
+            active_ranges: conf.active_ranges.clone(),
+            sample_position: 0,
+            strict_ceiling_tolerance_db: conf.strict_ceiling_tolerance_db.map(|t| t as PrcFmt),
+            max_output_over_ceiling_db: None,
+            previous_buffer: None,
+            transition_chunks: (WINDOW_TRANSITION_TIME / chunk_duration(chunksize, samplerate))
+                .ceil() as usize,
+            transition_left: 0,
+            detected_level: 0.0,
+            phase: Phase::Idle,
+            max_gain_step_db: Self::max_gain_step_db(chunksize, samplerate, &conf),
+            max_reduction_step_db: Self::max_reduction_step_db(chunksize, samplerate, &conf),
+            clip_ceiling: conf
+                .clip_ceiling_db
+                .map(|c| db_to_voltage_ratio(c as PrcFmt)),
+            attack_time: Self::attack_time(samplerate, &conf),
+            attack_curve: conf.attack_curve(),
+            attack_start_gain: None,
+            attack_elapsed: 0.0,
+            release_shelf: Self::release_shelf(&conf),
+            release_retrigger: Self::release_retrigger(&conf),
+            calibration_samples_left: conf
+                .calibration_ms
+                .map_or(0, |ms| (ms * samplerate as f32 / 1000.0).round() as usize),
+            calibration_squared_sum: 0.0,
+            calibration_count: 0,
+            calibration_peak: 0.0,
+            calibration_result: None,
+            peak_shaver: conf.peak_shaver(),
+            shave_gains: Vec::with_capacity(chunksize),
+            release_start_gain: 1.0,
+            staging_output: None,
+            staging_input: None,
+            staging_trim: 1.0,
+            trim_input_overs: conf.trim_input_overs(),
+            over_trim: 1.0,
+            input_overs: 0,
+            max_input_peak: 1.0,
+            detection_window: conf.detection_window(),
+            window: Self::window(conf.detection_window(), conf.detection_buffer_size()),
+            previous_window: None,
+            #[cfg(feature = "osc")]
+            osc_sender: Self::osc_sender(name, &conf),
+        }
+    }
+
+    /// Coefficients of the detection window, none for a flat window.
+    fn window(window: config::WindowFunction, capacity: usize) -> Option<Vec<PrcFmt>> {
+        (window != config::WindowFunction::Rectangular)
+            .then(|| window_coefficients(window, capacity))
+    }
+
+    /// Detect the level of a buffer, weighted by the window if there is one.
+    fn buffer_level(
+        buffer: &B,
+        window: Option<&[PrcFmt]>,
+        detection: config::DetectionType,
+    ) -> PrcFmt {
+        match window {
+            Some(weights) => buffer.weighted_level(weights),
+            None => buffer.level(detection),
+        }
+    }
+
+    /// Create the sender for the gain reduction, if an OSC target is configured.
+    #[cfg(feature = "osc")]
+    fn osc_sender(name: &str, conf: &config::RMSLimiterParameters) -> Option<OscSender> {
+        let target = conf.osc_target.as_ref()?;
+        match OscSender::new(target, &conf.osc_path()) {
+            Ok(sender) => Some(sender),
+            Err(err) => {
+                error!("RMSLimiter '{name}' can't send OSC messages to {target}: {err}");
+                None
+            }
+        }
+    }
+
+    /// Level below which the gain is reset to unity, if enabled.
+    fn silence_level(conf: &config::RMSLimiterParameters) -> Option<PrcFmt> {
+        conf.reset_on_silence()
+            .then(|| db_to_voltage_ratio(conf.silence_threshold_db() as PrcFmt))
+    }
+
+    /// Number of past chunks whose detected level is held, rounded up to whole chunks.
+    fn detection_hold_chunks(
+        chunksize: usize,
+        samplerate: usize,
+        conf: &config::RMSLimiterParameters,
+    ) -> usize {
+        let chunk_ms = 1000.0 * chunk_duration(chunksize, samplerate);
+        let hold_ms = conf.detection_hold_ms() as PrcFmt * conf.time_scale() as PrcFmt;
+        (hold_ms / chunk_ms).ceil() as usize
+    }
+
+    /// Hold the loudest level detected during the hold window,
+    /// so that the limiter stays engaged through short gaps between bursts.
+    fn hold_detection(&mut self, rms: PrcFmt) -> PrcFmt {
+        self.detection_history.push_back(rms);
+        while self.detection_history.len() > self.detection_hold_chunks + 1 {
+            self.detection_history.pop_front();
+        }
+        self.detection_history.iter().fold(0.0, |a, b| a.max(*b))
+    }
+
+    /// Change of the processed to dry mix per sample, when crossfading after a bypass toggle.
+    fn bypass_step(samplerate: usize, conf: &config::RMSLimiterParameters) -> PrcFmt {
+        let fade_samples = conf.bypass_crossfade_ms() as PrcFmt * samplerate as PrcFmt / 1000.0;
+        1.0 / fade_samples.max(1.0)
+    }
+
+    /// The limiter is active at a sample position, counted from the start of the stream.
+    /// Without any ranges it is always active.
+    fn is_active_at(ranges: &Option<Vec<(usize, usize)>>, position: usize) -> bool {
+        ranges.as_ref().map_or(true, |ranges| {
+            ranges
+                .iter()
+                .any(|(start, end)| (*start..*end).contains(&position))
+        })
+    }
+
+    /// Gain reduction in dB, as a positive number, smoothed for display with a fast attack
+    /// and slow release. This is only used for metering and does not affect the audio.
+    pub fn metered_reduction_db(&self) -> PrcFmt {
+        self.metered_reduction_db
+    }
+
+    /// Number of chunks processed with each amount of gain reduction,
+    /// in 1 dB bins starting from 0 dB. Empty if the histogram is not enabled.
+    pub fn reduction_histogram(&self) -> Vec<u32> {
+        self.histogram.map(|h| h.to_vec()).unwrap_or_default()
+    }
+
+    fn update_histogram(&mut self) {
+        if let Some(histogram) = &mut self.histogram {
+            let reduction_db = -voltage_ratio_to_db(self.current_gain);
+            let bin = (reduction_db.max(0.0) as usize).min(HISTOGRAM_BINS - 1);
+            histogram[bin] += 1;
+        }
+    }
+
+    fn update_meter(&mut self) {
+        let reduction_db = -voltage_ratio_to_db(self.current_gain);
+        let coeff = if reduction_db > self.metered_reduction_db {
+            self.meter_attack_coeff
+        } else {
+            self.meter_release_coeff
+        };
+        self.metered_reduction_db =
+            coeff * self.metered_reduction_db + (1.0 - coeff) * reduction_db;
+        #[cfg(feature = "osc")]
+        if let Some(sender) = &self.osc_sender {
+            sender.send(self.metered_reduction_db);
         }
     }
 
+    /// The threshold in dB currently used for limiting.
+    pub fn threshold(&self) -> PrcFmt {
+        voltage_ratio_to_db(self.threshold_voltage_ratio)
+    }
+
+    /// Step a slow loudness integrator by one chunk with the given level in dB.
+    /// This is the integrator that the automatic and relative thresholds use.
+    pub(crate) fn integrate_loudness(&self, loudness_db: PrcFmt, chunk_db: PrcFmt) -> PrcFmt {
+        self.auto_threshold_coeff * loudness_db + (1.0 - self.auto_threshold_coeff) * chunk_db
+    }
+
+    /// Measure the output loudness with a slow integrator,
+    /// and nudge the threshold towards the value that gives the target loudness.
+    /// The threshold is never raised above the configured value.
+    fn update_auto_threshold(&mut self, target_db: PrcFmt, output_rms: PrcFmt) {
+        let chunk_db = voltage_ratio_to_db(output_rms).max(-100.0);
+        self.output_loudness_db = self.integrate_loudness(self.output_loudness_db, chunk_db);
+        let threshold_db = self.threshold()
+            + (1.0 - self.auto_threshold_coeff) * (target_db - self.output_loudness_db);
+        let threshold_db = threshold_db.clamp(-100.0, self.max_threshold_db);
+        self.threshold_voltage_ratio = db_to_voltage_ratio(threshold_db);
+    }
+
+    /// The slowly averaged level of the input in dB, that a relative threshold follows.
+    pub fn program_level_db(&self) -> PrcFmt {
+        self.program_level_db
+    }
+
+    /// Measure the input level with the same slow integrator as the automatic threshold,
+    /// and set the threshold at an offset from it, but not above 0 dBFS.
+    fn update_relative_threshold(&mut self, offset_db: PrcFmt, input_rms: PrcFmt) {
+        let chunk_db = voltage_ratio_to_db(input_rms).max(-100.0);
+        self.program_level_db = self.integrate_loudness(self.program_level_db, chunk_db);
+        let threshold_db = (self.program_level_db + offset_db).clamp(-100.0, 0.0);
+        self.threshold_voltage_ratio = db_to_voltage_ratio(threshold_db);
+    }
+
     fn decay_per_chunk(
         chunksize: usize,
         samplerate: usize,
         conf: &config::RMSLimiterParameters,
     ) -> PrcFmt {
-        let decay_db_per_chunk =
-            conf.decay * RMSLimiter::chunks_per_second(chunksize, samplerate) as f32;
-        RMSLimiter::db_to_voltage_ratio(decay_db_per_chunk as PrcFmt)
+        // A larger time scale makes the release slower
+        let decay = conf.decay_db_per_s() as PrcFmt / conf.time_scale() as PrcFmt;
+        decay_coeff(decay, chunksize, samplerate)
+    }
+
+    /// Largest change of the gain in dB in one chunk, from the slew limit.
+    fn max_gain_step_db(
+        chunksize: usize,
+        samplerate: usize,
+        conf: &config::RMSLimiterParameters,
+    ) -> Option<PrcFmt> {
+        conf.slew_limit_db_per_ms
+            .map(|rate| rate as PrcFmt * 1000.0 * chunk_duration(chunksize, samplerate))
+    }
+
+    /// Largest increase of the gain reduction in dB in one chunk, if capped.
+    fn max_reduction_step_db(
+        chunksize: usize,
+        samplerate: usize,
+        conf: &config::RMSLimiterParameters,
+    ) -> Option<PrcFmt> {
+        conf.max_reduction_rate_db_per_s
+            .map(|rate| rate as PrcFmt * chunk_duration(chunksize, samplerate))
+    }
+
+    /// Attack time constant in seconds, as a fraction of the duration of the rms window,
+    /// scaled by the time scale. Without it, the gain is reduced at once.
+    fn attack_time(samplerate: usize, conf: &config::RMSLimiterParameters) -> Option<PrcFmt> {
+        conf.attack_from_window.map(|fraction| {
+            fraction as PrcFmt * conf.rms_samples as PrcFmt / samplerate as PrcFmt
+                * conf.time_scale() as PrcFmt
+        })
+    }
+
+    /// A rough estimate of the processing cost per sample, in multiply-adds.
+    /// This is a heuristic for comparing configurations, for example to warn
+    /// when a pipeline is too heavy for a device, and not a benchmark.
+    pub fn estimated_cost_per_sample(&self) -> f64 {
+        self.estimated_cost(TRUE_PEAK_OVERSAMPLING)
+    }
+
+    // The casts are needed when PrcFmt is f32
+    #[allow(clippy::unnecessary_cast)]
+    fn estimated_cost(&self, oversampling: usize) -> f64 {
+        let mut cost = SAMPLE_COST;
+        // The level is calculated from the whole buffer once per chunk
+        let level_cost = self.rms_buffer.capacity() as f64 / self.chunksize as f64;
+        cost += level_cost;
+        if self.window.is_some() {
+            cost += level_cost;
+        }
+        if let Some(weighting) = &self.weighting {
+            // Each Linkwitz-Riley filter of the weighting is two biquads
+            let filters = weighting.lowpasses.len() + weighting.highpasses.len();
+            cost += 2.0 * BIQUAD_COST * filters as f64;
+        }
+        if self.remove_dc {
+            cost += 2.0;
+        }
+        if self.true_peak_ceiling.is_some() {
+            if self.peak_shaver {
+                // Two passes of the smoothing ramp
+                cost += 2.0 * PEAK_SHAVER_SMOOTHING_TIME as f64 * self.samplerate as f64;
+            } else {
+                cost += true_peak_cost(oversampling);
+                // The overshoot is measured around each chunk boundary
+                cost += true_peak_cost(oversampling) * (4 * TRUE_PEAK_HALF_TAPS) as f64
+                    / self.chunksize as f64;
+                if self.strict_ceiling_tolerance_db.is_some() {
+                    cost += true_peak_cost(oversampling);
+                }
+            }
+        }
+        if self.warmth > 0.0 {
+            cost += TANH_COST;
+        }
+        if self.clip_ceiling.is_some() {
+            cost += TANH_COST;
+        }
+        cost
+    }
+
+    /// Lowest gain, as a voltage ratio, of the release shelf below unity.
+    fn release_shelf(conf: &config::RMSLimiterParameters) -> Option<PrcFmt> {
+        conf.release_shelf_db
+            .map(|shelf| db_to_voltage_ratio(-shelf as PrcFmt))
     }
 
-    fn chunks_per_second(chunksize: usize, samplerate: usize) -> f32 {
-        chunksize as f32 / samplerate as f32
+    /// Ratio to the current gain, as a voltage ratio, that a new attack
+    /// must go below during the release.
+    fn release_retrigger(conf: &config::RMSLimiterParameters) -> Option<PrcFmt> {
+        conf.release_retrigger_db
+            .map(|margin| db_to_voltage_ratio(-margin as PrcFmt))
     }
 
-    fn db_to_voltage_ratio(db: PrcFmt) -> PrcFmt {
-        (10.0 as PrcFmt).powf(db / 20.0)
+    /// Number of samples processed since the start of the stream.
+    pub fn sample_position(&self) -> usize {
+        self.sample_position
     }
 
-    fn voltage_ratio_to_db(voltage_ratio: PrcFmt) -> PrcFmt {
-        20.0 * voltage_ratio.log10()
+    /// Time in seconds since the start of the stream at a sample position.
+    pub fn stream_time(&self, position: usize) -> PrcFmt {
+        position as PrcFmt / self.samplerate as PrcFmt
     }
 
-    fn rms<'a>(waveform: impl Iterator<Item = &'a PrcFmt>) -> PrcFmt {
-        let mut squared_sum: PrcFmt = 0.0;
-        let mut values: u32 = 0;
+    /// The threshold in dB suggested by the calibration, once it has completed.
+    /// This is the rms level of the calibration input plus some headroom,
+    /// but not above its peak level.
+    pub fn calibration_result(&self) -> Option<PrcFmt> {
+        self.calibration_result
+    }
 
-        for item in waveform {
-            squared_sum += item * item;
-            values += 1;
+    /// Measure a chunk while calibrating. Returns true if the chunk was used for calibration,
+    /// and should then be passed through unchanged.
+    fn calibrate(&mut self, waveform: &[PrcFmt]) -> bool {
+        if self.calibration_samples_left == 0 {
+            return false;
+        }
+        // The calibration is part of the stream
+        self.sample_position += waveform.len();
+        for value in waveform.iter() {
+            let value = value * self.input_gain;
+            self.calibration_squared_sum += value * value;
+            self.calibration_peak = self.calibration_peak.max(value.abs());
+        }
+        self.calibration_count += waveform.len();
+        self.calibration_samples_left =
+            self.calibration_samples_left.saturating_sub(waveform.len());
+        if self.calibration_samples_left == 0 {
+            let rms = (self.calibration_squared_sum / self.calibration_count as PrcFmt).sqrt();
+            let threshold_db = PrcFmt::min(
+                voltage_ratio_to_db(rms) + CALIBRATION_HEADROOM_DB,
+                voltage_ratio_to_db(self.calibration_peak),
+            );
+            info!(
+                "Calibration of limiter {} done, suggested threshold: {:.2} dB",
+                self.name, threshold_db
+            );
+            self.calibration_result = Some(threshold_db);
         }
+        true
+    }
 
-        (squared_sum / values as PrcFmt).sqrt()
+    /// The gain of the limiter, as a voltage ratio.
+    /// This is before the input gain, the makeup and the channel trim.
+    pub fn gain(&self) -> PrcFmt {
+        self.current_gain
     }
-}
 
-impl Filter for RMSLimiter {
-    fn name(&self) -> &str {
-        &self.name
+    /// Set the gain of the limiter, as a voltage ratio. The value is clamped to 0 to 1.
+    /// In manual mode this gain is applied as it is, otherwise the detection
+    /// continues from it in the next chunk.
+    pub fn set_gain(&mut self, gain: PrcFmt) {
+        if gain.is_nan() {
+            warn!("Limiter {} ignored an invalid gain", self.name);
+            return;
+        }
+        self.current_gain = gain.clamp(0.0, 1.0);
     }
 
-    fn process_waveform(&mut self, waveform: &mut [PrcFmt]) -> Res<()> {
-        for item in waveform.iter_mut() {
-            self.rms_buffer.push(*item)
+    /// The level from the latest gain update, before the sensitivity is applied.
+    pub fn detected_level(&self) -> PrcFmt {
+        self.detected_level
+    }
+
+    /// The phase of the gain in the latest chunk.
+    pub fn current_phase(&self) -> Phase {
+        self.phase
+    }
+
+    /// Time in ms until the held detection level is let go, zero when nothing is held.
+    pub fn hold_remaining_ms(&self) -> PrcFmt {
+        let Some(newest) = self.detection_history.back() else {
+            return 0.0;
+        };
+        let held = self
+            .detection_history
+            .iter()
+            .fold(0.0, |a: PrcFmt, b| a.max(*b));
+        if held <= *newest {
+            return 0.0;
+        }
+        // The held level drops out of the history when it is older than the hold window
+        let held_index = self
+            .detection_history
+            .iter()
+            .rposition(|level| *level == held)
+            .unwrap_or_default();
+        let missing = (self.detection_hold_chunks + 1).saturating_sub(self.detection_history.len());
+        let chunks = held_index + 1 + missing;
+        1000.0 * chunks as PrcFmt * chunk_duration(self.chunksize, self.samplerate)
+    }
+
+    /// How far the release has come, in dB, from the lowest gain of the latest attack.
+    /// Zero when the release has just started, and one when the gain is back at unity.
+    pub fn release_progress(&self) -> PrcFmt {
+        if self.current_gain >= 1.0 || self.release_start_gain >= 1.0 {
+            return 1.0;
+        }
+        let progress = 1.0 - self.current_gain.ln() / self.release_start_gain.ln();
+        progress.clamp(0.0, 1.0)
+    }
+
+    /// Fraction of the rms buffer that has been filled with samples.
+    pub fn fill_fraction(&self) -> PrcFmt {
+        self.rms_buffer.len() as PrcFmt / self.rms_buffer.capacity() as PrcFmt
+    }
+
+    /// Snapshot of the detection buffer, from the oldest to the newest sample.
+    #[cfg(test)]
+    pub(crate) fn detection_buffer(&self) -> Vec<PrcFmt> {
+        self.rms_buffer.to_vec()
+    }
+
+    /// The DC offset of the input measured so far, when DC removal is enabled.
+    pub fn dc_offset(&self) -> PrcFmt {
+        self.dc_offset
+    }
+
+    /// Fill the detection buffer with a steady level, for example when resuming a stream
+    /// with a known level. The level is given before the input gain.
+    pub fn prefill(&mut self, level: PrcFmt) {
+        for _ in 0..self.rms_buffer.capacity() {
+            self.rms_buffer.push(level * self.input_gain);
         }
-        
-        let rms = RMSLimiter::rms(self.rms_buffer.iter());
+    }
+
+    /// In strict mode, the largest true peak of the output so far, in dB relative to the ceiling.
+    /// Negative values mean that the output has stayed below the ceiling.
+    pub fn max_output_over_ceiling_db(&self) -> Option<PrcFmt> {
+        self.max_output_over_ceiling_db
+    }
+
+    /// The largest true peak of the output around the chunk boundaries so far,
+    /// in dB relative to the ceiling. Within a chunk the gain is constant and the peaks
+    /// are kept below the ceiling, so this is the worst case overshoot.
+    /// Negative values mean that the output has stayed below the ceiling,
+    /// and a positive value means that `ceiling_trim_db` should be increased.
+    pub fn max_overshoot_db(&self) -> Option<PrcFmt> {
+        self.max_overshoot_db
+    }
 
-        let gain = self.threshold_voltage_ratio / rms;
-        let gain = PrcFmt::min(1.0, gain);
+    /// Measure the true peak across the boundary between the previous output chunk
+    /// of a channel and this one.
+    fn measure_overshoot(&mut self, channel: usize, waveform: &[PrcFmt]) {
+        let Some(ceiling) = self.true_peak_ceiling else {
+            return;
+        };
+        if self.output_tails.len() <= channel {
+            self.output_tails.resize(channel + 1, Vec::new());
+        }
+        let tail = &mut self.output_tails[channel];
+        let context = 2 * TRUE_PEAK_HALF_TAPS as usize;
+        if !tail.is_empty() {
+            let head = &waveform[..waveform.len().min(context)];
+            let tail_len = tail.len();
+            tail.extend_from_slice(head);
+            let over_db = voltage_ratio_to_db(true_peak(tail) / ceiling);
+            self.max_overshoot_db = Some(
+                self.max_overshoot_db
+                    .map_or(over_db, |max| max.max(over_db)),
+            );
+            tail.truncate(tail_len);
+        }
+        let start = tail.len() + waveform.len();
+        tail.extend_from_slice(waveform);
+        tail.drain(..start.saturating_sub(context));
+    }
 
-        if gain < self.current_gain {
-            self.current_gain = gain;
+    /// Track the output peak in strict mode, and log an error if it exceeds the ceiling
+    /// by more than the tolerance. Processing continues, a test or an offline render
+    /// checks the result with max_output_over_ceiling_db().
+    fn check_ceiling(&mut self, waveform: &[PrcFmt]) {
+        let (Some(tolerance_db), Some(ceiling)) =
+            (self.strict_ceiling_tolerance_db, self.true_peak_ceiling)
+        else {
+            return;
+        };
+        // Non-finite output, from non-finite input, counts as an infinite overshoot
+        let peak = if waveform.iter().all(|value| value.is_finite()) {
+            true_peak(waveform)
         } else {
-            self.current_gain = PrcFmt::min(1.0, self.current_gain * self.decay_per_chunk);
+            PrcFmt::INFINITY
+        };
+        let over_db = voltage_ratio_to_db(peak / ceiling);
+        let max_db = self
+            .max_output_over_ceiling_db
+            .map_or(over_db, |max| max.max(over_db));
+        self.max_output_over_ceiling_db = Some(max_db);
+        if over_db > tolerance_db {
+            error!(
+                "Output of limiter {} exceeded the ceiling by {:.3} dB",
+                self.name, over_db
+            );
         }
+    }
 
-        if self.current_gain < 1.0 {
+    /// A non-finite gain has been calculated since the last reset.
+    pub fn gain_fault(&self) -> bool {
+        self.gain_fault
+    }
+
+    /// Publish the peak of each processed waveform on a gain staging bus.
+    pub fn publish_output_peak(&mut self, bus: GainStagingBus) {
+        self.staging_output = Some(bus);
+    }
+
+    /// Trim the input so that the peak published on a gain staging bus,
+    /// usually by an earlier filter, is brought to a target in dB.
+    pub fn follow_input_peak(&mut self, bus: GainStagingBus, target_peak_db: PrcFmt) {
+        self.staging_input = Some((bus, target_peak_db));
+    }
+
+    /// The input trim in dB set by automatic gain staging.
+    pub fn staging_trim_db(&self) -> PrcFmt {
+        voltage_ratio_to_db(self.staging_trim)
+    }
+
+    /// Number of chunks whose input had samples above 0 dBFS.
+    pub fn input_overs(&self) -> usize {
+        self.input_overs
+    }
+
+    /// The trim in dB that brings input overs back below 0 dBFS, if enabled.
+    pub fn over_trim_db(&self) -> PrcFmt {
+        voltage_ratio_to_db(self.over_trim)
+    }
+
+    /// Check the input for samples above 0 dBFS, that were most likely clipped already
+    /// by an earlier stage. The optional trim is lowered to bring the loudest over
+    /// back below full scale, and is kept from then on.
+    fn check_input_overs(&mut self, members: &mut [&mut [PrcFmt]]) {
+        let peak = members
+            .iter()
+            .flat_map(|waveform| waveform.iter())
+            .fold(0.0, |acc: PrcFmt, val| acc.max(val.abs()));
+        if peak > 1.0 {
+            self.input_overs += 1;
+            // Warn only about new maximums, to avoid flooding the log
+            if peak > self.max_input_peak {
+                self.max_input_peak = peak;
+                warn!(
+                    "Limiter {} got input peaking at {:.2} dBFS, it was probably clipped upstream",
+                    self.name,
+                    voltage_ratio_to_db(peak)
+                );
+            }
+            if self.trim_input_overs {
+                self.over_trim = self
+                    .over_trim
+                    .min(db_to_voltage_ratio(-INPUT_OVER_HEADROOM_DB) / peak);
+            }
+        }
+        if self.over_trim < 1.0 {
+            members
+                .iter_mut()
+                .flat_map(|waveform| waveform.iter_mut())
+                .for_each(|item| *item *= self.over_trim);
+        }
+    }
+
+    /// Update the input trim from the gain staging bus, and apply it.
+    fn apply_staging_trim(&mut self, members: &mut [&mut [PrcFmt]]) {
+        let Some((bus, target_peak_db)) = &self.staging_input else {
+            return;
+        };
+        // Keep the previous trim while nothing is published
+        if let Some(peak_db) = bus.peak_db() {
+            let trim_db =
+                (target_peak_db - peak_db).clamp(-MAX_STAGING_TRIM_DB, MAX_STAGING_TRIM_DB);
+            self.staging_trim = db_to_voltage_ratio(trim_db);
+        }
+        members
+            .iter_mut()
+            .flat_map(|waveform| waveform.iter_mut())
+            .for_each(|item| *item *= self.staging_trim);
+    }
+
+    /// Start recording the gain of each processed chunk, discarding any earlier recording.
+    pub fn record_schedule(&mut self) {
+        self.recorded_schedule = Some(Vec::new());
+        self.recorded_lengths.clear();
+    }
+
+    /// The gains recorded since `record_schedule` was called, one per chunk,
+    /// or per part of a chunk that was split at a scheduled change.
+    pub fn recorded_schedule(&self) -> &[PrcFmt] {
+        self.recorded_schedule.as_deref().unwrap_or_default()
+    }
+
+    /// Write the gain envelope recorded since `record_schedule` was called to a file,
+    /// in the format of `envelopefile::write_envelope`. Each stored value is the lowest gain
+    /// of a block of `decimation` samples.
+    pub fn save_envelope(&self, path: &str, decimation: usize) -> Res<()> {
+        let gains = self
+            .recorded_schedule()
+            .iter()
+            .zip(self.recorded_lengths.iter())
+            .flat_map(|(gain, len)| std::iter::repeat(*gain).take(*len));
+        let envelope = Envelope::from_gains(gains, self.samplerate, decimation);
+        let mut writer = BufWriter::new(File::create(path)?);
+        write_envelope(&mut writer, &envelope)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Apply a recorded gain schedule, one gain per chunk, instead of detecting the level.
+    /// Detection resumes when the schedule runs out.
+    pub fn replay_schedule(&mut self, schedule: Vec<PrcFmt>) {
+        self.replayed_schedule = schedule.into();
+    }
+
+    /// Schedule a parameter change at an absolute sample position of the stream,
+    /// see `sample_position`. Chunks are split at the scheduled positions,
+    /// so that each change takes effect at the exact sample.
+    /// Changes at positions that have already passed are applied at the start of the next chunk.
+    pub fn schedule_change(&mut self, at_sample: usize, change: ParamChange) {
+        // Changes at the same position are applied in the order they were scheduled
+        let index = self
+            .scheduled_changes
+            .partition_point(|(position, _)| *position <= at_sample);
+        self.scheduled_changes.insert(index, (at_sample, change));
+    }
+
+    /// Apply the scheduled changes that are due at a sample position.
+    fn apply_scheduled_changes(&mut self, position: usize) {
+        while let Some((_, change)) = self
+            .scheduled_changes
+            .front()
+            .filter(|(at_sample, _)| *at_sample <= position)
+            .copied()
+        {
+            self.scheduled_changes.pop_front();
+            self.apply_change(change);
             debug!(
-                "Limiting by {:.2} db",
-                RMSLimiter::voltage_ratio_to_db(self.current_gain)
+                "Limiter {} applied scheduled change at sample {}: {:?}",
+                self.name, position, change
             );
         }
+    }
+
+    fn apply_change(&mut self, change: ParamChange) {
+        match change {
+            ParamChange::Threshold(threshold_db) => {
+                self.threshold_voltage_ratio = db_to_voltage_ratio(threshold_db);
+                self.max_threshold_db = threshold_db;
+            }
+            ParamChange::InputGain(gain_db) => {
+                self.input_gain = db_to_voltage_ratio(gain_db);
+            }
+        }
+    }
 
-        for item in waveform.iter_mut() {
-            *item *= self.current_gain;
+    /// Process the channels in parts, split at the positions of the scheduled changes.
+    fn process_scheduled(
+        &mut self,
+        members: &mut [&mut [PrcFmt]],
+        detection: LinkedDetection,
+    ) -> Res<()> {
+        let len = members[0].len();
+        let chunk_start = self.sample_position;
+        let mut start = 0;
+        while start < len {
+            self.apply_scheduled_changes(chunk_start + start);
+            let end = match self.scheduled_changes.front() {
+                Some((at_sample, _)) => len.min(at_sample - chunk_start),
+                None => len,
+            };
+            if start == 0 && end == len {
+                self.process_trimmed(members, detection)?;
+            } else {
+                let mut parts: Vec<&mut [PrcFmt]> = members
+                    .iter_mut()
+                    .map(|waveform| &mut waveform[start..end])
+                    .collect();
+                self.process_trimmed(&mut parts, detection)?;
+            }
+            start = end;
         }
+        Ok(())
+    }
 
+    /// Save the gain, the detection history and the coefficients to a file,
+    /// so that a restarted limiter can continue where this one stopped.
+    /// The file holds an 8 byte identifier followed by little endian f64 values,
+    /// with the buffer and history each preceded by their length as u64.
+    // The casts are needed when PrcFmt is f32
+    #[allow(clippy::unnecessary_cast)]
+    pub fn save_state(&self, path: &str) -> Res<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(STATE_MAGIC)?;
+        for value in [
+            self.decay_per_chunk,
+            self.dc_coeff,
+            self.current_gain,
+            self.threshold_voltage_ratio,
+            self.output_loudness_db,
+            self.dc_offset,
+        ] {
+            writer.write_all(&(value as f64).to_le_bytes())?;
+        }
+        for values in [
+            self.rms_buffer.to_vec(),
+            self.detection_history.iter().copied().collect(),
+        ] {
+            writer.write_all(&(values.len() as u64).to_le_bytes())?;
+            for value in values {
+                writer.write_all(&(value as f64).to_le_bytes())?;
+            }
+        }
+        writer.flush()?;
         Ok(())
     }
 
-    fn update_parameters(&mut self, conf: config::Filter) {
-        if let config::Filter::RMSLimiter { parameters: conf, .. } = conf {
-            self.decay_per_chunk = RMSLimiter::decay_per_chunk(self.chunksize, self.samplerate, &conf);
-            self.threshold_voltage_ratio = RMSLimiter::db_to_voltage_ratio(conf.threshold as PrcFmt);
+    /// Load a state saved by `save_state`. The limiter must use the same settings as the
+    /// one that saved the state, otherwise it is left unchanged and an error is returned.
+    pub fn load_state(&mut self, path: &str) -> Res<()> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if magic != STATE_MAGIC {
+            return Err(config::ConfigError::new("Not a limiter state file").into());
+        }
+        let read_word = |reader: &mut BufReader<File>| -> Res<[u8; 8]> {
+            let mut bytes = [0; 8];
+            reader.read_exact(&mut bytes)?;
+            Ok(bytes)
+        };
+        let read_value =
+            |reader: &mut BufReader<File>| Ok(f64::from_le_bytes(read_word(reader)?) as PrcFmt);
+        let decay_per_chunk = read_value(&mut reader)?;
+        let dc_coeff = read_value(&mut reader)?;
+        let same_coeff = |saved: PrcFmt, current: PrcFmt| {
+            (saved - current).abs() <= STATE_COEFF_TOLERANCE * current.abs()
+        };
+        if !same_coeff(decay_per_chunk, self.decay_per_chunk)
+            || !same_coeff(dc_coeff, self.dc_coeff)
+        {
+            let msg = format!(
+                "Limiter state in {} was saved with different settings",
+                path
+            );
+            return Err(config::ConfigError::new(&msg).into());
+        }
+        let current_gain = read_value(&mut reader)?;
+        let threshold_voltage_ratio = read_value(&mut reader)?;
+        let output_loudness_db = read_value(&mut reader)?;
+        let dc_offset = read_value(&mut reader)?;
+        let mut lists = Vec::new();
+        for _ in 0..2 {
+            let len = u64::from_le_bytes(read_word(&mut reader)?) as usize;
+            let values = (0..len)
+                .map(|_| read_value(&mut reader))
+                .collect::<Res<Vec<PrcFmt>>>()?;
+            lists.push(values);
+        }
+        let history = lists.pop().unwrap_or_default();
+        let buffered = lists.pop().unwrap_or_default();
+        if buffered.len() > self.rms_buffer.capacity() {
+            let msg = format!(
+                "Limiter state in {} has {} buffered samples, more than the capacity of {}",
+                path,
+                buffered.len(),
+                self.rms_buffer.capacity()
+            );
+            return Err(config::ConfigError::new(&msg).into());
+        }
 
-            if self.rms_buffer.capacity() != conf.rms_samples {
-                self.rms_buffer = AllocRingBuffer::with_capacity(conf.rms_samples);
-            }
+        self.rms_buffer = B::with_capacity(self.rms_buffer.capacity());
+        for value in buffered {
+            self.rms_buffer.push(value);
+        }
+        self.previous_buffer = None;
+        self.previous_window = None;
+        self.detection_history = history.into();
+        self.current_gain = current_gain;
+        self.dc_offset = dc_offset;
+        // The threshold is only state when it is adjusted automatically
+        if self.auto_threshold_target_db.is_some() {
+            self.threshold_voltage_ratio = threshold_voltage_ratio;
+            self.output_loudness_db = output_loudness_db;
+        }
+        Ok(())
+    }
+
+    /// Process a copy of a chunk and return the result, without changing the state
+    /// of the limiter. The preview does not record a gain schedule or send OSC messages.
+    pub fn preview(&self, waveform: &[PrcFmt]) -> Res<Vec<PrcFmt>> {
+        let mut limiter = self.preview_copy();
+        let mut output = waveform.to_vec();
+        limiter.process_waveform(&mut output)?;
+        Ok(output)
+    }
+
+    /// The gain reduction in dB, as positive numbers, that a snippet would get
+    /// with another threshold, one value per chunk. Like `preview`, this starts from
+    /// the current state and leaves the limiter unchanged.
+    pub fn preview_reduction(&self, waveform: &[PrcFmt], threshold_db: PrcFmt) -> Res<Vec<PrcFmt>> {
+        let mut limiter = self.preview_copy();
+        limiter.apply_change(ParamChange::Threshold(threshold_db));
+        let mut envelope = Vec::with_capacity(waveform.len().div_ceil(self.chunksize));
+        let mut chunk = Vec::with_capacity(self.chunksize);
+        for snippet in waveform.chunks(self.chunksize) {
+            chunk.clear();
+            chunk.extend_from_slice(snippet);
+            limiter.process_waveform(&mut chunk)?;
+            envelope.push(-voltage_ratio_to_db(limiter.current_gain));
+        }
+        Ok(envelope)
+    }
+
+    /// A copy of the limiter with the same state, for previewing.
+    fn preview_copy(&self) -> Self {
+        let mut limiter = self.clone();
+        // The recording and the outputs are not part of the preview
+        limiter.recorded_schedule = None;
+        limiter.recorded_lengths.clear();
+        limiter.staging_output = None;
+        #[cfg(feature = "osc")]
+        {
+            limiter.osc_sender = None;
+        }
+        limiter
+    }
+
+    /// The rms buffer is full, and the detected level is reliable.
+    pub fn is_warmed_up(&self) -> bool {
+        self.rms_buffer.is_full()
+    }
+
+    /// Calculate the gain needed to bring a signal with the given rms level down to the threshold.
+    fn target_gain(&self, rms: PrcFmt) -> PrcFmt {
+        if let Some(table) = &self.gain_table {
+            // Custom curve, gains above unity are not used
+            PrcFmt::min(1.0, table.gain(voltage_ratio_to_db(rms)))
+        } else if self.soft {
+            let level_db = voltage_ratio_to_db(rms);
+            let threshold_db = voltage_ratio_to_db(self.threshold_voltage_ratio);
+            db_to_voltage_ratio(self.knee.output_db(level_db, threshold_db) - level_db)
         } else {
-            // This should never happen unless there is a bug somewhere else
-            panic!("Invalid config change!");
+            PrcFmt::min(1.0, self.threshold_voltage_ratio / rms)
         }
     }
-}
 
-/// Validate a RMSLimiter config.
-pub fn validate_config(conf: &config::RMSLimiterParameters) -> Res<()> {
-    if conf.decay < 0.0 {
-        return Err(config::ConfigError::new("Decay (dB/s) cannot be negative").into());
+    /// Return the gain to unity. With a decay of zero the limiter holds the lowest gain
+    /// reached, and this is the only way to release it.
+    pub fn reset(&mut self) {
+        self.current_gain = 1.0;
+        self.release_start_gain = 1.0;
+        self.phase = Phase::Idle;
+        self.gain_fault = false;
+        self.detection_history.clear();
+        self.attack_start_gain = None;
+    }
+
+    /// Update the current gain from the contents of the rms buffer.
+    /// The release and the step limits are scaled by `chunk_fraction`,
+    /// the length of the processed chunk relative to the configured chunksize.
+    fn update_gain(&mut self, chunk_fraction: PrcFmt) {
+        let mut level =
+            Self::buffer_level(&self.rms_buffer, self.window.as_deref(), self.detection);
+        if let Some(previous) = &self.previous_buffer {
+            // Blend from the old window to the new one after a change of rms_samples
+            let fade = self.transition_left as PrcFmt / self.transition_chunks as PrcFmt;
+            let previous_level =
+                Self::buffer_level(previous, self.previous_window.as_deref(), self.detection);
+            level = fade * previous_level + (1.0 - fade) * level;
+        }
+        self.detected_level = level;
+        // The sensitivity only scales the detected level, not the audio
+        let level = self.sensitivity * level;
+        let level = self.hold_detection(level);
+
+        let mut gain = self.target_gain(level);
+        if self.release_shelf.is_some_and(|shelf| gain >= shelf) {
+            // Reductions within the shelf are not applied, to avoid a lingering tiny reduction
+            gain = 1.0;
+        }
+        if let Some(margin) = self.release_retrigger {
+            // While releasing, smaller peaks only pause the release,
+            // and a new attack needs a peak that asks for clearly more reduction
+            let releasing = matches!(self.phase, Phase::Release | Phase::Hold);
+            if releasing && gain < self.current_gain && gain > margin * self.current_gain {
+                gain = self.current_gain;
+            }
+        }
+
+        if gain >= self.current_gain {
+            // The attack is over, the next one starts from the gain at that time
+            self.attack_start_gain = None;
+        }
+        let previous_gain = self.current_gain;
+        if self.silence_level.is_some_and(|silence| level < silence) {
+            // Return to unity at once, instead of a slow release during silence
+            self.current_gain = 1.0;
+        } else if gain < self.current_gain {
+            let attacked = match self.attack_time {
+                Some(attack_time) => self.attack_step(gain, attack_time, chunk_fraction),
+                None => gain,
+            };
+            self.current_gain = flush_denormal(attacked);
+        } else if gain == self.current_gain {
+            // The detected level, for example while held, asks for exactly the current gain
+        } else if !self.hold {
+            let released = if self.linear_release {
+                // The same step as the dB release has at unity gain
+                self.current_gain + chunk_fraction * (1.0 - 1.0 / self.decay_per_chunk)
+            } else {
+                self.current_gain * self.decay_per_chunk.powf(chunk_fraction)
+            };
+            self.current_gain = match self.release_shelf {
+                // Snap to unity instead of creeping through the last part of the release
+                Some(shelf) if released >= shelf => 1.0,
+                _ => PrcFmt::min(1.0, released),
+            };
+        }
+        if let Some(max_step_db) = self.max_gain_step_db.filter(|_| previous_gain > 0.0) {
+            let max_step_db = chunk_fraction * max_step_db;
+            // Limit the rate of change in both directions, to avoid zipper noise
+            let step_db = voltage_ratio_to_db(self.current_gain / previous_gain);
+            if step_db.abs() > max_step_db {
+                self.current_gain =
+                    previous_gain * db_to_voltage_ratio(step_db.clamp(-max_step_db, max_step_db));
+            }
+        }
+        if let Some(max_step_db) = self.max_reduction_step_db.filter(|_| previous_gain > 0.0) {
+            let max_step_db = chunk_fraction * max_step_db;
+            // A safety cap on how fast the reduction can grow, whatever the input looks like.
+            // The ceiling is still enforced after this.
+            let lowest = previous_gain * db_to_voltage_ratio(-max_step_db);
+            if self.current_gain < lowest {
+                self.current_gain = lowest;
+            }
+        }
+    }
+
+    /// The gain after one chunk of attack towards a lower target gain.
+    /// The linear and S-curve attacks move from the gain where the attack started,
+    /// and reach the target after the attack time. A target that drops further
+    /// during the attack is followed without restarting it.
+    fn attack_step(
+        &mut self,
+        target: PrcFmt,
+        attack_time: PrcFmt,
+        chunk_fraction: PrcFmt,
+    ) -> PrcFmt {
+        if self.attack_curve == config::AttackCurve::Exponential {
+            let coeff =
+                chunk_coeff(attack_time, self.chunksize, self.samplerate).powf(chunk_fraction);
+            return target + coeff * (self.current_gain - target);
+        }
+        let start = match self.attack_start_gain {
+            Some(start) => start,
+            None => {
+                self.attack_elapsed = 0.0;
+                *self.attack_start_gain.insert(self.current_gain)
+            }
+        };
+        self.attack_elapsed += chunk_fraction * chunk_duration(self.chunksize, self.samplerate);
+        let progress = PrcFmt::min(1.0, self.attack_elapsed / attack_time);
+        let shape = match self.attack_curve {
+            config::AttackCurve::SCurve => progress * progress * (3.0 - 2.0 * progress),
+            _ => progress,
+        };
+        // Never move back up while the attack is running
+        PrcFmt::min(self.current_gain, start + shape * (target - start))
+    }
+
+    /// Flush a tiny value that is used for detection. The log rms detection keeps
+    /// the resolution of very quiet signals, so then only actual denormals are flushed.
+    fn flush_detection(&self, value: PrcFmt) -> PrcFmt {
+        match self.detection {
+            config::DetectionType::LogRms if value.is_subnormal() => 0.0,
+            config::DetectionType::LogRms => value,
+            _ => flush_denormal(value),
+        }
+    }
+
+    /// Push the detection signal of a chunk into the detection buffer and update the gain.
+    /// The ceiling is applied to the peaks of all the channels that get the gain.
+    /// Returns false if the channels must be muted because of a gain fault.
+    fn measure_chunk(&mut self, detection: &[PrcFmt], members: &[&mut [PrcFmt]]) -> bool {
+        // The active ranges are checked at the start of each chunk
+        let chunk_start = self.sample_position;
+        self.in_active_range = Self::is_active_at(&self.active_ranges, chunk_start);
+        self.sample_position += detection.len();
+        if let Some(weighting) = &mut self.weighting {
+            weighting.process(detection);
+        }
+        self.output_dc.clear();
+        for (n, item) in detection.iter().enumerate() {
+            // Denormal input is flushed before any arithmetic
+            let input = self.flush_detection(*item);
+            // With detection weights, the level is detected from the weighted signal
+            let mut value = match &self.weighting {
+                Some(weighting) => self.flush_detection(weighting.output[n]),
+                None => input,
+            };
+            if self.remove_dc {
+                // Subtract a running mean, so that a DC offset does not count as signal level
+                self.dc_offset =
+                    flush_denormal(self.dc_offset + self.dc_coeff * (value - self.dc_offset));
+                value -= self.dc_offset;
+                // The output is only changed by apply_gain
+                if self.remove_dc_from_output {
+                    self.output_dc.push(self.dc_offset);
+                }
+            }
+            // Only every Nth sample is used for detection when decimating
+            // Tiny values are flushed, so that the squared sums of quiet tails stay normal
+            if self.decimation_phase == 0 {
+                let value = self.flush_detection(value * self.input_gain);
+                self.rms_buffer.push(value);
+                if let Some(previous) = &mut self.previous_buffer {
+                    previous.push(value);
+                }
+            }
+            self.decimation_phase = (self.decimation_phase + 1) % self.detection_decimation;
+        }
+
+        let previous_gain = self.current_gain;
+        if let Some(scheduled_gain) = self.replayed_schedule.pop_front() {
+            self.current_gain = scheduled_gain;
+        } else {
+            // Keep the current gain until the buffer is full when waiting for warmup
+            if !self.wait_for_warmup || self.is_warmed_up() {
+                self.update_gain(detection.len() as PrcFmt / self.chunksize as PrcFmt);
+            }
+        }
+        // The ceiling also applies to a replayed gain, since the schedule may have been
+        // recorded from a quieter signal
+        if let Some(ceiling) = self.true_peak_ceiling.map(|c| c * self.ceiling_trim) {
+            // Reduce the gain further if the reconstructed output would exceed the ceiling
+            let member_peak = members
+                .iter()
+                .map(|waveform| true_peak(waveform))
+                .fold(0.0, PrcFmt::max);
+            let peak = self.input_gain * self.current_gain * self.channel_trim * member_peak;
+            if peak > ceiling {
+                self.current_gain *= ceiling / peak;
+            }
+        }
+        if self.previous_buffer.is_some() {
+            self.transition_left -= 1;
+            if self.transition_left == 0 {
+                self.previous_buffer = None;
+                self.previous_window = None;
+            }
+        }
+        if !self.current_gain.is_finite() {
+            // Never send garbage to the speakers. Start over from unity gain in the next chunk.
+            error!(
+                "Limiter {} calculated a non-finite gain: {}",
+                self.name, self.current_gain
+            );
+            self.gain_fault = true;
+            self.current_gain = 1.0;
+            if self.mute_on_fault && !self.analyze_only {
+                return false;
+            }
+        }
+        self.phase = if self.current_gain >= 1.0 {
+            Phase::Idle
+        } else if self.current_gain < previous_gain {
+            Phase::Attack
+        } else if self.current_gain > previous_gain {
+            Phase::Release
+        } else {
+            Phase::Hold
+        };
+        if self.phase == Phase::Attack {
+            self.release_start_gain = self.current_gain;
+        }
+        if self.current_gain < 1.0 {
+            debug!(
+                "Limiter {} limiting by {:.2} dB at sample {} ({:.3} s)",
+                self.name,
+                -voltage_ratio_to_db(self.current_gain),
+                chunk_start,
+                self.stream_time(chunk_start)
+            );
+        }
+        if let Some(schedule) = &mut self.recorded_schedule {
+            schedule.push(self.current_gain);
+            self.recorded_lengths.push(detection.len());
+        }
+        self.update_meter();
+        self.update_histogram();
+
+        if let Some(target_db) = self.auto_threshold_target_db {
+            let output_rms = self.input_gain * self.current_gain * rms(detection.iter());
+            self.update_auto_threshold(target_db, output_rms);
+        }
+        if let Some(offset_db) = self.threshold_relative_db {
+            let input_rms = self.input_gain * rms(detection.iter());
+            self.update_relative_threshold(offset_db, input_rms);
+        }
+        true
+    }
+
+    /// Apply the current gain to a chunk. Returns true if the full gain was applied,
+    /// and false if the chunk was left untouched or crossfaded with the dry signal.
+    fn apply_gain(&mut self, waveform: &mut [PrcFmt]) -> bool {
+        // In analyze only mode, all measurements are done but the audio is left untouched
+        if self.analyze_only {
+            return false;
+        }
+
+        // The makeup restores a fraction of the reduction, as makeup = reduction^fraction.
+        // The static trim is applied after the limiter gain, and does not affect the detection.
+        let limiter_gain = self.current_gain.powf(1.0 - self.makeup_fraction);
+        let gain = self.input_gain * limiter_gain * self.channel_trim;
+
+        // The level detection keeps running while bypassed,
+        // so that the gain is up to date when the bypass is released.
+        // Outside the active ranges, the limiter is bypassed in the same way.
+        let bypassed = self.bypassed || !self.in_active_range;
+        let target_mix = if bypassed { 0.0 } else { 1.0 };
+        // Denormal input is flushed, and the DC offset removed, only in the processed signal
+        let output_dc = &self.output_dc;
+        let processed = |n: usize, item: PrcFmt| {
+            (flush_denormal(item) - output_dc.get(n).copied().unwrap_or(0.0)) * gain
+        };
+        if self.bypass_mix != target_mix {
+            // Crossfade between the processed and the dry signal
+            for (n, item) in waveform.iter_mut().enumerate() {
+                self.bypass_mix = if target_mix > self.bypass_mix {
+                    PrcFmt::min(target_mix, self.bypass_mix + self.bypass_step)
+                } else {
+                    PrcFmt::max(target_mix, self.bypass_mix - self.bypass_step)
+                };
+                *item += self.bypass_mix * (processed(n, *item) - *item);
+            }
+            return false;
+        }
+        if bypassed {
+            return false;
+        }
+
+        for (n, item) in waveform.iter_mut().enumerate() {
+            *item = processed(n, *item);
+        }
+
+        // Saturate the output with a drive that follows the amount of gain reduction
+        let drive = self.warmth * WARMTH_MAX_DRIVE * (1.0 - self.current_gain);
+        if drive > 1.0e-6 {
+            for item in waveform.iter_mut() {
+                *item = (drive * *item).tanh() / drive;
+            }
+        }
+
+        // A final safety, for the peaks that the slow detection lets through
+        if let Some(ceiling) = self.clip_ceiling {
+            for item in waveform.iter_mut() {
+                *item = soft_clip(*item, ceiling);
+            }
+        }
+        true
+    }
+
+    /// Peak shaver mode, scale down only the samples above the ceiling.
+    /// The ceiling is applied to the sample values.
+    fn shave_peaks(&mut self, waveform: &mut [PrcFmt]) {
+        if self.analyze_only || self.bypassed {
+            return;
+        }
+        if let Some(ceiling) = self.true_peak_ceiling {
+            let smoothing = (PEAK_SHAVER_SMOOTHING_TIME * self.samplerate as PrcFmt) as usize;
+            shave_peaks(waveform, ceiling, smoothing, &mut self.shave_gains);
+        }
+    }
+
+    /// Process the channels that share one gain, after any gain staging trim.
+    fn process_trimmed(
+        &mut self,
+        members: &mut [&mut [PrcFmt]],
+        detection: LinkedDetection,
+    ) -> Res<()> {
+        let len = members[0].len();
+        let loudest;
+        let detection: &[PrcFmt] = match detection {
+            LinkedDetection::Channel(channel) => members[channel],
+            LinkedDetection::Loudest => {
+                loudest = Self::loudest(members);
+                &loudest
+            }
+        };
+        if self.calibrate(detection) {
+            return Ok(());
+        }
+        if self.peak_shaver {
+            // The peaks of each channel are shaved separately
+            self.sample_position += len;
+            for waveform in members.iter_mut() {
+                self.shave_peaks(waveform);
+            }
+            return Ok(());
+        }
+        if self.manual {
+            // The gain is set from outside, and the ceiling is not enforced
+            self.sample_position += len;
+            self.output_dc.clear();
+            self.apply_linked_gain(members);
+            return Ok(());
+        }
+        if !self.measure_chunk(detection, members) {
+            for waveform in members.iter_mut() {
+                waveform.iter_mut().for_each(|item| *item = 0.0);
+            }
+            return Ok(());
+        }
+        if self.apply_linked_gain(members) {
+            for (channel, waveform) in members.iter().enumerate() {
+                self.measure_overshoot(channel, waveform);
+            }
+        } else {
+            // The boundary to a chunk that was not processed tells nothing about the limiter
+            self.output_tails.iter_mut().for_each(|tail| tail.clear());
+        }
+        for waveform in members.iter() {
+            self.check_ceiling(waveform);
+        }
+        Ok(())
+    }
+
+    /// The largest sample of the channels at each instant, with its sign.
+    fn loudest(members: &[&mut [PrcFmt]]) -> Vec<PrcFmt> {
+        let mut loudest: Vec<PrcFmt> = vec![0.0; members[0].len()];
+        for waveform in members.iter() {
+            for (linked, value) in loudest.iter_mut().zip(waveform.iter()) {
+                if value.abs() > linked.abs() {
+                    *linked = *value;
+                }
+            }
+        }
+        loudest
+    }
+
+    /// Apply the current gain to the channels that share one detection,
+    /// with the same crossfade for all of them. Returns true if the full gain was applied.
+    fn apply_linked_gain(&mut self, members: &mut [&mut [PrcFmt]]) -> bool {
+        let bypass_mix = self.bypass_mix;
+        let mut applied = false;
+        for waveform in members.iter_mut() {
+            self.bypass_mix = bypass_mix;
+            applied = self.apply_gain(waveform);
+        }
+        applied
+    }
+
+    /// Process one chunk of several channels that share one gain, with the level
+    /// detected as given. All channels must have the same length.
+    pub(crate) fn process_linked(
+        &mut self,
+        members: &mut [&mut [PrcFmt]],
+        detection: LinkedDetection,
+    ) -> Res<()> {
+        let Some(len) = members.first().map(|waveform| waveform.len()) else {
+            return Ok(());
+        };
+        if members.iter().any(|waveform| waveform.len() != len) {
+            let msg = format!("Limiter {} got channels of different lengths", self.name);
+            return Err(ProcessingError::new(&msg).into());
+        }
+        // Nothing to measure, leave the state untouched
+        if len == 0 {
+            return Ok(());
+        }
+        self.check_input_overs(members);
+        self.apply_staging_trim(members);
+        let result = self.process_scheduled(members, detection);
+        if let Some(bus) = &self.staging_output {
+            let peak = |waveform: &[PrcFmt]| {
+                waveform
+                    .iter()
+                    .fold(0.0, |acc: PrcFmt, val| acc.max(val.abs()))
+            };
+            if let Some(waveform) = members.iter().max_by(|a, b| peak(a).total_cmp(&peak(b))) {
+                bus.publish_peak(waveform);
+            }
+        }
+        result
+    }
+}
+
+impl<B: DetectionBuffer> Filter for RMSLimiter<B> {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn filter_type(&self) -> &'static str {
+        "RMSLimiter"
+    }
+
+    fn can_increase_level(&self) -> bool {
+        // The gain staging trim can go up as well as down
+        self.input_gain > 1.0
+            || self.channel_trim > 1.0
+            || self.makeup_fraction > 0.0
+            || self.staging_input.is_some()
+    }
+
+    fn process_waveform(&mut self, waveform: &mut [PrcFmt]) -> Res<()> {
+        self.process_linked(&mut [waveform], LinkedDetection::Channel(0))
+    }
+
+    /// One limiter has one state, so all the channels get the same gain.
+    /// The level is detected from the configured channel, or else from the loudest one.
+    fn process_frames(&mut self, frames: &mut [&mut [PrcFmt]]) -> Res<()> {
+        let Some(channel) = self.detect_from_channel else {
+            return self.process_linked(frames, LinkedDetection::Loudest);
+        };
+        if channel >= frames.len() {
+            let msg = format!(
+                "Limiter {} detects from channel {}, but only got {} channels",
+                self.name,
+                channel,
+                frames.len()
+            );
+            return Err(ProcessingError::new(&msg).into());
+        }
+        // The level of one channel drives the gain of all of them
+        self.process_linked(frames, LinkedDetection::Channel(channel))
+    }
+
+    fn update_parameters(&mut self, conf: config::Filter) {
+        if let config::Filter::RMSLimiter {
+            parameters: conf, ..
+        } = conf
+        {
+            self.decay_per_chunk = Self::decay_per_chunk(self.chunksize, self.samplerate, &conf);
+            self.linear_release = conf.release_domain() == config::ReleaseDomain::Linear;
+            self.silence_level = Self::silence_level(&conf);
+            self.sensitivity = db_to_voltage_ratio(conf.sensitivity_db() as PrcFmt);
+            self.remove_dc = conf.remove_dc();
+            self.remove_dc_from_output = conf.remove_dc_from_output();
+            self.mute_on_fault = conf.mute_on_fault();
+            self.detect_from_channel = conf.detect_from_channel;
+            self.active_ranges = conf.active_ranges.clone();
+            self.strict_ceiling_tolerance_db =
+                conf.strict_ceiling_tolerance_db.map(|t| t as PrcFmt);
+            self.threshold_voltage_ratio = db_to_voltage_ratio(conf.threshold as PrcFmt);
+            self.soft = conf.soft();
+            self.knee = Knee::from_config(&conf);
+            self.wait_for_warmup = conf.wait_for_warmup();
+            self.max_threshold_db = conf.threshold as PrcFmt;
+            self.auto_threshold_target_db = conf.auto_threshold_target_db.map(|t| t as PrcFmt);
+            self.threshold_relative_db = conf.threshold_relative_db.map(|t| t as PrcFmt);
+            self.true_peak_ceiling = conf.ceiling_dbtp.map(|c| db_to_voltage_ratio(c as PrcFmt));
+            self.ceiling_trim = db_to_voltage_ratio(-conf.ceiling_trim_db() as PrcFmt);
+            self.hold = conf.decay_db_per_s() == 0.0;
+            self.input_gain = db_to_voltage_ratio(conf.input_gain_db() as PrcFmt);
+            if !conf.reduction_histogram() {
+                self.histogram = None;
+            } else if self.histogram.is_none() {
+                self.histogram = Some([0; HISTOGRAM_BINS]);
+            }
+            self.gain_table = conf.curve_table.as_ref().map(|c| GainTable::from_curve(c));
+            self.analyze_only = conf.analyze_only();
+            self.bypassed = conf.bypassed();
+            self.manual = conf.manual();
+            self.bypass_step = Self::bypass_step(self.samplerate, &conf);
+            self.detection_hold_chunks =
+                Self::detection_hold_chunks(self.chunksize, self.samplerate, &conf);
+            self.detection = conf.detection();
+            // Keep the filter states unless the weights were changed
+            if conf.detection_weights.as_ref() != self.weighting.as_ref().map(|w| &w.weights_conf) {
+                self.weighting = conf
+                    .detection_weights
+                    .as_ref()
+                    .map(|w| DetectionWeighting::from_weights(w, self.samplerate));
+            }
+            self.warmth = conf.warmth() as PrcFmt;
+            self.makeup_fraction = conf.makeup_fraction() as PrcFmt;
+            self.channel_trim = db_to_voltage_ratio(conf.channel_trim_db() as PrcFmt);
+            self.peak_shaver = conf.peak_shaver();
+            #[cfg(feature = "osc")]
+            {
+                self.osc_sender = Self::osc_sender(&self.name, &conf);
+            }
+
+            self.detection_decimation = conf.detection_decimation();
+            self.max_gain_step_db = Self::max_gain_step_db(self.chunksize, self.samplerate, &conf);
+            self.max_reduction_step_db =
+                Self::max_reduction_step_db(self.chunksize, self.samplerate, &conf);
+            self.clip_ceiling = conf
+                .clip_ceiling_db
+                .map(|c| db_to_voltage_ratio(c as PrcFmt));
+            self.attack_time = Self::attack_time(self.samplerate, &conf);
+            if conf.attack_curve() != self.attack_curve {
+                self.attack_curve = conf.attack_curve();
+                self.attack_start_gain = None;
+            }
+            self.release_shelf = Self::release_shelf(&conf);
+            self.release_retrigger = Self::release_retrigger(&conf);
+            self.trim_input_overs = conf.trim_input_overs();
+            if !self.trim_input_overs {
+                self.over_trim = 1.0;
+            }
+            let resized = self.rms_buffer.capacity() != conf.detection_buffer_size();
+            if resized {
+                // Keep the old window running for a while, to avoid a jump in the detected level
+                let old_buffer = std::mem::replace(
+                    &mut self.rms_buffer,
+                    B::with_capacity(conf.detection_buffer_size()),
+                );
+                self.previous_buffer = Some(old_buffer);
+                self.previous_window = self.window.take();
+                self.transition_left = self.transition_chunks.max(1);
+            }
+            if resized || conf.detection_window() != self.detection_window {
+                self.detection_window = conf.detection_window();
+                self.window = Self::window(self.detection_window, self.rms_buffer.capacity());
+            }
+        } else {
+            // This should never happen unless there is a bug somewhere else
+            panic!("Invalid config change!");
+        }
+    }
+}
+
+/// Shape and placement of the soft limiter curve.
+#[derive(Clone, Copy, Debug)]
+struct Knee {
+    shape: config::KneeShape,
+    width_db: PrcFmt,
+    offset_db: PrcFmt,
+}
+
+impl Knee {
+    fn from_config(conf: &config::RMSLimiterParameters) -> Self {
+        Knee {
+            shape: conf.knee_shape(),
+            width_db: conf.knee_db() as PrcFmt,
+            offset_db: conf.knee_offset_db() as PrcFmt,
+        }
+    }
+
+    /// Output level in dB of the soft limiter for a given input level.
+    fn output_db(&self, level_db: PrcFmt, threshold_db: PrcFmt) -> PrcFmt {
+        match self.shape {
+            config::KneeShape::Tanh => {
+                // Starts bending the knee width below the threshold plus the offset,
+                // and approaches that level asymptotically for loud input.
+                let ceiling = threshold_db + self.offset_db;
+                let knee_start = ceiling - self.width_db;
+                if level_db <= knee_start {
+                    return level_db;
+                }
+                knee_start + self.width_db * ((level_db - knee_start) / self.width_db).tanh()
+            }
+            config::KneeShape::Linear | config::KneeShape::Smoothstep => {
+                // The knee is centered on the threshold plus the offset,
+                // and the output levels off at the center of the knee
+                let center = threshold_db + self.offset_db;
+                let knee_start = center - 0.5 * self.width_db;
+                let t = (level_db - knee_start) / self.width_db;
+                if t <= 0.0 {
+                    return level_db;
+                } else if t >= 1.0 {
+                    return center;
+                }
+                let rise = if self.shape == config::KneeShape::Linear {
+                    0.5 * t
+                } else {
+                    // Integral of a slope that falls from 1 to 0 as 1 - smoothstep(t)
+                    t - t * t * t + 0.5 * t * t * t * t
+                };
+                knee_start + self.width_db * rise
+            }
+        }
+    }
+}
+
+/// The gain that the limiter, with a hard gain curve, settles to for a sustained input
+/// with the given rms level. Input below the threshold is left unchanged,
+/// and louder input is brought down to the threshold.
+pub fn steady_state_gain(threshold_db: PrcFmt, input_db: PrcFmt) -> PrcFmt {
+    db_to_voltage_ratio(PrcFmt::min(0.0, threshold_db - input_db))
+}
+
+/// Interpolate a curve of (x, y) points linearly, holding the end values outside the curve.
+fn interpolate_linear(curve: &[(PrcFmt, PrcFmt)], x: PrcFmt) -> PrcFmt {
+    if curve.is_empty() {
+        return 0.0;
+    }
+    if x <= curve[0].0 {
+        return curve[0].1;
+    }
+    for points in curve.windows(2) {
+        let (x_low, y_low) = points[0];
+        let (x_high, y_high) = points[1];
+        if x <= x_high {
+            return y_low + (x - x_low) / (x_high - x_low) * (y_high - y_low);
+        }
+    }
+    curve[curve.len() - 1].1
+}
+
+/// Validate a RMSLimiter config.
+pub fn validate_config(
+    samplerate: usize,
+    chunksize: usize,
+    conf: &config::RMSLimiterParameters,
+) -> Res<()> {
+    if samplerate == 0 {
+        return Err(config::ConfigError::new("Samplerate must be larger than zero").into());
+    }
+    if chunksize == 0 {
+        return Err(config::ConfigError::new("Chunksize must be larger than zero").into());
+    }
+    if conf.input_gain_db() < -150.0 {
+        return Err(config::ConfigError::new("Input gain must be larger than -150 dB").into());
+    } else if conf.input_gain_db() > 150.0 {
+        return Err(config::ConfigError::new("Input gain must be less than +150 dB").into());
+    }
+    if conf.decay < 0.0 {
+        return Err(config::ConfigError::new("Decay (dB/s) cannot be negative").into());
+    }
+    if conf.release_ms.is_some_and(|ms| ms <= 0.0) {
+        return Err(config::ConfigError::new("Release time must be positive").into());
+    }
+    if let Some(note) = &conf.release_note {
+        if conf.release_ms.is_some() {
+            let msg = "Release time and release note cannot both be given";
+            return Err(config::ConfigError::new(msg).into());
+        }
+        if config::parse_note_value(note).is_none() {
+            let msg = format!("Invalid release note: {}, expected a value like 1/4", note);
+            return Err(config::ConfigError::new(&msg).into());
+        }
+        if !conf.bpm.is_some_and(|bpm| bpm > 0.0) {
+            let msg = "A release note requires a positive bpm";
+            return Err(config::ConfigError::new(msg).into());
+        }
+    }
+    if let Some(target) = &conf.osc_target {
+        if cfg!(not(feature = "osc")) {
+            let msg = "Sending OSC messages requires the osc feature";
+            return Err(config::ConfigError::new(msg).into());
+        }
+        if target.parse::<std::net::SocketAddr>().is_err() {
+            let msg = format!("Invalid OSC target address '{target}'");
+            return Err(config::ConfigError::new(&msg).into());
+        }
+    }
+    if conf.sensitivity_db().abs() > 40.0 {
+        return Err(config::ConfigError::new("Sensitivity must be within +-40 dB").into());
+    }
+    if conf.time_scale() <= 0.0 {
+        return Err(config::ConfigError::new("Time scale must be positive").into());
+    }
+    if conf.channel_trim_db().abs() > 20.0 {
+        return Err(config::ConfigError::new("Channel trim must be within +-20 dB").into());
+    }
+    if !(0.0..=1.0).contains(&conf.warmth()) {
+        return Err(config::ConfigError::new("Warmth must be between 0 and 1").into());
+    }
+    if !(0.0..=1.0).contains(&conf.makeup_fraction()) {
+        return Err(config::ConfigError::new("Makeup fraction must be between 0 and 1").into());
+    }
+    if conf.makeup_fraction() > 0.0 && conf.ceiling_dbtp.is_some() {
+        let msg =
+            "Makeup fraction cannot be combined with a ceiling, since the makeup would exceed it";
+        return Err(config::ConfigError::new(msg).into());
+    }
+    if conf.detection_hold_ms() < 0.0 {
+        return Err(config::ConfigError::new("Detection hold time cannot be negative").into());
+    }
+    if conf.bypass_crossfade_ms() < 0.0 {
+        return Err(config::ConfigError::new("Bypass crossfade time cannot be negative").into());
+    }
+    if conf.calibration_ms.is_some_and(|ms| ms <= 0.0) {
+        let msg = "Calibration time must be larger than zero";
+        return Err(config::ConfigError::new(msg).into());
+    }
+    if conf.slew_limit_db_per_ms.is_some_and(|rate| rate <= 0.0) {
+        let msg = "Slew limit must be larger than zero";
+        return Err(config::ConfigError::new(msg).into());
+    }
+    if conf.clip_ceiling_db.is_some_and(|ceiling| ceiling > 0.0) {
+        let msg = "Clip ceiling cannot be above 0 dB";
+        return Err(config::ConfigError::new(msg).into());
+    }
+    if conf.threshold_relative_db.is_some() && conf.auto_threshold_target_db.is_some() {
+        let msg = "A relative threshold cannot be combined with an automatic threshold";
+        return Err(config::ConfigError::new(msg).into());
+    }
+    if conf.channels() == 0 {
+        return Err(config::ConfigError::new("Number of channels must be larger than zero").into());
+    }
+    if conf.channels() > 1 && conf.detect_from_channel.is_some() {
+        let msg = "Independent channels cannot be combined with detecting from one channel";
+        return Err(config::ConfigError::new(msg).into());
+    }
+    if conf
+        .release_retrigger_db
+        .is_some_and(|margin| margin <= 0.0)
+    {
+        let msg = "Release retrigger margin must be larger than zero";
+        return Err(config::ConfigError::new(msg).into());
+    }
+    if conf.release_shelf_db.is_some_and(|shelf| shelf <= 0.0) {
+        let msg = "Release shelf must be larger than zero";
+        return Err(config::ConfigError::new(msg).into());
+    }
+    if conf
+        .attack_from_window
+        .is_some_and(|fraction| fraction <= 0.0)
+    {
+        let msg = "Attack as a fraction of the rms window must be larger than zero";
+        return Err(config::ConfigError::new(msg).into());
+    }
+    if conf
+        .max_reduction_rate_db_per_s
+        .is_some_and(|rate| rate <= 0.0)
+    {
+        let msg = "Maximum reduction rate must be larger than zero";
+        return Err(config::ConfigError::new(msg).into());
+    }
+    if conf.knee_db() <= 0.0 || conf.knee_db() > 40.0 {
+        let msg = format!(
+            "Knee width must be between 0 and 40 dB, got {}",
+            conf.knee_db()
+        );
+        return Err(config::ConfigError::new(&msg).into());
+    }
+    if conf.knee_offset_db().abs() > 0.5 * conf.knee_db() {
+        let msg = format!(
+            "Knee offset must be within half the knee width, got {} for a {} dB knee",
+            conf.knee_offset_db(),
+            conf.knee_db()
+        );
+        return Err(config::ConfigError::new(&msg).into());
+    }
+    if conf.detection_window() != config::WindowFunction::Rectangular
+        && conf.detection() != config::DetectionType::Rms
+    {
+        let msg = "A detection window can only be used with rms detection";
+        return Err(config::ConfigError::new(msg).into());
+    }
+    if conf.peak_shaver() && conf.ceiling_dbtp.is_none() {
+        let msg = "The peak shaver needs a ceiling";
+        return Err(config::ConfigError::new(msg).into());
+    }
+    if conf.attack_curve.is_some() && conf.attack_from_window.is_none() {
+        let msg = "An attack curve needs an attack time, set with attack_from_window";
+        return Err(config::ConfigError::new(msg).into());
+    }
+    if conf.manual() && conf.peak_shaver() {
+        let msg = "The peak shaver has no gain that could be set manually";
+        return Err(config::ConfigError::new(msg).into());
+    }
+    if conf.ceiling_trim_db.is_some() && conf.ceiling_dbtp.is_none() {
+        let msg = "The ceiling trim needs a ceiling";
+        return Err(config::ConfigError::new(msg).into());
+    }
+    if conf.ceiling_trim_db() < 0.0 {
+        let msg = "Ceiling trim must not be negative";
+        return Err(config::ConfigError::new(msg).into());
+    }
+    if conf.remove_dc_from_output() && conf.detect_from_channel.is_some() {
+        let msg = "Removing DC from the output cannot be combined with detecting from one channel";
+        return Err(config::ConfigError::new(msg).into());
+    }
+    if conf.strict_ceiling_tolerance_db.is_some() && conf.ceiling_dbtp.is_none() {
+        let msg = "The strict ceiling check needs a ceiling";
+        return Err(config::ConfigError::new(msg).into());
+    }
+    if let Some((start, end)) = conf
+        .active_ranges
+        .iter()
+        .flatten()
+        .find(|(start, end)| start >= end)
+    {
+        let msg = format!(
+            "Active range must end after it starts, got {} to {}",
+            start, end
+        );
+        return Err(config::ConfigError::new(&msg).into());
+    }
+    let decimation = conf.detection_decimation();
+    if !decimation.is_power_of_two() || decimation > conf.rms_samples {
+        let msg = format!(
+            "Detection decimation must be a power of two, and at most the number of rms samples, got {}",
+            decimation
+        );
+        return Err(config::ConfigError::new(&msg).into());
+    }
+    if !conf.rms_samples.is_power_of_two() {
+        let msg = format!(
+            "Number of rms samples must be a power of two, got {}",
+            conf.rms_samples
+        );
+        return Err(config::ConfigError::new(&msg).into());
+    }
+    if let Some(weights) = &conf.detection_weights {
+        if weights.is_empty() {
+            return Err(config::ConfigError::new("Detection weights cannot be empty").into());
+        }
+        if weights.windows(2).any(|points| points[1].0 <= points[0].0) {
+            let msg = "Frequencies of the detection weights must be increasing";
+            return Err(config::ConfigError::new(msg).into());
+        }
+        if let Some((freq, weight)) = weights.iter().find(|(freq, weight)| {
+            *freq <= 0.0 || *freq >= samplerate as f32 / 2.0 || *weight < 0.0
+        }) {
+            let msg = format!(
+                "Invalid detection weight {} at {} Hz, the frequency must be between 0 and {} Hz and the weight cannot be negative",
+                weight, freq, samplerate / 2
+            );
+            return Err(config::ConfigError::new(&msg).into());
+        }
+        if conf.remove_dc_from_output() {
+            let msg = "Removing DC from the output cannot be combined with detection weights";
+            return Err(config::ConfigError::new(msg).into());
+        }
+    }
+    if let Some(curve) = &conf.curve_table {
+        if curve.is_empty() {
+            return Err(config::ConfigError::new("Curve table cannot be empty").into());
+        }
+        if curve.windows(2).any(|points| points[1].0 <= points[0].0) {
+            let msg = "Input levels of the curve table must be increasing";
+            return Err(config::ConfigError::new(msg).into());
+        }
+    }
+    for warning in config_warnings(conf) {
+        warn!("{}", warning);
+    }
+    Ok(())
+}
+
+/// Settings that are valid but most likely a mistake.
+/// These are logged as warnings by `validate_config`.
+pub fn config_warnings(conf: &config::RMSLimiterParameters) -> Vec<String> {
+    let mut warnings = Vec::new();
+    if conf.threshold > 0.0 {
+        warnings.push(format!(
+            "RMSLimiter threshold is {} dB, above 0 dBFS. The limiter will not engage on normal full-scale audio, was a negative threshold intended?",
+            conf.threshold
+        ));
+    }
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config;
+    use crate::detectionbuffer::{DetectionBuffer, VecDequeBuffer};
+    use crate::dynamics_math::{db_to_voltage_ratio, rms, true_peak, voltage_ratio_to_db};
+    use crate::envelopefile::read_envelope;
+    use crate::filters::{Filter, ProcessingError};
+    use crate::gainstaging::GainStagingBus;
+    use crate::limiter::Limiter;
+    use crate::rms_limiter::{
+        config_warnings, steady_state_gain, validate_config, Knee, ParamChange, Phase, RMSLimiter,
+        HISTOGRAM_BINS,
+    };
+    use crate::PrcFmt;
+
+    fn params(threshold: f32, decay: f32, rms_samples: usize) -> config::RMSLimiterParameters {
+        config::RMSLimiterParameters {
+            rms_samples,
+            ..config::RMSLimiterParameters::new(threshold, decay)
+        }
+    }
+
+    fn knee(shape: config::KneeShape, offset_db: PrcFmt) -> Knee {
+        Knee {
+            shape,
+            width_db: 6.0,
+            offset_db,
+        }
+    }
+
+    fn output_level(limiter: &mut RMSLimiter, level: PrcFmt, len: usize) -> PrcFmt {
+        let mut waveform = vec![level; len];
+        limiter.process_waveform(&mut waveform).unwrap();
+        waveform[len - 1]
+    }
+
+    #[test]
+    fn soft_limiter_asymptotes() {
+        let mut conf = params(-6.0, 12.0, 64);
+        conf.soft = Some(true);
+        let threshold = db_to_voltage_ratio(-6.0);
+        let mut prev_output = 0.0;
+        for level_db in (-30..=40).step_by(2) {
+            let mut limiter = RMSLimiter::from_config("test", conf.clone(), 64, 48000);
+            let level = db_to_voltage_ratio(level_db as PrcFmt);
+            let output = output_level(&mut limiter, level, 64);
+            // Allow for rounding with 32-bit floats, where the curve is almost flat
+            assert!(output > prev_output - 1.0e-6, "output must rise with input");
+            assert!(
+                output < threshold + 1.0e-6,
+                "output must stay below threshold"
+            );
+            prev_output = output;
+        }
+        // Very loud input ends up just below the threshold
+        assert!(voltage_ratio_to_db(prev_output) > -6.1);
+    }
+
+    #[test]
+    fn reject_zero_samplerate() {
+        assert!(validate_config(0, 1024, &params(-6.0, 12.0, 64)).is_err());
+    }
+
+    #[test]
+    fn reject_zero_chunksize() {
+        assert!(validate_config(48000, 0, &params(-6.0, 12.0, 64)).is_err());
+        assert!(validate_config(48000, 1024, &params(-6.0, 12.0, 64)).is_ok());
+    }
+
+    #[test]
+    fn fill_fraction_during_warmup() {
+        let mut limiter = RMSLimiter::from_config("test", params(-6.0, 12.0, 64), 16, 48000);
+        assert_eq!(limiter.fill_fraction(), 0.0);
+        for n in 1..=4 {
+            output_level(&mut limiter, 0.1, 16);
+            assert_eq!(limiter.fill_fraction(), n as PrcFmt / 4.0);
+        }
+        assert!(limiter.is_warmed_up());
+        output_level(&mut limiter, 0.1, 16);
+        assert_eq!(limiter.fill_fraction(), 1.0);
+    }
+
+    #[test]
+    fn wait_for_warmup() {
+        let mut conf = params(-6.0, 12.0, 64);
+        conf.wait_for_warmup = Some(true);
+        let mut limiter = RMSLimiter::from_config("test", conf, 16, 48000);
+        for _ in 0..3 {
+            assert_eq!(output_level(&mut limiter, 1.0, 16), 1.0);
+        }
+        assert!(output_level(&mut limiter, 1.0, 16) < 1.0);
+    }
+
+    #[test]
+    fn auto_threshold_follows_target() {
+        let mut conf = params(0.0, 12.0, 1024);
+        conf.auto_threshold_target_db = Some(-12.0);
+        let mut limiter = RMSLimiter::from_config("test", conf, 1024, 48000);
+        // Input rises from -20 dB to 0 dB over 10 seconds, then stays at 0 dB for 20 seconds
+        let nbr_chunks = 1400;
+        let mut output = 0.0;
+        for n in 0..nbr_chunks {
+            let level_db = (-20.0 + 20.0 * n as PrcFmt / 470.0).min(0.0);
+            let level = db_to_voltage_ratio(level_db);
+            output = output_level(&mut limiter, level, 1024);
+        }
+        let output_db = voltage_ratio_to_db(output);
+        assert!(limiter.threshold() < -10.0);
+        assert!(
+            (output_db + 12.0).abs() < 1.0,
+            "output level {} dB",
+            output_db
+        );
+    }
+
+    #[test]
+    fn relative_threshold_follows_program_level() {
+        let mut conf = params(-20.0, 12.0, 1024);
+        conf.threshold_relative_db = Some(6.0);
+        assert!(validate_config(48000, 1024, &conf).is_ok());
+        let mut limiter = RMSLimiter::from_config("test", conf.clone(), 1024, 48000);
+        assert!((limiter.threshold() + 20.0).abs() < 1.0e-3);
+        // 30 seconds of a steady level of -14 dB
+        let level = db_to_voltage_ratio(-14.0);
+        for _ in 0..1400 {
+            output_level(&mut limiter, level, 1024);
+        }
+        assert!((limiter.program_level_db() + 14.0).abs() < 0.01);
+        assert!((limiter.threshold() + 8.0).abs() < 0.01);
+        // Not limited, since the threshold is above the level
+        assert_eq!(limiter.current_gain, 1.0);
+
+        conf.auto_threshold_target_db = Some(-12.0);
+        assert!(validate_config(48000, 1024, &conf).is_err());
+    }
+
+    #[test]
+    fn meter_ballistics() {
+        let mut limiter = RMSLimiter::from_config("test", params(-20.0, 200.0, 1024), 1024, 48000);
+        output_level(&mut limiter, 1.0, 1024);
+        let reduction = -voltage_ratio_to_db(limiter.current_gain);
+        assert!((reduction - 20.0).abs() < 1.0e-6);
+        // Fast attack
+        assert!(limiter.metered_reduction_db() > 0.8 * reduction);
+        for _ in 0..10 {
+            output_level(&mut limiter, 0.01, 1024);
+        }
+        // The real gain is back at unity, the meter falls slowly
+        assert_eq!(limiter.current_gain, 1.0);
+        let metered = limiter.metered_reduction_db();
+        assert!(metered > 0.6 * reduction && metered < reduction);
+    }
+
+    #[test]
+    fn true_peak_ceiling() {
+        // A sine at fs/4 with 45 degree phase has samples at -3 dBFS but a true peak at 0 dBFS
+        let sine: Vec<PrcFmt> = (0..1024)
+            .map(|n| (std::f64::consts::PI as PrcFmt * (0.5 * n as PrcFmt + 0.25)).sin())
+            .collect();
+        let sample_peak = |wf: &[PrcFmt]| wf.iter().fold(0.0, |acc: PrcFmt, v| acc.max(v.abs()));
+        let ceiling = db_to_voltage_ratio(-1.0);
+
+        // Sample peaks are below a -1 dB ceiling, nothing to do for a sample peak limiter
+        assert!(sample_peak(&sine) < ceiling);
+
+        let mut conf = params(0.0, 12.0, 1024);
+        conf.ceiling_dbtp = Some(-1.0);
+        let mut limiter = RMSLimiter::from_config("test", conf, 1024, 48000);
+        let mut waveform = sine.clone();
+        limiter.process_waveform(&mut waveform).unwrap();
+        // The analytic peak of the output is the applied gain
+        let peak_db = voltage_ratio_to_db(waveform[0] / sine[0]);
+        assert!(peak_db <= -0.9, "true peak {} dB", peak_db);
+        assert!(peak_db > -1.5, "true peak {} dB", peak_db);
+    }
+
+    #[test]
+    fn hold_with_zero_decay() {
+        let mut limiter = RMSLimiter::from_config("test", params(-20.0, 0.0, 64), 64, 48000);
+        output_level(&mut limiter, 1.0, 64);
+        let min_gain = limiter.current_gain;
+        assert!(min_gain < 1.0);
+        for _ in 0..1000 {
+            output_level(&mut limiter, 0.001, 64);
+            assert_eq!(limiter.current_gain, min_gain);
+        }
+        limiter.reset();
+        assert_eq!(output_level(&mut limiter, 0.001, 64), 0.001);
+    }
+
+    #[test]
+    fn input_gain_engages_limiting() {
+        // A 0.25 rms signal is -12 dB, just below the -10 dB threshold
+        let mut limiter = RMSLimiter::from_config("test", params(-10.0, 12.0, 64), 64, 48000);
+        assert_eq!(output_level(&mut limiter, 0.25, 64), 0.25);
+        assert_eq!(limiter.current_gain, 1.0);
+
+        let mut conf = params(-10.0, 12.0, 64);
+        conf.input_gain_db = Some(6.0);
+        let mut limiter = RMSLimiter::from_config("test", conf, 64, 48000);
+        let output = output_level(&mut limiter, 0.25, 64);
+        assert!(limiter.current_gain < 1.0);
+        assert!((voltage_ratio_to_db(output) + 10.0).abs() < 1.0e-4);
+    }
+
+    #[test]
+    fn reject_bad_input_gain() {
+        let mut conf = params(-10.0, 12.0, 64);
+        conf.input_gain_db = Some(200.0);
+        assert!(validate_config(48000, 1024, &conf).is_err());
+    }
+
+    #[test]
+    fn histogram_of_reduction() {
+        let mut conf = params(-20.0, 12.0, 64);
+        conf.reduction_histogram = Some(true);
+        let mut limiter = RMSLimiter::from_config("test", conf, 64, 48000);
+        // 10 chunks below threshold, 5 chunks with 6.5 dB reduction and 2 with 40 dB
+        for _ in 0..10 {
+            output_level(&mut limiter, 0.01, 64);
+        }
+        for _ in 0..5 {
+            output_level(&mut limiter, db_to_voltage_ratio(-13.5), 64);
+        }
+        for _ in 0..2 {
+            output_level(&mut limiter, db_to_voltage_ratio(20.0), 64);
+        }
+        let histogram = limiter.reduction_histogram();
+        assert_eq!(histogram.len(), HISTOGRAM_BINS);
+        assert_eq!(histogram[0], 10);
+        assert_eq!(histogram[6], 5);
+        assert_eq!(histogram[HISTOGRAM_BINS - 1], 2);
+        assert_eq!(histogram.iter().sum::<u32>(), 17);
+
+        let limiter = RMSLimiter::from_config("test", params(-20.0, 12.0, 64), 64, 48000);
+        assert!(limiter.reduction_histogram().is_empty());
+    }
+
+    #[test]
+    fn curve_table_matches_parametric() {
+        // A table describing the same curve as a hard limiter with a -6 dB threshold
+        let mut conf = params(-6.0, 12.0, 64);
+        conf.curve_table = Some(vec![(-100.0, 0.0), (-6.0, 0.0), (30.0, -36.0)]);
+        let table_limiter = RMSLimiter::from_config("test", conf, 64, 48000);
+        let limiter = RMSLimiter::from_config("test", params(-6.0, 12.0, 64), 64, 48000);
+        for level_db in [-40.0, -10.0, -6.0, -5.55, -3.0, 0.0, 7.77, 20.0] {
+            let level = db_to_voltage_ratio(level_db);
+            let expected = limiter.target_gain(level);
+            let gain = table_limiter.target_gain(level);
+            assert!((gain - expected).abs() < 1.0e-6, "{} != {}", gain, expected);
+        }
+    }
+
+    #[test]
+    fn reject_non_monotonic_curve_table() {
+        let mut conf = params(-6.0, 12.0, 64);
+        conf.curve_table = Some(vec![(-20.0, 0.0), (-10.0, 0.0), (-10.0, -3.0)]);
+        assert!(validate_config(48000, 1024, &conf).is_err());
+        conf.curve_table = Some(vec![(-20.0, 0.0), (-10.0, 0.0), (0.0, -3.0)]);
+        assert!(validate_config(48000, 1024, &conf).is_ok());
+    }
+
+    #[test]
+    fn analyze_only_leaves_audio() {
+        let mut conf = params(-20.0, 12.0, 64);
+        conf.analyze_only = Some(true);
+        conf.reduction_histogram = Some(true);
+        let mut limiter = RMSLimiter::from_config("test", conf, 64, 48000);
+        let input: Vec<PrcFmt> = (0..64).map(|n| (n as PrcFmt * 0.3).sin()).collect();
+        let mut waveform = input.clone();
+        limiter.process_waveform(&mut waveform).unwrap();
+        assert_eq!(waveform, input);
+        assert!(limiter.current_gain < 1.0);
+        assert!(limiter.metered_reduction_db() > 0.0);
+        assert_eq!(limiter.reduction_histogram().iter().sum::<u32>(), 1);
+    }
+
+    #[test]
+    fn default_parameters_are_valid() {
+        let conf = config::RMSLimiterParameters::default();
+        assert!(validate_config(48000, 1024, &conf).is_ok());
+        let conf = config::RMSLimiterParameters::new(-3.0, 20.0);
+        assert_eq!(conf.threshold, -3.0);
+        assert_eq!(conf.decay, 20.0);
+        assert_eq!(conf.rms_samples, 2048);
+        assert!(validate_config(48000, 1024, &conf).is_ok());
+    }
+
+    #[test]
+    fn reject_rms_samples_not_power_of_two() {
+        assert!(validate_config(48000, 1024, &params(-3.0, 12.0, 2400)).is_err());
+        assert!(validate_config(48000, 1024, &params(-3.0, 12.0, 0)).is_err());
+    }
+
+    fn max_step(limiter: &mut RMSLimiter, conf: &config::RMSLimiterParameters) -> PrcFmt {
+        let chunksize = 1024;
+        let mut output = Vec::new();
+        for chunk in 0..8 {
+            if chunk == 4 {
+                let mut bypassed = conf.clone();
+                bypassed.bypassed = Some(true);
+                limiter.update_parameters(config::Filter::RMSLimiter {
+                    description: None,
+                    parameters: bypassed,
+                });
+            }
+            let mut waveform: Vec<PrcFmt> = (0..chunksize)
+                .map(|n| {
+                    let t = (chunk * chunksize + n) as PrcFmt / 48000.0;
+                    (2.0 * std::f64::consts::PI as PrcFmt * 100.0 * t).sin()
+                })
+                .collect();
+            limiter.process_waveform(&mut waveform).unwrap();
+            output.extend(waveform);
+        }
+        // Skip the first chunks where the gain is still settling
+        output[2 * chunksize..]
+            .windows(2)
+            .map(|pair| (pair[1] - pair[0]).abs())
+            .fold(0.0, PrcFmt::max)
+    }
+
+    #[test]
+    fn bypass_crossfade() {
+        // The limiter holds a gain of about -17 dB, a 100 Hz sine moves at most 0.013 per sample
+        let conf = params(-20.0, 0.0, 1024);
+        let mut limiter = RMSLimiter::from_config("test", conf.clone(), 1024, 48000);
+        assert!(max_step(&mut limiter, &conf) < 0.02);
+
+        let mut conf = conf;
+        conf.bypass_crossfade_ms = Some(0.0);
+        let mut limiter = RMSLimiter::from_config("test", conf.clone(), 1024, 48000);
+        assert!(max_step(&mut limiter, &conf) > 0.1);
+    }
+
+    #[test]
+    fn bypass_keeps_detection_warm() {
+        let mut conf = params(-20.0, 12.0, 1024);
+        conf.bypass_crossfade_ms = Some(0.0);
+        let mut reference = RMSLimiter::from_config("test", conf.clone(), 1024, 48000);
+        conf.bypassed = Some(true);
+        let mut limiter = RMSLimiter::from_config("test", conf.clone(), 1024, 48000);
+        for _ in 0..3 {
+            output_level(&mut reference, 0.5, 1024);
+            // The output is dry, but the gain follows the input
+            assert_eq!(output_level(&mut limiter, 0.5, 1024), 0.5);
+            assert_eq!(limiter.current_gain, reference.current_gain);
+        }
+        conf.bypassed = None;
+        limiter.update_parameters(config::Filter::RMSLimiter {
+            description: None,
+            parameters: conf,
+        });
+        // The first active chunk applies the same gain as a limiter that was never bypassed
+        let mut expected = vec![0.5; 1024];
+        reference.process_waveform(&mut expected).unwrap();
+        let mut waveform = vec![0.5; 1024];
+        limiter.process_waveform(&mut waveform).unwrap();
+        for (value, expected) in waveform.iter().zip(expected.iter()) {
+            assert!((value - expected).abs() < 1.0e-6);
+        }
+        assert!(waveform[0] < 0.2);
+    }
+
+    fn gain_after_bursts(conf: config::RMSLimiterParameters) -> PrcFmt {
+        let mut limiter = RMSLimiter::from_config("test", conf, 1024, 48000);
+        for _ in 0..4 {
+            output_level(&mut limiter, 1.0, 1024);
+            for _ in 0..3 {
+                output_level(&mut limiter, 0.01, 1024);
+            }
+        }
+        limiter.current_gain
+    }
+
+    #[test]
+    fn detection_hold_between_bursts() {
+        // A fast decay of about 10 dB per chunk releases fully during the gaps of 64 ms
+        let conf = params(-20.0, 500.0, 1024);
+        assert!((gain_after_bursts(conf.clone()) - 1.0).abs() < 1.0e-6);
+        // Holding the detected level for 100 ms keeps the limiter engaged
+        let conf = config::RMSLimiterParameters {
+            detection_hold_ms: Some(100.0),
+            ..conf
+        };
+        assert!(gain_after_bursts(conf) < 0.5);
+    }
+
+    #[test]
+    fn empty_waveform() {
+        let mut limiter = RMSLimiter::from_config("test", params(-20.0, 12.0, 64), 64, 48000);
+        let mut empty: Vec<PrcFmt> = Vec::new();
+        assert!(limiter.process_waveform(&mut empty).is_ok());
+        assert_eq!(limiter.current_gain, 1.0);
+        output_level(&mut limiter, 1.0, 64);
+        let gain = limiter.current_gain;
+        assert!(limiter.process_waveform(&mut empty).is_ok());
+        assert_eq!(limiter.current_gain, gain);
+        assert!(limiter.is_warmed_up());
+    }
+
+    /// Amplitude of the third harmonic relative to the fundamental,
+    /// for a sine with exactly 16 periods per chunk.
+    fn third_harmonic(conf: config::RMSLimiterParameters, amplitude: PrcFmt) -> PrcFmt {
+        let mut limiter = RMSLimiter::from_config("test", conf, 1024, 48000);
+        let omega = 2.0 * std::f64::consts::PI as PrcFmt * 16.0 / 1024.0;
+        let mut waveform = Vec::new();
+        for _ in 0..4 {
+            waveform = (0..1024)
+                .map(|n| amplitude * (omega * n as PrcFmt).sin())
+                .collect();
+            limiter.process_waveform(&mut waveform).unwrap();
+        }
+        let component = |harmonic: PrcFmt| {
+            let (re, im) = waveform
+                .iter()
+                .enumerate()
+                .fold((0.0, 0.0), |(re, im), (n, v)| {
+                    let phase = harmonic * omega * n as PrcFmt;
+                    (re + v * phase.cos(), im + v * phase.sin())
+                });
+            (re * re + im * im).sqrt()
+        };
+        component(3.0) / component(1.0)
+    }
+
+    #[test]
+    fn warmth_follows_reduction() {
+        let mut conf = params(0.0, 12.0, 1024);
+        conf.warmth = Some(1.0);
+        // Not limiting, no added harmonics
+        assert!(third_harmonic(conf.clone(), 0.5) < 1.0e-4);
+        // Heavy limiting adds harmonics
+        conf.threshold = -20.0;
+        assert!(third_harmonic(conf.clone(), 1.0) > 0.01);
+        // Same limiting without warmth stays clean
+        conf.warmth = None;
+        assert!(third_harmonic(conf, 1.0) < 1.0e-4);
+    }
+
+    #[test]
+    fn channel_trim_after_gain() {
+        let left_conf = params(-20.0, 12.0, 1024);
+        let mut right_conf = left_conf.clone();
+        right_conf.channel_trim_db = Some(-0.5);
+        let mut left = RMSLimiter::from_config("left", left_conf, 1024, 48000);
+        let mut right = RMSLimiter::from_config("right", right_conf, 1024, 48000);
+        for level in [0.05, 0.5, 1.0, 0.2] {
+            let left_output = output_level(&mut left, level, 1024);
+            let right_output = output_level(&mut right, level, 1024);
+            assert_eq!(left.current_gain, right.current_gain);
+            let difference_db = voltage_ratio_to_db(right_output / left_output);
+            assert!((difference_db + 0.5).abs() < 1.0e-4);
+        }
+        assert!(left.current_gain < 1.0);
+    }
+
+    #[test]
+    fn detection_buffer_wraps_around() {
+        let mut limiter = RMSLimiter::from_config("test", params(0.0, 12.0, 64), 48, 48000);
+        let mut waveform: Vec<PrcFmt> = (0..48).map(|n| n as PrcFmt / 100.0).collect();
+        limiter.process_waveform(&mut waveform).unwrap();
+        assert_eq!(limiter.detection_buffer(), waveform);
+
+        // The next 48 samples overwrite the 32 oldest ones
+        let mut waveform: Vec<PrcFmt> = (48..96).map(|n| n as PrcFmt / 100.0).collect();
+        limiter.process_waveform(&mut waveform).unwrap();
+        let expected: Vec<PrcFmt> = (32..96).map(|n| n as PrcFmt / 100.0).collect();
+        assert_eq!(limiter.detection_buffer(), expected);
+    }
+
+    #[cfg(feature = "osc")]
+    #[test]
+    fn send_reduction_over_osc() {
+        let receiver = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        receiver
+            .set_read_timeout(Some(std::time::Duration::from_secs(2)))
+            .unwrap();
+        let mut conf = params(-20.0, 12.0, 1024);
+        conf.osc_target = Some(receiver.local_addr().unwrap().to_string());
+        conf.osc_path = Some("/test".to_string());
+        let mut limiter = RMSLimiter::from_config("test", conf, 1024, 48000);
+        // One message for each update of the meter
+        let mut sent = Vec::new();
+        for _ in 0..4 {
+            output_level(&mut limiter, 1.0, 1024);
+            sent.push(limiter.metered_reduction_db() as f32);
+        }
+        let mut buffer = [0; 64];
+        for value in sent {
+            let len = receiver.recv(&mut buffer).unwrap();
+            assert_eq!(
+                &buffer[..len],
+                crate::oscsender::encode_float_message("/test", value)
+            );
+        }
+        receiver.set_nonblocking(true).unwrap();
+        assert!(receiver.recv(&mut buffer).is_err());
+    }
+
+    fn release_chunks(time_scale: f32) -> usize {
+        let mut conf = params(-20.0, 20.0, 1024);
+        conf.time_scale = Some(time_scale);
+        let mut limiter = RMSLimiter::from_config("test", conf, 1024, 48000);
+        output_level(&mut limiter, 1.0, 1024);
+        let mut chunks = 0;
+        while limiter.current_gain < 1.0 {
+            output_level(&mut limiter, 0.001, 1024);
+            chunks += 1;
+        }
+        chunks
+    }
+
+    #[test]
+    fn time_scale_slows_release() {
+        // 20 dB at 20 dB/s takes one second, or 47 chunks
+        assert_eq!(release_chunks(1.0), 47);
+        assert_eq!(release_chunks(2.0), 94);
+        // The attack is scaled as well
+        let mut conf = params(-20.0, 20.0, 1024);
+        conf.attack_from_window = Some(0.5);
+        let limiter = RMSLimiter::from_config("test", conf.clone(), 1024, 48000);
+        let attack_time = limiter.attack_time.unwrap();
+        conf.time_scale = Some(2.0);
+        let mut limiter = RMSLimiter::from_config("test", conf.clone(), 1024, 48000);
+        assert!((limiter.attack_time.unwrap() - 2.0 * attack_time).abs() < 1.0e-9);
+        conf.time_scale = Some(4.0);
+        limiter.update_parameters(config::Filter::RMSLimiter {
+            description: None,
+            parameters: conf,
+        });
+        assert!((limiter.attack_time.unwrap() - 4.0 * attack_time).abs() < 1.0e-9);
+        assert!(validate_config(48000, 1024, &params(-20.0, 20.0, 1024)).is_ok());
+        let mut conf = params(-20.0, 20.0, 1024);
+        conf.time_scale = Some(0.0);
+        assert!(validate_config(48000, 1024, &conf).is_err());
+    }
+
+    #[test]
+    fn prefill_limits_first_chunk() {
+        let mut conf = params(-20.0, 12.0, 1024);
+        conf.wait_for_warmup = Some(true);
+        let mut limiter = RMSLimiter::from_config("test", conf.clone(), 64, 48000);
+        assert_eq!(output_level(&mut limiter, 1.0, 64), 1.0);
+
+        let mut limiter = RMSLimiter::from_config("test", conf, 64, 48000);
+        limiter.prefill(1.0);
+        assert!(limiter.is_warmed_up());
+        let output = output_level(&mut limiter, 1.0, 64);
+        assert!((voltage_ratio_to_db(output) + 20.0).abs() < 1.0e-4);
+    }
+
+    fn release_curve(release_domain: config::ReleaseDomain) -> Vec<PrcFmt> {
+        let mut conf = params(-20.0, 20.0, 1024);
+        conf.release_domain = Some(release_domain);
+        let mut limiter = RMSLimiter::from_config("test", conf, 1024, 48000);
+        output_level(&mut limiter, 1.0, 1024);
+        (0..10)
+            .map(|_| {
+                output_level(&mut limiter, 0.001, 1024);
+                limiter.current_gain
+            })
+            .collect()
+    }
+
+    #[test]
+    fn release_domains() {
+        let db_curve = release_curve(config::ReleaseDomain::Db);
+        let linear_curve = release_curve(config::ReleaseDomain::Linear);
+        // Constant steps in dB, and constant linear steps
+        for gains in db_curve.windows(3) {
+            assert!((gains[2] / gains[1] - gains[1] / gains[0]).abs() < 1.0e-4);
+        }
+        for gains in linear_curve.windows(3) {
+            assert!(((gains[2] - gains[1]) - (gains[1] - gains[0])).abs() < 1.0e-4);
+        }
+        // Starting from the same gain, the linear release recovers faster
+        for (db_gain, linear_gain) in db_curve.iter().zip(linear_curve.iter()) {
+            assert!(linear_gain > db_gain);
+        }
+    }
+
+    #[test]
+    fn reset_on_silence() {
+        let mut conf = params(-20.0, 12.0, 1024);
+        let mut limiter = RMSLimiter::from_config("test", conf.clone(), 1024, 48000);
+        output_level(&mut limiter, 1.0, 1024);
+        output_level(&mut limiter, 0.0, 1024);
+        assert!(limiter.current_gain < 0.2);
+
+        conf.reset_on_silence = Some(true);
+        let mut limiter = RMSLimiter::from_config("test", conf, 1024, 48000);
+        output_level(&mut limiter, 1.0, 1024);
+        assert!(limiter.current_gain < 0.2);
+        // Quiet but not silent, the normal release is used
+        output_level(&mut limiter, 1.0e-3, 1024);
+        assert!(limiter.current_gain < 0.2);
+        output_level(&mut limiter, 0.0, 1024);
+        assert_eq!(limiter.current_gain, 1.0);
+    }
+
+    /// Gain reduction in dB of two limiters in series, for a 0 dB input.
+    fn cascade_reductions(first_sensitivity_db: f32) -> (PrcFmt, PrcFmt) {
+        let mut first_conf = params(-10.0, 12.0, 1024);
+        first_conf.sensitivity_db = Some(first_sensitivity_db);
+        let mut first = RMSLimiter::from_config("first", first_conf, 1024, 48000);
+        let mut second = RMSLimiter::from_config("second", params(-12.0, 12.0, 1024), 1024, 48000);
+        let mut waveform = vec![1.0; 1024];
+        first.process_waveform(&mut waveform).unwrap();
+        second.process_waveform(&mut waveform).unwrap();
+        assert!((voltage_ratio_to_db(waveform[0]) + 12.0).abs() < 1.0e-4);
+        (
+            -voltage_ratio_to_db(first.current_gain),
+            -voltage_ratio_to_db(second.current_gain),
+        )
+    }
+
+    #[test]
+    fn sensitivity_shifts_reduction_between_stages() {
+        let (first, second) = cascade_reductions(0.0);
+        assert!((first - 10.0).abs() < 1.0e-4);
+        assert!((second - 2.0).abs() < 1.0e-4);
+        // A less sensitive first stage leaves more of the work to the second
+        let (first, second) = cascade_reductions(-6.0);
+        assert!((first - 4.0).abs() < 1.0e-4);
+        assert!((second - 8.0).abs() < 1.0e-4);
+    }
+
+    fn sine_with_dc(limiter: &mut RMSLimiter, chunks: usize) -> Vec<PrcFmt> {
+        let mut waveform = Vec::new();
+        for chunk in 0..chunks {
+            waveform = (0..1024)
+                .map(|n| {
+                    let phase = 2.0 * std::f64::consts::PI as PrcFmt * 16.0 / 1024.0;
+                    0.3 + 0.1 * (phase * (chunk * 1024 + n) as PrcFmt).sin()
+                })
+                .collect();
+            limiter.process_waveform(&mut waveform).unwrap();
+        }
+        waveform
+    }
+
+    #[test]
+    fn remove_dc_before_detection() {
+        // The sine alone is -23 dB rms, below the threshold, but the DC offset pushes it above
+        let conf = params(-20.0, 12.0, 1024);
+        let mut limiter = RMSLimiter::from_config("test", conf.clone(), 1024, 48000);
+        sine_with_dc(&mut limiter, 50);
+        assert!(limiter.current_gain < 0.5);
+
+        let mut conf = conf;
+        conf.remove_dc = Some(true);
+        let mut limiter = RMSLimiter::from_config("test", conf.clone(), 1024, 48000);
+        let output = sine_with_dc(&mut limiter, 50);
+        assert_eq!(limiter.current_gain, 1.0);
+        assert!((limiter.dc_offset() - 0.3).abs() < 1.0e-3);
+        // The DC is still in the output
+        let mean = output.iter().sum::<PrcFmt>() / output.len() as PrcFmt;
+        assert!((mean - 0.3).abs() < 1.0e-3);
+
+        conf.remove_dc_from_output = Some(true);
+        let mut limiter = RMSLimiter::from_config("test", conf, 1024, 48000);
+        let output = sine_with_dc(&mut limiter, 50);
+        let mean = output.iter().sum::<PrcFmt>() / output.len() as PrcFmt;
+        assert!(mean.abs() < 1.0e-3);
+    }
+
+    #[test]
+    fn dc_stays_in_untouched_output() {
+        let mut conf = params(-20.0, 12.0, 1024);
+        conf.remove_dc = Some(true);
+        conf.remove_dc_from_output = Some(true);
+        let mut analyze_only = conf.clone();
+        analyze_only.analyze_only = Some(true);
+        let mut bypassed = conf.clone();
+        bypassed.bypassed = Some(true);
+        let mut inactive = conf.clone();
+        inactive.active_ranges = Some(vec![(1024 * 1024, 2048 * 1024)]);
+        for conf in [analyze_only, bypassed, inactive] {
+            let mut limiter = RMSLimiter::from_config("test", conf, 1024, 48000);
+            let output = sine_with_dc(&mut limiter, 50);
+            let mean = output.iter().sum::<PrcFmt>() / output.len() as PrcFmt;
+            assert!((mean - 0.3).abs() < 1.0e-3);
+            assert!((limiter.dc_offset() - 0.3).abs() < 1.0e-3);
+        }
+        conf.detect_from_channel = Some(0);
+        assert!(validate_config(48000, 1024, &conf).is_err());
+    }
+
+    #[test]
+    fn steady_state_gains() {
+        assert_eq!(steady_state_gain(-6.0, -20.0), 1.0);
+        assert_eq!(steady_state_gain(-6.0, -6.0), 1.0);
+        assert!((voltage_ratio_to_db(steady_state_gain(-6.0, 4.0)) + 10.0).abs() < 1.0e-4);
+        // Matches the gain the limiter reaches for a steady signal
+        let mut limiter = RMSLimiter::from_config("test", params(-6.0, 12.0, 1024), 1024, 48000);
+        output_level(&mut limiter, db_to_voltage_ratio(4.0), 1024);
+        assert!((limiter.current_gain - steady_state_gain(-6.0, 4.0)).abs() < 1.0e-6);
+    }
+
+    fn gains_for_sweep(decimation: usize) -> Vec<PrcFmt> {
+        let mut conf = params(-20.0, 12.0, 1024);
+        conf.detection_decimation = Some(decimation);
+        let mut limiter = RMSLimiter::from_config("test", conf, 256, 192000);
+        (0..64)
+            .map(|chunk| {
+                // A 1 kHz sine with a slowly rising level
+                let mut waveform: Vec<PrcFmt> = (0..256)
+                    .map(|n| {
+                        let t = (chunk * 256 + n) as PrcFmt / 192000.0;
+                        (0.05 + 5.0 * t) * (2.0 * std::f64::consts::PI as PrcFmt * 1000.0 * t).sin()
+                    })
+                    .collect();
+                limiter.process_waveform(&mut waveform).unwrap();
+                limiter.current_gain
+            })
+            .collect()
+    }
+
+    #[test]
+    fn decimated_detection() {
+        let full_rate = gains_for_sweep(1);
+        let decimated = gains_for_sweep(8);
+        assert!(full_rate[63] < 0.5);
+        for (full, dec) in full_rate.iter().zip(decimated.iter()) {
+            assert!((voltage_ratio_to_db(*full) - voltage_ratio_to_db(*dec)).abs() < 0.2);
+        }
+        let mut conf = params(-20.0, 12.0, 1024);
+        conf.detection_decimation = Some(3);
+        assert!(validate_config(192000, 256, &conf).is_err());
+        conf.detection_decimation = Some(2048);
+        assert!(validate_config(192000, 256, &conf).is_err());
+    }
+
+    #[test]
+    fn replay_gain_schedule() {
+        let input: Vec<Vec<PrcFmt>> = (0..20)
+            .map(|chunk| {
+                (0..256)
+                    .map(|n| 0.1 * (chunk % 5) as PrcFmt * (n as PrcFmt * 0.05).sin())
+                    .collect()
+            })
+            .collect();
+        let mut recorder = RMSLimiter::from_config("test", params(-20.0, 12.0, 512), 256, 48000);
+        recorder.record_schedule();
+        let mut recorded_output = input.clone();
+        for waveform in recorded_output.iter_mut() {
+            recorder.process_waveform(waveform).unwrap();
+        }
+        let schedule = recorder.recorded_schedule().to_vec();
+        assert_eq!(schedule.len(), 20);
+        assert!(schedule.iter().any(|g| *g < 1.0));
+
+        // A limiter with a different threshold follows the schedule, not its own detection
+        let mut player = RMSLimiter::from_config("test", params(-3.0, 12.0, 512), 256, 48000);
+        player.replay_schedule(schedule);
+        let mut replayed_output = input.clone();
+        for waveform in replayed_output.iter_mut() {
+            player.process_waveform(waveform).unwrap();
+        }
+        assert_eq!(recorded_output, replayed_output);
+
+        // Detection resumes when the schedule has run out
+        let mut waveform = vec![10.0; 256];
+        player.process_waveform(&mut waveform).unwrap();
+        assert!(player.current_gain < 0.2);
+    }
+
+    #[test]
+    fn ceiling_applies_to_replayed_gain() {
+        let mut conf = params(-20.0, 12.0, 512);
+        conf.ceiling_dbtp = Some(-1.0);
+        let mut recorder = RMSLimiter::from_config("test", conf.clone(), 256, 48000);
+        recorder.record_schedule();
+        for _ in 0..10 {
+            output_level(&mut recorder, 0.1, 256);
+        }
+        let schedule = recorder.recorded_schedule().to_vec();
+        assert!(schedule.iter().all(|g| *g > 0.99));
+
+        // The schedule recorded from the quiet input would let the loud one through
+        let mut player = RMSLimiter::from_config("test", conf, 256, 48000);
+        player.replay_schedule(schedule);
+        let ceiling = db_to_voltage_ratio(-1.0);
+        for _ in 0..10 {
+            let mut waveform = vec![1.0; 256];
+            player.process_waveform(&mut waveform).unwrap();
+            assert!(true_peak(&waveform) <= ceiling * 1.000001);
+        }
+    }
+
+    #[test]
+    fn non_finite_input_is_reported_by_strict_ceiling() {
+        let mut conf = params(-20.0, 12.0, 512);
+        conf.ceiling_dbtp = Some(-1.0);
+        conf.mute_on_fault = Some(false);
+        conf.strict_ceiling_tolerance_db = Some(0.1);
+        let mut limiter = RMSLimiter::from_config("test", conf, 256, 48000);
+        let mut waveform = vec![0.5; 256];
+        waveform[100] = PrcFmt::INFINITY;
+        assert!(limiter.process_waveform(&mut waveform).is_ok());
+        assert_eq!(limiter.max_output_over_ceiling_db(), Some(PrcFmt::INFINITY));
+    }
+
+    #[test]
+    fn mute_on_non_finite_gain() {
+        let mut limiter = RMSLimiter::from_config("test", params(-20.0, 12.0, 512), 256, 48000);
+        limiter.replay_schedule(vec![PrcFmt::NAN, PrcFmt::INFINITY]);
+        let mut waveform = vec![0.5; 256];
+        limiter.process_waveform(&mut waveform).unwrap();
+        assert!(waveform.iter().all(|v| *v == 0.0));
+        assert!(limiter.gain_fault());
+        let mut waveform = vec![0.5; 256];
+        limiter.process_waveform(&mut waveform).unwrap();
+        assert!(waveform.iter().all(|v| *v == 0.0));
+        // Detection takes over again, with a finite gain
+        let mut waveform = vec![0.05; 256];
+        limiter.process_waveform(&mut waveform).unwrap();
+        assert!(waveform.iter().all(|v| *v > 0.0 && v.is_finite()));
+        assert!(limiter.gain_fault());
+        limiter.reset();
+        assert!(!limiter.gain_fault());
+
+        // Without muting, the chunk passes at unity gain
+        let mut conf = params(-20.0, 12.0, 512);
+        conf.mute_on_fault = Some(false);
+        let mut limiter = RMSLimiter::from_config("test", conf, 256, 48000);
+        limiter.replay_schedule(vec![PrcFmt::NAN]);
+        let mut waveform = vec![0.05; 256];
+        limiter.process_waveform(&mut waveform).unwrap();
+        assert!(waveform.iter().all(|v| *v == 0.05));
+        assert!(limiter.gain_fault());
+    }
+
+    #[test]
+    fn release_time_instead_of_decay() {
+        let conf: config::RMSLimiterParameters =
+            serde_yaml::from_str("threshold: -6.0\nrms_samples: 512\nrelease_ms: 500").unwrap();
+        assert!((conf.decay_db_per_s() - 20.0).abs() < 1.0e-4);
+        let mut limiter = RMSLimiter::from_config("test", conf, 4800, 48000);
+        let mut decayed = RMSLimiter::from_config("test", params(-6.0, 20.0, 512), 4800, 48000);
+        assert_eq!(limiter.decay_per_chunk, decayed.decay_per_chunk);
+        limiter.process_waveform(&mut vec![1.0; 4800]).unwrap();
+        decayed.process_waveform(&mut vec![1.0; 4800]).unwrap();
+        assert_eq!(limiter.current_gain, decayed.current_gain);
+
+        let mut conf = params(-6.0, 12.0, 512);
+        conf.release_ms = Some(0.0);
+        assert!(validate_config(48000, 1024, &conf).is_err());
+    }
+
+    #[test]
+    fn detect_from_other_channel() {
+        let mut conf = params(-20.0, 12.0, 256);
+        conf.detect_from_channel = Some(0);
+        let mut limiter = RMSLimiter::from_config("test", conf, 256, 48000);
+        let mut loud = vec![0.5; 256];
+        let mut quiet = vec![0.01; 256];
+        limiter
+            .process_frames(&mut [loud.as_mut_slice(), quiet.as_mut_slice()])
+            .unwrap();
+        // The loud channel 0 sets a gain of -14 dB, that is applied to channel 1 as well
+        let gain = db_to_voltage_ratio(-20.0) / 0.5;
+        assert!((loud[0] - 0.5 * gain).abs() < 1.0e-6);
+        assert!((quiet[0] - 0.01 * gain).abs() < 1.0e-6);
+
+        let mut conf = params(-20.0, 12.0, 256);
+        conf.detect_from_channel = Some(2);
+        let mut limiter = RMSLimiter::from_config("test", conf, 256, 48000);
+        let err = limiter
+            .process_frames(&mut [loud.as_mut_slice(), quiet.as_mut_slice()])
+            .unwrap_err();
+        assert!(err.is::<ProcessingError>());
+    }
+
+    #[test]
+    fn linked_channels_share_the_processing() {
+        // The ceiling covers the louder channel, also when detecting from the quiet one
+        let mut conf = params(-20.0, 12.0, 256);
+        conf.detect_from_channel = Some(0);
+        conf.ceiling_dbtp = Some(-1.0);
+        conf.strict_ceiling_tolerance_db = Some(0.0);
+        let mut limiter = RMSLimiter::from_config("test", conf.clone(), 256, 48000);
+        limiter.schedule_change(128, ParamChange::InputGain(-6.0));
+        let mut quiet = vec![0.01; 256];
+        let mut loud = vec![1.0; 256];
+        limiter
+            .process_frames(&mut [quiet.as_mut_slice(), loud.as_mut_slice()])
+            .unwrap();
+        assert!(true_peak(&loud) <= db_to_voltage_ratio(-1.0) * 1.000001);
+        // The scheduled change splits both channels at the same sample
+        assert!(loud[200] < 0.6 * loud[100]);
+        assert_eq!(limiter.sample_position(), 256);
+
+        // The peaks of each channel are shaved, in one step of the stream position
+        conf.peak_shaver = Some(true);
+        let mut limiter = RMSLimiter::from_config("test", conf, 256, 48000);
+        let mut quiet = vec![0.01; 256];
+        let mut loud = vec![1.0; 256];
+        limiter
+            .process_frames(&mut [quiet.as_mut_slice(), loud.as_mut_slice()])
+            .unwrap();
+        assert!(loud
+            .iter()
+            .all(|v| *v <= db_to_voltage_ratio(-1.0) * 1.000001));
+        assert_eq!(quiet, vec![0.01; 256]);
+        assert_eq!(limiter.sample_position(), 256);
+    }
+
+    #[test]
+    fn frames_without_detection_channel() {
+        // The stream position advances once per frame, not once per channel
+        let mut limiter = RMSLimiter::from_config("test", params(0.0, 12.0, 256), 256, 48000);
+        limiter.schedule_change(384, ParamChange::InputGain(-6.0));
+        let mut left = vec![0.5; 256];
+        let mut right = vec![0.2; 256];
+        limiter
+            .process_frames(&mut [left.as_mut_slice(), right.as_mut_slice()])
+            .unwrap();
+        assert_eq!(left, vec![0.5; 256]);
+        assert_eq!(right, vec![0.2; 256]);
+        assert_eq!(limiter.sample_position(), 256);
+        let mut left = vec![0.5; 256];
+        let mut right = vec![0.2; 256];
+        limiter
+            .process_frames(&mut [left.as_mut_slice(), right.as_mut_slice()])
+            .unwrap();
+        assert_eq!(left[100], 0.5);
+        assert!(left[200] < 0.6 * left[100]);
+        assert!(right[200] < 0.6 * right[100]);
+        assert_eq!(limiter.sample_position(), 512);
+
+        // The channels share one gain, detected from the loudest of them
+        let mut limiter = RMSLimiter::from_config("test", params(-20.0, 12.0, 256), 256, 48000);
+        let mut quiet = vec![0.01; 256];
+        let mut loud = vec![0.5; 256];
+        limiter
+            .process_frames(&mut [quiet.as_mut_slice(), loud.as_mut_slice()])
+            .unwrap();
+        let gain = db_to_voltage_ratio(-20.0) / 0.5;
+        assert!((loud[255] - 0.5 * gain).abs() < 1.0e-6);
+        assert!((quiet[255] - 0.01 * gain).abs() < 1.0e-6);
+    }
+
+    #[test]
+    fn smoothstep_knee_slope() {
+        // Slope of the output curve just below and just above a level
+        let slopes = |level_db: PrcFmt, shape: config::KneeShape| -> (PrcFmt, PrcFmt) {
+            let delta = 0.001;
+            let output = |db: PrcFmt| knee(shape, 0.0).output_db(db, -10.0);
+            (
+                (output(level_db) - output(level_db - delta)) / delta,
+                (output(level_db + delta) - output(level_db)) / delta,
+            )
+        };
+        // The knee spans -13 to -7 dB
+        for boundary in [-13.0, -7.0] {
+            let (below, above) = slopes(boundary, config::KneeShape::Smoothstep);
+            assert!((below - above).abs() < 0.01);
+            let (below, above) = slopes(boundary, config::KneeShape::Linear);
+            assert!((below - above).abs() > 0.4);
+        }
+        for shape in [config::KneeShape::Linear, config::KneeShape::Smoothstep] {
+            assert_eq!(knee(shape, 0.0).output_db(-20.0, -10.0), -20.0);
+            assert_eq!(knee(shape, 0.0).output_db(0.0, -10.0), -10.0);
+        }
+        let linear = knee(config::KneeShape::Linear, 0.0).output_db(-10.0, -10.0);
+        let smooth = knee(config::KneeShape::Smoothstep, 0.0).output_db(-10.0, -10.0);
+        assert!((linear + 11.5).abs() < 1.0e-4);
+        assert!((smooth + 10.5625).abs() < 1.0e-4);
+
+        let mut conf = params(-10.0, 12.0, 512);
+        conf.soft = Some(true);
+        conf.knee_shape = Some(config::KneeShape::Smoothstep);
+        let mut limiter = RMSLimiter::from_config("test", conf, 512, 48000);
+        let level = db_to_voltage_ratio(0.0);
+        let out = output_level(&mut limiter, level, 512);
+        assert!((voltage_ratio_to_db(out) + 10.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn limit_within_active_range() {
+        let mut conf = params(-20.0, 12.0, 1024);
+        conf.active_ranges = Some(vec![(4096, 8192)]);
+        let mut limiter = RMSLimiter::from_config("test", conf, 1024, 48000);
+        let gain = db_to_voltage_ratio(-20.0) / 0.5;
+        for chunk in 0..12 {
+            let mut waveform = vec![0.5; 1024];
+            limiter.process_waveform(&mut waveform).unwrap();
+            if chunk < 4 {
+                // Before the range the signal is untouched
+                assert!(waveform.iter().all(|v| *v == 0.5));
+            } else if chunk < 8 {
+                // The crossfade of 10 ms is done within the first chunk of the range.
+                // A steady level makes the gain step up and down by one release step.
+                assert!((waveform[1023] / (0.5 * gain) - 1.0).abs() < 0.05);
+            } else if chunk > 8 {
+                assert!(waveform.iter().all(|v| *v == 0.5));
+            }
+        }
+
+        let mut conf = params(-20.0, 12.0, 1024);
+        conf.active_ranges = Some(vec![(0, 1000), (2000, 2000)]);
+        assert!(validate_config(48000, 1024, &conf).is_err());
+    }
+
+    #[test]
+    fn same_output_with_vecdeque_buffer() {
+        let mut conf = params(-20.0, 12.0, 512);
+        conf.detection = Some(config::DetectionType::RmsOfPeaks);
+        let mut default = RMSLimiter::from_config("test", conf.clone(), 256, 48000);
+        let mut deque: RMSLimiter<VecDequeBuffer> =
+            RMSLimiter::with_detection_buffer("test", conf, 256, 48000);
+        for chunk in 0..20 {
+            let input: Vec<PrcFmt> = (0..256)
+                .map(|n| 0.1 * (chunk % 7) as PrcFmt * ((chunk * 256 + n) as PrcFmt * 0.03).sin())
+                .collect();
+            let mut left = input.clone();
+            let mut right = input;
+            default.process_waveform(&mut left).unwrap();
+            deque.process_waveform(&mut right).unwrap();
+            assert_eq!(left, right);
+        }
+    }
+
+    #[test]
+    fn strict_ceiling_report() {
+        let mut conf = params(-3.0, 12.0, 256);
+        conf.ceiling_dbtp = Some(-1.0);
+        conf.strict_ceiling_tolerance_db = Some(0.1);
+        let mut limiter = RMSLimiter::from_config("test", conf.clone(), 256, 48000);
+        assert_eq!(limiter.max_output_over_ceiling_db(), None);
+        // Bursts of a sine at fs/4 with intersample peaks, and full scale square waves
+        for chunk in 0..40 {
+            let level = [0.1, 1.5, 0.5, 4.0][chunk % 4];
+            let mut waveform: Vec<PrcFmt> = (0..256)
+                .map(|n| {
+                    if chunk % 8 == 7 {
+                        level * if n % 16 < 8 { 1.0 } else { -1.0 }
+                    } else {
+                        level * (std::f64::consts::PI as PrcFmt * (0.5 * n as PrcFmt + 0.25)).sin()
+                    }
+                })
+                .collect();
+            limiter.process_waveform(&mut waveform).unwrap();
+        }
+        let max_over = limiter.max_output_over_ceiling_db().unwrap();
+        assert!(max_over <= 0.1);
+        assert!(max_over > -1.0);
+
+        // Analyze only mode leaves the loud input untouched, which is reported
+        // without stopping the processing
+        conf.analyze_only = Some(true);
+        let mut limiter = RMSLimiter::from_config("test", conf.clone(), 256, 48000);
+        assert!(limiter.process_waveform(&mut vec![1.0; 256]).is_ok());
+        assert!(limiter.max_output_over_ceiling_db().unwrap() > 0.9);
+
+        conf.ceiling_dbtp = None;
+        assert!(validate_config(48000, 256, &conf).is_err());
+    }
+
+    #[test]
+    fn smooth_window_change() {
+        let conf = params(-20.0, 12.0, 4096);
+        let mut limiter = RMSLimiter::from_config("test", conf, 256, 48000);
+        // A long quiet part followed by a short loud part
+        for chunk in 0..16 {
+            let level = if chunk < 14 { 0.2 } else { 1.0 };
+            limiter.process_waveform(&mut vec![level; 256]).unwrap();
+        }
+        let long_level = limiter.detected_level();
+        assert!((long_level - 0.4).abs() < 0.01);
+
+        limiter.update_parameters(config::Filter::RMSLimiter {
+            description: None,
+            parameters: params(-20.0, 12.0, 512),
+        });
+        let mut previous = long_level;
+        for _ in 0..40 {
+            limiter.process_waveform(&mut vec![1.0; 256]).unwrap();
+            // The new window alone would jump straight to 1.0
+            let level = limiter.detected_level();
+            assert!((level - previous).abs() < 0.1);
+            previous = level;
+        }
+        assert!((previous - 1.0).abs() < 1.0e-6);
+    }
+
+    #[test]
+    fn knee_offset() {
+        // A 6 dB knee fully below a threshold of -10 dB spans -16 to -10 dB
+        let below = knee(config::KneeShape::Smoothstep, -3.0);
+        assert_eq!(below.output_db(-16.01, -10.0), -16.01);
+        assert!(below.output_db(-15.9, -10.0) < -15.9);
+        assert_eq!(below.output_db(-5.0, -10.0), -13.0);
+
+        let mut conf = params(-10.0, 12.0, 512);
+        conf.soft = Some(true);
+        conf.knee_shape = Some(config::KneeShape::Linear);
+        conf.knee_db = Some(10.0);
+        conf.knee_offset_db = Some(2.0);
+        assert!(validate_config(48000, 512, &conf).is_ok());
+        // The knee spans -13 to -3 dB
+        for (level_db, limited) in [(-13.5, false), (-12.5, true), (-20.0, false), (0.0, true)] {
+            let mut limiter = RMSLimiter::from_config("test", conf.clone(), 512, 48000);
+            let level = db_to_voltage_ratio(level_db);
+            let out = output_level(&mut limiter, level, 512);
+            assert_eq!(out < level * 0.9999, limited);
+        }
+        conf.knee_offset_db = Some(6.0);
+        assert!(validate_config(48000, 512, &conf).is_err());
+        conf.knee_offset_db = None;
+        conf.knee_db = Some(0.0);
+        assert!(validate_config(48000, 512, &conf).is_err());
+    }
+
+    #[test]
+    fn tanh_knee_follows_the_configuration() {
+        // A 12 dB knee approaching -13 dB, for a threshold of -10 dB, starts at -25 dB
+        let tanh = Knee {
+            shape: config::KneeShape::Tanh,
+            width_db: 12.0,
+            offset_db: -3.0,
+        };
+        assert_eq!(tanh.output_db(-25.01, -10.0), -25.01);
+        assert!(tanh.output_db(-24.0, -10.0) < -24.0);
+        let loud = tanh.output_db(60.0, -10.0);
+        assert!(loud < -13.0 && loud > -13.01);
+        // The default knee is unchanged
+        let default = Knee::from_config(&params(-10.0, 12.0, 512));
+        assert_eq!(default.output_db(-16.0, -10.0), -16.0);
+        assert!(
+            (default.output_db(-10.0, -10.0) - (-16.0 + 6.0 * (1.0 as PrcFmt).tanh())).abs()
+                < 1.0e-9
+        );
+    }
+
+    #[test]
+    fn flush_denormals_in_quiet_tail() {
+        let mut conf = params(-20.0, 12.0, 1024);
+        conf.remove_dc = Some(true);
+        let mut limiter = RMSLimiter::from_config("test", conf, 1024, 1000);
+        let mut waveform = vec![0.3; 1024];
+        limiter.process_waveform(&mut waveform).unwrap();
+        // The running mean decays towards zero, and would end up as a denormal without flushing
+        for _ in 0..10 {
+            let mut waveform = vec![0.0; 1024];
+            limiter.process_waveform(&mut waveform).unwrap();
+        }
+        assert_eq!(limiter.dc_offset(), 0.0);
+
+        // Denormal input reaches neither the detection buffer nor the output
+        let mut waveform = vec![PrcFmt::MIN_POSITIVE / 4.0; 1024];
+        limiter.process_waveform(&mut waveform).unwrap();
+        assert!(limiter.rms_buffer.to_vec().iter().all(|v| *v == 0.0));
+        assert!(waveform.iter().all(|v| *v == 0.0));
+        assert_eq!(limiter.current_gain, 1.0);
+    }
+
+    #[test]
+    fn log_rms_detects_below_the_flush_limit() {
+        // The samples are flushed for rms detection, and their squares underflow
+        let amplitude = 1.0e-9 * PrcFmt::MIN_POSITIVE.sqrt();
+        let sine: Vec<PrcFmt> = (0..1024).map(|n| (0.05 * n as PrcFmt).sin()).collect();
+        let quiet: Vec<PrcFmt> = sine.iter().map(|v| amplitude * v).collect();
+        let mut conf = params(-20.0, 12.0, 1024);
+        let mut limiter = RMSLimiter::from_config("test", conf.clone(), 1024, 48000);
+        limiter.process_waveform(&mut quiet.clone()).unwrap();
+        assert_eq!(limiter.detected_level(), 0.0);
+
+        conf.detection = Some(config::DetectionType::LogRms);
+        let mut limiter = RMSLimiter::from_config("test", conf, 1024, 48000);
+        limiter.process_waveform(&mut quiet.clone()).unwrap();
+        let expected = amplitude * rms(sine.iter());
+        assert!((limiter.detected_level() - expected).abs() / expected < 1.0e-3);
+        assert_eq!(limiter.gain(), 1.0);
+    }
+
+    #[test]
+    fn makeup_follows_reduction() {
+        let conf = params(-20.0, 12.0, 1024);
+        let mut plain = RMSLimiter::from_config("test", conf.clone(), 1024, 48000);
+        let mut with_makeup = conf;
+        with_makeup.makeup_fraction = Some(0.0);
+        let mut none = RMSLimiter::from_config("test", with_makeup.clone(), 1024, 48000);
+        with_makeup.makeup_fraction = Some(0.5);
+        let mut half = RMSLimiter::from_config("test", with_makeup.clone(), 1024, 48000);
+        with_makeup.makeup_fraction = Some(1.0);
+        assert!(validate_config(48000, 1024, &with_makeup).is_ok());
+        let mut full = RMSLimiter::from_config("test", with_makeup.clone(), 1024, 48000);
+        let level = 0.5;
+        let plain_output = output_level(&mut plain, level, 1024);
+        assert!(plain_output < 0.5 * level);
+        assert_eq!(output_level(&mut none, level, 1024), plain_output);
+        // Half the reduction in dB is restored
+        let half_output = output_level(&mut half, level, 1024);
+        assert!((half_output - (level * plain_output).sqrt()).abs() < 1.0e-6);
+        assert!((output_level(&mut full, level, 1024) - level).abs() < 1.0e-6);
+
+        with_makeup.ceiling_dbtp = Some(-1.0);
+        assert!(validate_config(48000, 1024, &with_makeup).is_err());
+        with_makeup.ceiling_dbtp = None;
+        with_makeup.makeup_fraction = Some(1.5);
+        assert!(validate_config(48000, 1024, &with_makeup).is_err());
+    }
+
+    #[test]
+    fn warm_restart_from_state() {
+        let conf = params(-20.0, 12.0, 4096);
+        let mut limiter = RMSLimiter::from_config("test", conf.clone(), 1024, 48000);
+        for n in 0..20 {
+            output_level(&mut limiter, 0.2 + 0.04 * n as PrcFmt, 1024);
+        }
+        assert!(limiter.current_gain < 0.5);
+        let path = std::env::temp_dir().join("camilladsp_limiter_state_test.bin");
+        let path = path.to_str().unwrap();
+        limiter.save_state(path).unwrap();
+
+        let mut restarted = RMSLimiter::from_config("test", conf.clone(), 1024, 48000);
+        restarted.load_state(path).unwrap();
+        assert_eq!(restarted.current_gain, limiter.current_gain);
+        assert!(restarted.is_warmed_up());
+        let output = output_level(&mut limiter, 0.3, 1024);
+        assert_eq!(output_level(&mut restarted, 0.3, 1024), output);
+        assert_eq!(restarted.current_gain, limiter.current_gain);
+
+        // A cold limiter behaves differently
+        let mut cold = RMSLimiter::from_config("test", conf.clone(), 1024, 48000);
+        assert!(output_level(&mut cold, 0.3, 1024) != output);
+
+        // A state saved with other settings is rejected
+        let mut conf = conf;
+        conf.decay = 6.0;
+        let mut other = RMSLimiter::from_config("test", conf, 1024, 48000);
+        assert!(other.load_state(path).is_err());
+        assert_eq!(other.current_gain, 1.0);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn export_recorded_envelope() {
+        let mut limiter = RMSLimiter::from_config("test", params(-20.0, 12.0, 1024), 1024, 48000);
+        limiter.record_schedule();
+        // The scheduled change splits the second chunk in two parts
+        limiter.schedule_change(1024 + 256, ParamChange::InputGain(-6.0));
+        for _ in 0..4 {
+            output_level(&mut limiter, 0.5, 1024);
+        }
+        assert_eq!(limiter.recorded_schedule().len(), 5);
+        let path = std::env::temp_dir().join("camilladsp_limiter_envelope_test.bin");
+        let path = path.to_str().unwrap();
+        limiter.save_envelope(path, 256).unwrap();
+        let envelope = read_envelope(&mut std::fs::File::open(path).unwrap()).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(envelope.samplerate, 48000);
+        assert_eq!(envelope.decimation, 256);
+        assert_eq!(envelope.values.len(), 16);
+        let schedule = limiter.recorded_schedule();
+        for (value, gain) in [(4, 1), (5, 2), (15, 4)] {
+            assert!((envelope.values[value] as PrcFmt - schedule[gain]).abs() < 1.0e-6);
+        }
+    }
+
+    fn weighted_gain(weights: Option<Vec<(f32, f32)>>, freq: PrcFmt) -> PrcFmt {
+        let mut conf = params(-20.0, 12.0, 4096);
+        conf.detection_weights = weights;
+        assert!(validate_config(48000, 1024, &conf).is_ok());
+        let mut limiter = RMSLimiter::from_config("test", conf, 1024, 48000);
+        let omega = 2.0 * std::f64::consts::PI as PrcFmt * freq / 48000.0;
+        for chunk in 0..20 {
+            let mut waveform: Vec<PrcFmt> = (chunk * 1024..(chunk + 1) * 1024)
+                .map(|n| 0.5 * (omega * n as PrcFmt).sin())
+                .collect();
+            limiter.process_waveform(&mut waveform).unwrap();
+        }
+        limiter.current_gain
+    }
+
+    #[test]
+    fn weighted_detection() {
+        let unweighted = weighted_gain(None, 50.0);
+        assert!(unweighted < 0.3);
+        // Equal weights detect like no weighting
+        let flat = vec![(100.0, 1.0), (1000.0, 1.0), (10000.0, 1.0)];
+        assert!((weighted_gain(Some(flat), 50.0) - unweighted).abs() < 0.1 * unweighted);
+        // Bass is de-emphasized with a low bass weight, while treble is not
+        let low_bass = vec![(100.0, 0.1), (1000.0, 1.0), (10000.0, 1.0)];
+        assert!(weighted_gain(Some(low_bass.clone()), 50.0) > 0.9);
+        assert!(weighted_gain(Some(low_bass), 5000.0) < 0.3);
+
+        let mut conf = params(-20.0, 12.0, 4096);
+        conf.detection_weights = Some(vec![(1000.0, 1.0), (100.0, 1.0)]);
+        assert!(validate_config(48000, 1024, &conf).is_err());
+        conf.detection_weights = Some(vec![(100.0, -1.0)]);
+        assert!(validate_config(48000, 1024, &conf).is_err());
+        conf.detection_weights = Some(vec![(30000.0, 1.0)]);
+        assert!(validate_config(48000, 1024, &conf).is_err());
+    }
+
+    #[test]
+    fn phase_follows_transient() {
+        let mut conf = params(-20.0, 12.0, 1024);
+        conf.detection_hold_ms = Some(100.0);
+        let mut limiter = RMSLimiter::from_config("test", conf, 1024, 48000);
+        assert_eq!(limiter.current_phase(), Phase::Idle);
+        let mut phases = Vec::new();
+        for chunk in 0..200 {
+            let level = if chunk == 0 { 1.0 } else { 0.01 };
+            output_level(&mut limiter, level, 1024);
+            if phases.last() != Some(&limiter.current_phase()) {
+                phases.push(limiter.current_phase());
+            }
+        }
+        assert_eq!(
+            phases,
+            vec![Phase::Attack, Phase::Hold, Phase::Release, Phase::Idle]
+        );
+    }
+
+    #[test]
+    fn preview_reduction_envelope() {
+        let mut limiter = RMSLimiter::from_config("test", params(-6.0, 12.0, 256), 1024, 48000);
+        output_level(&mut limiter, 0.01, 1024);
+        let snippet: Vec<PrcFmt> = (0..5000)
+            .map(|n| (n as PrcFmt / 5000.0) * (n as PrcFmt * 0.05).sin())
+            .collect();
+        for threshold_db in [-20.0, -12.0] {
+            let envelope = limiter.preview_reduction(&snippet, threshold_db).unwrap();
+            assert_eq!(envelope.len(), 5);
+            assert_eq!(limiter.current_gain, 1.0);
+            let mut processed = RMSLimiter::from_config(
+                "test",
+                params(threshold_db as f32, 12.0, 256),
+                1024,
+                48000,
+            );
+            output_level(&mut processed, 0.01, 1024);
+            for (chunk, reduction) in snippet.chunks(1024).zip(envelope.iter()) {
+                let mut chunk = chunk.to_vec();
+                processed.process_waveform(&mut chunk).unwrap();
+                assert!((reduction + voltage_ratio_to_db(processed.current_gain)).abs() < 1.0e-3);
+            }
+            assert!(envelope[4] > 0.0);
+        }
+    }
+
+    #[test]
+    fn preview_leaves_state_unchanged() {
+        let mut conf = params(-20.0, 12.0, 2048);
+        conf.detection_hold_ms = Some(50.0);
+        let mut limiter = RMSLimiter::from_config("test", conf, 1024, 48000);
+        for level in [0.2, 0.5, 0.3] {
+            output_level(&mut limiter, level, 1024);
+        }
+        let gain = limiter.current_gain;
+        let buffered = limiter.rms_buffer.to_vec();
+        let loud = vec![1.0; 1024];
+        let preview = limiter.preview(&loud).unwrap();
+        assert_eq!(limiter.current_gain, gain);
+        assert_eq!(limiter.rms_buffer.to_vec(), buffered);
+        // The preview matches what processing the chunk gives
+        let mut processed = loud.clone();
+        limiter.process_waveform(&mut processed).unwrap();
+        assert_eq!(preview, processed);
+        assert!(limiter.current_gain < gain);
+    }
+
+    #[test]
+    fn slew_limited_gain() {
+        let mut conf = params(-20.0, 100.0, 1024);
+        conf.slew_limit_db_per_ms = Some(0.05);
+        assert!(validate_config(48000, 1024, &conf).is_ok());
+        let mut limiter = RMSLimiter::from_config("test", conf.clone(), 1024, 48000);
+        // 0.05 dB/ms gives at most 1.07 dB per chunk
+        let max_step_db = 0.05 * 1024.0 / 48.0;
+        let mut previous_db: PrcFmt = 0.0;
+        let mut reached_db: PrcFmt = 0.0;
+        for chunk in 0..60 {
+            let level = if chunk < 30 { 1.0 } else { 0.01 };
+            output_level(&mut limiter, level, 1024);
+            let gain_db = voltage_ratio_to_db(limiter.current_gain);
+            assert!((gain_db - previous_db).abs() <= max_step_db + 1.0e-4);
+            previous_db = gain_db;
+            reached_db = reached_db.min(gain_db);
+        }
+        // The full reduction is still reached, and released
+        assert!(reached_db < -19.0);
+        assert_eq!(limiter.current_gain, 1.0);
+        conf.slew_limit_db_per_ms = Some(0.0);
+        assert!(validate_config(48000, 1024, &conf).is_err());
+    }
+
+    #[test]
+    fn calibration_suggests_threshold() {
+        let mut conf = params(-30.0, 12.0, 1024);
+        conf.calibration_ms = Some(500.0);
+        assert!(validate_config(48000, 1024, &conf).is_ok());
+        let mut limiter = RMSLimiter::from_config("test", conf, 1024, 48000);
+        // The rms of a sine with amplitude 0.25 is -15 dB, and the peak -12 dB
+        let sine: Vec<PrcFmt> = (0..48000)
+            .map(|n| 0.25 * (n as PrcFmt * 0.05).sin())
+            .collect();
+        for chunk in sine.chunks(1024) {
+            let mut waveform = chunk.to_vec();
+            let calibrating = limiter.calibration_result().is_none();
+            limiter.process_waveform(&mut waveform).unwrap();
+            if calibrating {
+                // Passed through unchanged while calibrating
+                assert_eq!(waveform, chunk);
+            }
+        }
+        let threshold = limiter.calibration_result().unwrap();
+        assert!((threshold + 12.04).abs() < 0.1);
+        // After calibrating, the limiter works as configured
+        assert!(limiter.current_gain < 0.5);
+    }
+
+    #[test]
+    fn position_follows_stream() {
+        let mut conf = params(-20.0, 12.0, 1024);
+        conf.calibration_ms = Some(20.0);
+        let mut limiter = RMSLimiter::from_config("test", conf, 480, 48000);
+        assert_eq!(limiter.sample_position(), 0);
+        for chunk in 1..=5 {
+            output_level(&mut limiter, 0.5, 480);
+            assert_eq!(limiter.sample_position(), 480 * chunk);
+        }
+        // Chunks used for calibration are counted
+        assert!(limiter.calibration_result().is_some());
+        let mut empty = vec![];
+        limiter.process_waveform(&mut empty).unwrap();
+        assert_eq!(limiter.sample_position(), 2400);
+        assert!((limiter.stream_time(limiter.sample_position()) - 0.05).abs() < 1.0e-9);
+    }
+
+    #[test]
+    fn release_time_from_note() {
+        let conf: config::RMSLimiterParameters =
+            serde_yaml::from_str("threshold: -6.0\nrms_samples: 512\nrelease_note: 1/4\nbpm: 120")
+                .unwrap();
+        assert!(validate_config(48000, 1024, &conf).is_ok());
+        assert_eq!(conf.release_time_ms(), Some(500.0));
+        let limiter = RMSLimiter::from_config("test", conf.clone(), 4800, 48000);
+        let mut timed = params(-6.0, 12.0, 512);
+        timed.release_ms = Some(500.0);
+        let timed = RMSLimiter::from_config("test", timed, 4800, 48000);
+        assert_eq!(limiter.decay_per_chunk, timed.decay_per_chunk);
+
+        assert_eq!(config::parse_note_value("3/8"), Some(0.375));
+        assert_eq!(config::parse_note_value("0.5"), Some(0.5));
+        assert_eq!(config::parse_note_value("1/0"), None);
+        let mut conf = conf;
+        conf.bpm = None;
+        assert!(validate_config(48000, 1024, &conf).is_err());
+        conf.bpm = Some(120.0);
+        conf.release_note = Some("quarter".to_string());
+        assert!(validate_config(48000, 1024, &conf).is_err());
+    }
+
+    #[test]
+    fn shave_only_peaks() {
+        let mut conf = params(-6.0, 12.0, 1024);
+        conf.ceiling_dbtp = Some(-6.0);
+        assert!(validate_config(48000, 1024, &conf).is_ok());
+        conf.peak_shaver = Some(true);
+        assert!(validate_config(48000, 1024, &conf).is_ok());
+        let mut limiter = RMSLimiter::from_config("test", conf.clone(), 1024, 48000);
+        let ceiling = db_to_voltage_ratio(-6.0);
+        // A quiet sine with a few short spikes
+        let input: Vec<PrcFmt> = (0..1024)
+            .map(|n| {
+                let spike = if n % 256 == 100 { 0.8 } else { 0.0 };
+                0.3 * (n as PrcFmt * 0.05).sin() + spike
+            })
+            .collect();
+        let mut waveform = input.clone();
+        limiter.process_waveform(&mut waveform).unwrap();
+        for (input, output) in input.iter().zip(waveform.iter()) {
+            if input.abs() <= ceiling {
+                assert_eq!(input.to_bits(), output.to_bits());
+            } else {
+                assert!(output.abs() <= ceiling && output.abs() < input.abs());
+            }
+        }
+        conf.ceiling_dbtp = None;
+        assert!(validate_config(48000, 1024, &conf).is_err());
+    }
+
+    fn level_after_step(window: config::WindowFunction) -> PrcFmt {
+        let mut conf = params(-6.0, 12.0, 4096);
+        conf.detection_window = Some(window);
+        assert!(validate_config(48000, 1024, &conf).is_ok());
+        let mut limiter = RMSLimiter::from_config("test", conf, 1024, 48000);
+        for amplitude in [0.01, 0.01, 0.01, 0.01, 0.5] {
+            let mut waveform = vec![amplitude; 1024];
+            limiter.process_waveform(&mut waveform).unwrap();
+        }
+        limiter.detected_level()
+    }
+
+    #[test]
+    fn windowed_detection_follows_recent_level() {
+        let flat = level_after_step(config::WindowFunction::Rectangular);
+        assert!((flat - rms([0.01, 0.01, 0.01, 0.5].iter())).abs() < 1.0e-9);
+        for window in [
+            config::WindowFunction::Hann,
+            config::WindowFunction::Hamming,
+        ] {
+            assert!(level_after_step(window) > flat * 1.2);
+        }
+        let mut conf = params(-6.0, 12.0, 4096);
+        conf.detection_window = Some(config::WindowFunction::Hann);
+        conf.detection = Some(config::DetectionType::Peak);
+        assert!(validate_config(48000, 1024, &conf).is_err());
+    }
+
+    #[test]
+    fn hold_and_release_progress() {
+        let mut conf = params(-20.0, 12.0, 1024);
+        conf.detection_hold_ms = Some(100.0);
+        let mut limiter = RMSLimiter::from_config("test", conf, 1024, 48000);
+        assert_eq!(limiter.hold_remaining_ms(), 0.0);
+        assert_eq!(limiter.release_progress(), 1.0);
+        output_level(&mut limiter, 1.0, 1024);
+        assert_eq!(limiter.release_progress(), 0.0);
+        // The hold window is 5 chunks of 21.3 ms, counting down while the level is held
+        let chunk_ms = 1000.0 * 1024.0 / 48000.0;
+        let mut remaining = Vec::new();
+        let mut progress = Vec::new();
+        for _ in 0..150 {
+            output_level(&mut limiter, 0.01, 1024);
+            remaining.push(limiter.hold_remaining_ms());
+            progress.push(limiter.release_progress());
+        }
+        for (chunk, ms) in remaining.iter().take(5).enumerate() {
+            assert!((ms - (5 - chunk) as PrcFmt * chunk_ms).abs() < 1.0e-3);
+        }
+        assert!(remaining.iter().skip(5).all(|ms| *ms == 0.0));
+        // The release starts when the hold ends, and progresses steadily to unity
+        assert!(progress.iter().take(5).all(|p| *p == 0.0));
+        assert!(progress[5] > 0.0 && progress[5] < 0.1);
+        assert!(progress.windows(2).all(|p| p[1] >= p[0]));
+        assert_eq!(progress.last(), Some(&1.0));
+    }
+
+    #[test]
+    fn reduction_rate_is_bounded() {
+        let mut conf = params(-20.0, 12.0, 1024);
+        conf.max_reduction_rate_db_per_s = Some(100.0);
+        assert!(validate_config(48000, 1024, &conf).is_ok());
+        let mut limiter = RMSLimiter::from_config("test", conf.clone(), 1024, 48000);
+        // 100 dB/s gives at most 2.13 dB more reduction per chunk
+        let max_step_db = 100.0 * 1024.0 / 48000.0;
+        let mut previous_db: PrcFmt = 0.0;
+        for chunk in 0..20 {
+            // Loud junk close to the Nyquist frequency
+            let mut waveform: Vec<PrcFmt> = (0..1024)
+                .map(|n| {
+                    (0.98 * std::f64::consts::PI as PrcFmt * (chunk * 1024 + n) as PrcFmt).sin()
+                })
+                .collect();
+            limiter.process_waveform(&mut waveform).unwrap();
+            let gain_db = voltage_ratio_to_db(limiter.current_gain);
+            assert!(previous_db - gain_db <= max_step_db + 1.0e-4);
+            previous_db = gain_db;
+        }
+        // The reduction still gets where it is going, just more slowly
+        assert!(previous_db < -15.0);
+        conf.max_reduction_rate_db_per_s = Some(0.0);
+        assert!(validate_config(48000, 1024, &conf).is_err());
+    }
+
+    #[test]
+    fn gain_staging_between_limiters() {
+        // The first limiter does not limit, and its input gain sets the level
+        let mut first_conf = params(0.0, 12.0, 1024);
+        first_conf.input_gain_db = Some(-20.0);
+        let mut first = RMSLimiter::from_config("first", first_conf, 1024, 48000);
+        let mut second = RMSLimiter::from_config("second", params(0.0, 12.0, 1024), 1024, 48000);
+        let bus = GainStagingBus::new();
+        first.publish_output_peak(bus.clone());
+        second.follow_input_peak(bus, -6.0);
+        for _ in 0..4 {
+            let mut waveform: Vec<PrcFmt> = (0..1024)
+                .map(|n| 0.5 * (n as PrcFmt * 0.05).sin())
+                .collect();
+            first.process_waveform(&mut waveform).unwrap();
+            second.process_waveform(&mut waveform).unwrap();
+            let peak = waveform
+                .iter()
+                .fold(0.0, |acc: PrcFmt, val| acc.max(val.abs()));
+            // The output of the first peaks at -26 dB, and the second brings it up to -6 dB
+            assert!((voltage_ratio_to_db(peak) + 6.0).abs() < 1.0e-3);
+        }
+        assert!((second.staging_trim_db() - 20.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn soft_clip_ceiling() {
+        let mut conf = params(-12.0, 12.0, 4096);
+        conf.clip_ceiling_db = Some(-6.0);
+        assert!(validate_config(48000, 1024, &conf).is_ok());
+        let mut limiter = RMSLimiter::from_config("test", conf.clone(), 1024, 48000);
+        let ceiling = db_to_voltage_ratio(-6.0);
+        // Short spikes on a quiet sine do not raise the rms level enough to engage the limiter
+        let input: Vec<PrcFmt> = (0..1024)
+            .map(|n| {
+                let spike = if n % 256 == 100 { 0.8 } else { 0.0 };
+                0.2 * (n as PrcFmt * 0.05).sin() + spike
+            })
+            .collect();
+        let mut waveform = input.clone();
+        limiter.process_waveform(&mut waveform).unwrap();
+        assert_eq!(limiter.current_gain, 1.0);
+        let mut clipped = 0;
+        for (input, output) in input.iter().zip(waveform.iter()) {
+            assert!(output.abs() < ceiling);
+            if input.abs() <= 0.7 * ceiling {
+                assert_eq!(output, input);
+            } else {
+                // The peaks are rounded off, not flattened at the ceiling
+                assert!(output.abs() < input.abs() && output.abs() > 0.7 * ceiling);
+                clipped += 1;
+            }
+        }
+        assert_eq!(clipped, 4);
+        // The rms limiting still brings loud input down to the threshold
+        let mut level_db = 0.0;
+        for chunk in 0..20 {
+            let mut waveform: Vec<PrcFmt> = (chunk * 1024..(chunk + 1) * 1024)
+                .map(|n| 0.9 * (n as PrcFmt * 0.05).sin())
+                .collect();
+            limiter.process_waveform(&mut waveform).unwrap();
+            level_db = voltage_ratio_to_db(rms(waveform.iter()));
+        }
+        assert!((level_db + 12.0).abs() < 0.5);
+        conf.clip_ceiling_db = Some(1.0);
+        assert!(validate_config(48000, 1024, &conf).is_err());
+    }
+
+    #[test]
+    fn partial_chunk_release() {
+        let limiter_after_reduction = || {
+            let mut limiter =
+                RMSLimiter::from_config("test", params(-20.0, 12.0, 256), 1024, 48000);
+            output_level(&mut limiter, 0.5, 1024);
+            // Empty the detection buffer, so that the next chunks only release
+            output_level(&mut limiter, 0.0, 1024);
+            limiter
+        };
+        let release_db = |limiter: &mut RMSLimiter, len: usize| {
+            let before = voltage_ratio_to_db(limiter.current_gain);
+            output_level(limiter, 0.0, len);
+            voltage_ratio_to_db(limiter.current_gain) - before
+        };
+        let full = release_db(&mut limiter_after_reduction(), 1024);
+        let half = release_db(&mut limiter_after_reduction(), 512);
+        assert!(full > 0.0);
+        assert!((half - 0.5 * full).abs() < 1.0e-3);
+    }
+
+    #[test]
+    fn warn_for_threshold_above_full_scale() {
+        let conf = params(3.0, 12.0, 1024);
+        assert!(validate_config(48000, 1024, &conf).is_ok());
+        let warnings = config_warnings(&conf);
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("above 0 dBFS"));
+        assert!(config_warnings(&params(-3.0, 12.0, 1024)).is_empty());
+    }
+
+    #[test]
+    fn attack_follows_window_length() {
+        let mut conf = params(-20.0, 12.0, 1024);
+        conf.attack_from_window = Some(0.5);
+        assert!(validate_config(48000, 1024, &conf).is_ok());
+        let mut limiter = RMSLimiter::from_config("test", conf.clone(), 1024, 48000);
+        assert!((limiter.attack_time.unwrap() - 0.5 * 1024.0 / 48000.0).abs() < 1.0e-6);
+        // The reduction is reached gradually instead of at once
+        output_level(&mut limiter, 0.5, 1024);
+        let target = limiter.target_gain(limiter.detected_level);
+        assert!(limiter.current_gain > target * 1.01);
+
+        conf.rms_samples = 4096;
+        limiter.update_parameters(config::Filter::RMSLimiter {
+            description: None,
+            parameters: conf.clone(),
+        });
+        assert!((limiter.attack_time.unwrap() - 0.5 * 4096.0 / 48000.0).abs() < 1.0e-6);
+
+        conf.attack_from_window = Some(0.0);
+        assert!(validate_config(48000, 1024, &conf).is_err());
+    }
+
+    #[test]
+    fn scheduled_threshold_changes() {
+        let mut limiter = RMSLimiter::from_config("test", params(0.0, 12.0, 64), 1024, 48000);
+        limiter.schedule_change(1500, ParamChange::Threshold(-20.0));
+        limiter.schedule_change(300, ParamChange::Threshold(-10.0));
+        let mut first = vec![0.5; 1024];
+        limiter.process_waveform(&mut first).unwrap();
+        let mut second = vec![0.5; 1024];
+        limiter.process_waveform(&mut second).unwrap();
+        // Nothing is limited before the first change
+        assert_eq!(first[299], 0.5);
+        assert!((first[300] - db_to_voltage_ratio(-10.0)).abs() < 1.0e-3);
+        assert_eq!(first[300], first[1023]);
+        assert_eq!(second[475], first[1023]);
+        assert!((second[476] - db_to_voltage_ratio(-20.0)).abs() < 1.0e-3);
+        assert_eq!(limiter.sample_position(), 2048);
+    }
+
+    #[test]
+    fn cost_estimate_is_monotonic() {
+        let mut conf = params(-6.0, 12.0, 1024);
+        conf.ceiling_dbtp = Some(-1.0);
+        let limiter = RMSLimiter::from_config("test", conf.clone(), 1024, 48000);
+        let costs: Vec<f64> = [1, 2, 4, 8]
+            .iter()
+            .map(|factor| limiter.estimated_cost(*factor))
+            .collect();
+        assert!(costs.windows(2).all(|pair| pair[1] > pair[0]));
+        let costs: Vec<f64> = [256, 1024, 4096, 16384]
+            .iter()
+            .map(|rms_samples| {
+                conf.rms_samples = *rms_samples;
+                RMSLimiter::from_config("test", conf.clone(), 1024, 48000)
+                    .estimated_cost_per_sample()
+            })
+            .collect();
+        assert!(costs.windows(2).all(|pair| pair[1] > pair[0]));
+        let plain = RMSLimiter::from_config("test", params(-6.0, 12.0, 1024), 1024, 48000);
+        assert!(plain.estimated_cost_per_sample() < limiter.estimated_cost_per_sample());
+    }
+
+    #[test]
+    fn release_shelf_reaches_unity() {
+        let mut conf = params(-6.0, 12.0, 1024);
+        conf.soft = Some(true);
+        conf.release_shelf_db = Some(1.0);
+        assert!(validate_config(48000, 1024, &conf).is_ok());
+        // A level in the soft knee, that asks for a gain reduction smaller than the shelf
+        let level = db_to_voltage_ratio(-7.0);
+        let mut limiter = RMSLimiter::from_config("test", conf.clone(), 1024, 48000);
+        let knee_gain = limiter.target_gain(level);
+        assert!(knee_gain < 1.0 && knee_gain > db_to_voltage_ratio(-1.0));
+        conf.release_shelf_db = None;
+        let mut without_shelf = RMSLimiter::from_config("test", conf.clone(), 1024, 48000);
+        for other in [&mut limiter, &mut without_shelf] {
+            output_level(other, 1.0, 1024);
+            for _ in 0..100 {
+                output_level(other, level, 1024);
+            }
+        }
+        assert_eq!(limiter.current_gain, 1.0);
+        assert!(without_shelf.current_gain < 1.0);
+
+        conf.release_shelf_db = Some(0.0);
+        assert!(validate_config(48000, 1024, &conf).is_err());
+    }
+
+    #[test]
+    fn input_overs_are_trimmed() {
+        let mut conf = params(0.0, 12.0, 1024);
+        let over = |limiter: &mut RMSLimiter| {
+            let mut waveform: Vec<PrcFmt> = (0..1024)
+                .map(|n| 1.5 * (n as PrcFmt * 0.05).sin())
+                .collect();
+            limiter.process_waveform(&mut waveform).unwrap();
+            waveform
+                .iter()
+                .fold(0.0, |acc: PrcFmt, val| acc.max(val.abs()))
+        };
+        // Without the trim, the overs are only counted
+        let mut limiter = RMSLimiter::from_config("test", conf.clone(), 1024, 48000);
+        over(&mut limiter);
+        assert_eq!(limiter.input_overs(), 1);
+        assert_eq!(limiter.over_trim_db(), 0.0);
+
+        conf.trim_input_overs = Some(true);
+        let mut limiter = RMSLimiter::from_config("test", conf, 1024, 48000);
+        output_level(&mut limiter, 0.5, 1024);
+        assert_eq!(limiter.input_overs(), 0);
+        for _ in 0..3 {
+            assert!(over(&mut limiter) < 1.0);
+        }
+        assert_eq!(limiter.input_overs(), 3);
+        assert!(limiter.over_trim_db() < -3.5);
+    }
+
+    #[test]
+    fn smaller_peaks_do_not_retrigger() {
+        let mut conf = params(-20.0, 100.0, 256);
+        conf.release_retrigger_db = Some(6.0);
+        assert!(validate_config(48000, 1024, &conf).is_ok());
+        // One large peak, followed by smaller ones during the release
+        let levels = [
+            1.0, 0.01, 0.01, 0.01, 0.01, 0.01, 0.01, 0.2, 0.2, 0.01, 0.2, 0.01, 0.2, 0.01,
+        ];
+        let gains = |conf: &config::RMSLimiterParameters| -> Vec<PrcFmt> {
+            let mut limiter = RMSLimiter::from_config("test", conf.clone(), 1024, 48000);
+            levels
+                .iter()
+                .map(|level| {
+                    output_level(&mut limiter, *level, 1024);
+                    limiter.current_gain
+                })
+                .collect()
+        };
+        let held = gains(&conf);
+        assert!(held.windows(2).all(|pair| pair[1] >= pair[0]));
+        assert!(held[levels.len() - 1] > held[0]);
+        conf.release_retrigger_db = None;
+        let retriggered = gains(&conf);
+        assert!(retriggered.windows(2).any(|pair| pair[1] < pair[0]));
+    }
+
+    #[test]
+    fn report_level_increase() {
+        let limiter = RMSLimiter::from_config("test", params(-6.0, 12.0, 1024), 1024, 48000);
+        assert!(!limiter.can_increase_level());
+        let mut conf = params(-6.0, 12.0, 1024);
+        conf.makeup_fraction = Some(0.5);
+        let limiter = RMSLimiter::from_config("test", conf, 1024, 48000);
+        assert!(limiter.can_increase_level());
+        let clip_limiter = Limiter::from_config(
+            "test",
+            config::LimiterParameters {
+                soft_clip: Some(true),
+                clip_limit: -1.0,
+            },
+        );
+        assert!(!clip_limiter.can_increase_level());
+    }
+
+    #[test]
+    fn attack_curves() {
+        // Chunks until the gain is within 1% of the target, after a step up in level
+        let chunks_to_target = |curve: config::AttackCurve| {
+            let mut conf = params(-20.0, 12.0, 256);
+            conf.attack_from_window = Some(8.0);
+            conf.attack_curve = Some(curve);
+            assert!(validate_config(48000, 64, &conf).is_ok());
+            let mut limiter = RMSLimiter::from_config("test", conf, 64, 48000);
+            let mut gains = Vec::new();
+            for _ in 0..1000 {
+                output_level(&mut limiter, 0.5, 64);
+                gains.push(limiter.current_gain);
+            }
+            let target = limiter.target_gain(limiter.detected_level);
+            let chunks = gains.iter().position(|g| *g < target * 1.01).unwrap();
+            (chunks, gains)
+        };
+        // The attack time is 8 * 256 samples, 32 chunks
+        let (linear, linear_gains) = chunks_to_target(config::AttackCurve::Linear);
+        let (exponential, _) = chunks_to_target(config::AttackCurve::Exponential);
+        let (s_curve, s_curve_gains) = chunks_to_target(config::AttackCurve::SCurve);
+        assert!((29..=32).contains(&linear), "linear {}", linear);
+        assert!(exponential > 3 * linear, "exponential {}", exponential);
+        assert!((29..=32).contains(&s_curve), "s-curve {}", s_curve);
+        // The S-curve starts more gently
+        assert!(s_curve_gains[4] > linear_gains[4]);
+
+        let mut conf = params(-20.0, 12.0, 256);
+        conf.attack_curve = Some(config::AttackCurve::Linear);
+        assert!(validate_config(48000, 64, &conf).is_err());
+    }
+
+    #[test]
+    fn ceiling_trim_overshoot() {
+        // Short bursts of a sine at fs/4 that straddle every other chunk boundary,
+        // so that the peak is split between two chunks
+        let overshoot = |trim_db: Option<f32>| {
+            let mut conf = params(0.0, 12.0, 256);
+            conf.ceiling_dbtp = Some(-1.0);
+            conf.ceiling_trim_db = trim_db;
+            assert!(validate_config(48000, 256, &conf).is_ok());
+            let mut limiter = RMSLimiter::from_config("test", conf, 256, 48000);
+            assert_eq!(limiter.max_overshoot_db(), None);
+            for chunk in 0..40 {
+                let mut waveform: Vec<PrcFmt> = (0..256)
+                    .map(|n| {
+                        let m = chunk * 256 + n;
+                        if (m + 8) % 512 < 16 {
+                            2.0 * (std::f64::consts::PI as PrcFmt * (0.5 * m as PrcFmt + 0.25))
+                                .sin()
+                        } else {
+                            0.0
+                        }
+                    })
+                    .collect();
+                limiter.process_waveform(&mut waveform).unwrap();
+            }
+            limiter.max_overshoot_db().unwrap()
+        };
+        let untrimmed = overshoot(Some(0.0));
+        assert!(untrimmed > 0.0, "overshoot {} dB", untrimmed);
+        // The default trim is enough for this, and costs only a little level
+        let trimmed = overshoot(None);
+        assert!(trimmed <= 0.0, "overshoot {} dB", trimmed);
+        assert!(trimmed > -0.2, "overshoot {} dB", trimmed);
+
+        let mut conf = params(0.0, 12.0, 256);
+        conf.ceiling_trim_db = Some(0.1);
+        assert!(validate_config(48000, 256, &conf).is_err());
+        conf.ceiling_dbtp = Some(-1.0);
+        conf.ceiling_trim_db = Some(-0.1);
+        assert!(validate_config(48000, 256, &conf).is_err());
+    }
+
+    #[test]
+    fn manual_gain() {
+        let mut conf = params(-20.0, 12.0, 256);
+        conf.manual = Some(true);
+        assert!(validate_config(48000, 256, &conf).is_ok());
+        let mut limiter = RMSLimiter::from_config("test", conf.clone(), 256, 48000);
+        // The detection is skipped, a loud input is passed at unity gain
+        assert_eq!(output_level(&mut limiter, 1.0, 256), 1.0);
+        assert_eq!(limiter.gain(), 1.0);
+        assert_eq!(limiter.fill_fraction(), 0.0);
+
+        limiter.set_gain(0.3);
+        assert_eq!(limiter.gain(), 0.3);
+        let input: Vec<PrcFmt> = (0..256).map(|n| (n as PrcFmt * 0.1).sin()).collect();
+        let mut waveform = input.clone();
+        limiter.process_waveform(&mut waveform).unwrap();
+        for (output, input) in waveform.iter().zip(input.iter()) {
+            assert_eq!(*output, 0.3 * input);
+        }
+        limiter.set_gain(1.5);
+        assert_eq!(limiter.gain(), 1.0);
+        limiter.set_gain(-0.5);
+        assert_eq!(limiter.gain(), 0.0);
+        limiter.set_gain(PrcFmt::NAN);
+        assert_eq!(limiter.gain(), 0.0);
+
+        // Without manual mode, the detection continues from the set gain
+        let mut limiter = RMSLimiter::from_config("test", params(-20.0, 12.0, 256), 256, 48000);
+        limiter.set_gain(0.5);
+        output_level(&mut limiter, 0.001, 256);
+        assert!(limiter.gain() > 0.5 && limiter.gain() < 1.0);
+
+        conf.ceiling_dbtp = Some(-1.0);
+        conf.peak_shaver = Some(true);
+        assert!(validate_config(48000, 256, &conf).is_err());
     }
-    Ok(())
 }
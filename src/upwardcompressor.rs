@@ -0,0 +1,222 @@
+use crate::config;
+use crate::detectionbuffer::{DefaultDetectionBuffer, DetectionBuffer};
+use crate::dynamics_math::{chunk_duration, db_to_voltage_ratio, voltage_ratio_to_db};
+use crate::filters::Filter;
+use crate::PrcFmt;
+use crate::Res;
+
+/// Upward compressor, that raises quiet passages towards the threshold.
+/// Signals above the threshold are left untouched. Below it, the level is raised
+/// according to the ratio, but never by more than the maximum boost.
+/// The detection works like in the RMSLimiter, with the gain logic inverted.
+pub struct UpwardCompressor {
+    pub name: String,
+    samplerate: usize,
+    chunksize: usize,
+    rms_buffer: DefaultDetectionBuffer,
+    threshold_db: PrcFmt,
+    ratio: PrcFmt,
+    max_boost_db: PrcFmt,
+    boost_step_db: PrcFmt,
+    boost_db: PrcFmt,
+    gain: PrcFmt,
+}
+
+impl UpwardCompressor {
+    /// Creates an UpwardCompressor filter from a config struct
+    pub fn from_config(
+        name: &str,
+        conf: config::UpwardCompressorParameters,
+        chunksize: usize,
+        samplerate: usize,
+    ) -> Self {
+        debug!(
+            "Creating upward compressor '{}', threshold: {}, ratio: {}, max boost: {}",
+            name, conf.threshold, conf.ratio, conf.max_boost_db
+        );
+        UpwardCompressor {
+            name: name.to_string(),
+            samplerate,
+            chunksize,
+            rms_buffer: DefaultDetectionBuffer::with_capacity(conf.rms_samples),
+            threshold_db: conf.threshold as PrcFmt,
+            ratio: conf.ratio as PrcFmt,
+            max_boost_db: conf.max_boost_db as PrcFmt,
+            boost_step_db: Self::boost_step_db(chunksize, samplerate, &conf),
+            boost_db: 0.0,
+            gain: 1.0,
+        }
+    }
+
+    /// Largest increase of the boost in dB in one chunk.
+    fn boost_step_db(
+        chunksize: usize,
+        samplerate: usize,
+        conf: &config::UpwardCompressorParameters,
+    ) -> PrcFmt {
+        conf.boost_rate() as PrcFmt * chunk_duration(chunksize, samplerate)
+    }
+
+    /// The boost in dB currently applied.
+    pub fn boost_db(&self) -> PrcFmt {
+        self.boost_db
+    }
+
+    /// The boost needed for a signal at the given level in dB.
+    fn target_boost_db(&self, level_db: PrcFmt) -> PrcFmt {
+        if level_db >= self.threshold_db {
+            return 0.0;
+        }
+        // Silence gives an infinite boost, that is clamped to the maximum
+        PrcFmt::min(
+            self.max_boost_db,
+            (self.threshold_db - level_db) * (1.0 - 1.0 / self.ratio),
+        )
+    }
+}
+
+impl Filter for UpwardCompressor {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn filter_type(&self) -> &'static str {
+        "UpwardCompressor"
+    }
+
+    fn process_waveform(&mut self, waveform: &mut [PrcFmt]) -> Res<()> {
+        if waveform.is_empty() {
+            return Ok(());
+        }
+        for value in waveform.iter() {
+            self.rms_buffer.push(*value);
+        }
+        let level_db = voltage_ratio_to_db(self.rms_buffer.level(config::DetectionType::Rms));
+        let target_db = self.target_boost_db(level_db);
+        // Reduce the boost at once when the signal gets louder, and raise it slowly
+        self.boost_db = if target_db < self.boost_db {
+            target_db
+        } else {
+            PrcFmt::min(target_db, self.boost_db + self.boost_step_db)
+        };
+
+        // Ramp the gain over the chunk to avoid steps between chunks
+        let target_gain = db_to_voltage_ratio(self.boost_db);
+        let step = (target_gain - self.gain) / waveform.len() as PrcFmt;
+        for item in waveform.iter_mut() {
+            self.gain += step;
+            *item *= self.gain;
+        }
+        self.gain = target_gain;
+        Ok(())
+    }
+
+    fn update_parameters(&mut self, conf: config::Filter) {
+        if let config::Filter::UpwardCompressor {
+            parameters: conf, ..
+        } = conf
+        {
+            if conf.rms_samples != self.rms_buffer.capacity() {
+                self.rms_buffer = DefaultDetectionBuffer::with_capacity(conf.rms_samples);
+            }
+            self.threshold_db = conf.threshold as PrcFmt;
+            self.ratio = conf.ratio as PrcFmt;
+            self.max_boost_db = conf.max_boost_db as PrcFmt;
+            self.boost_step_db = Self::boost_step_db(self.chunksize, self.samplerate, &conf);
+            debug!(
+                "Updated upward compressor '{}', threshold: {}, ratio: {}, max boost: {}",
+                self.name, self.threshold_db, self.ratio, self.max_boost_db
+            );
+        } else {
+            // This should never happen unless there is a bug somewhere else
+            panic!("Invalid config change!");
+        }
+    }
+}
+
+/// Validate an UpwardCompressor config.
+pub fn validate_config(conf: &config::UpwardCompressorParameters) -> Res<()> {
+    if conf.ratio < 1.0 {
+        let msg = format!("Ratio must be at least 1, got {}", conf.ratio);
+        return Err(config::ConfigError::new(&msg).into());
+    }
+    if conf.max_boost_db <= 0.0 {
+        let msg = "Maximum boost must be larger than zero";
+        return Err(config::ConfigError::new(msg).into());
+    }
+    if conf.boost_rate() <= 0.0 {
+        let msg = "Boost rate must be larger than zero";
+        return Err(config::ConfigError::new(msg).into());
+    }
+    if !conf.rms_samples.is_power_of_two() {
+        let msg = format!(
+            "Number of rms samples must be a power of two, got {}",
+            conf.rms_samples
+        );
+        return Err(config::ConfigError::new(&msg).into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config;
+    use crate::dynamics_math::{db_to_voltage_ratio, rms, voltage_ratio_to_db};
+    use crate::filters::Filter;
+    use crate::upwardcompressor::{validate_config, UpwardCompressor};
+    use crate::PrcFmt;
+
+    fn params() -> config::UpwardCompressorParameters {
+        config::UpwardCompressorParameters {
+            threshold: -20.0,
+            ratio: 2.0,
+            max_boost_db: 12.0,
+            rms_samples: 1024,
+            boost_rate: Some(24.0),
+        }
+    }
+
+    /// Output level in dB of a steady sine with the given rms level, after settling.
+    fn output_db(compressor: &mut UpwardCompressor, level_db: PrcFmt) -> PrcFmt {
+        let amplitude = db_to_voltage_ratio(level_db) * (2.0 as PrcFmt).sqrt();
+        let mut level = 0.0;
+        for chunk in 0..100 {
+            let mut waveform: Vec<PrcFmt> = (chunk * 1024..(chunk + 1) * 1024)
+                .map(|n| amplitude * (n as PrcFmt * 0.05).sin())
+                .collect();
+            compressor.process_waveform(&mut waveform).unwrap();
+            level = voltage_ratio_to_db(rms(waveform.iter()));
+        }
+        level
+    }
+
+    #[test]
+    fn boost_quiet_signals() {
+        assert!(validate_config(&params()).is_ok());
+        // 20 dB below threshold with a 2:1 ratio gives 10 dB of boost
+        let mut compressor = UpwardCompressor::from_config("test", params(), 1024, 48000);
+        assert!((output_db(&mut compressor, -40.0) + 30.0).abs() < 0.1);
+        assert!((compressor.boost_db() - 10.0).abs() < 0.1);
+
+        // A loud signal is untouched
+        let mut compressor = UpwardCompressor::from_config("test", params(), 1024, 48000);
+        let mut waveform: Vec<PrcFmt> = (0..1024)
+            .map(|n| 0.5 * (n as PrcFmt * 0.05).sin())
+            .collect();
+        let input = waveform.clone();
+        compressor.process_waveform(&mut waveform).unwrap();
+        assert_eq!(waveform, input);
+
+        // The boost is clamped at the maximum
+        let mut compressor = UpwardCompressor::from_config("test", params(), 1024, 48000);
+        assert!((output_db(&mut compressor, -60.0) + 48.0).abs() < 0.1);
+        assert_eq!(compressor.boost_db(), 12.0);
+
+        let mut conf = params();
+        conf.ratio = 0.5;
+        assert!(validate_config(&conf).is_err());
+        let mut conf = params();
+        conf.max_boost_db = 0.0;
+        assert!(validate_config(&conf).is_err());
+    }
+}
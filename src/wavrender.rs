@@ -0,0 +1,152 @@
+//! Offline rendering of wav files through a filter, for regression tests of the dynamics filters.
+//! The input is read from a wav file, processed chunk by chunk like in the pipeline,
+//! and written to a new wav file. The metrics are measured on the written output.
+
+use std::io::Cursor;
+
+use crate::dynamics_math::voltage_ratio_to_db;
+use crate::filters::Filter;
+use crate::PrcFmt;
+use crate::Res;
+
+/// Levels measured on a rendered wav file.
+#[derive(Clone, Debug)]
+pub struct RenderMetrics {
+    /// Sample peak of the output in dBFS.
+    pub peak_db: PrcFmt,
+    /// Largest difference between the input and output peaks of a chunk, in dB.
+    pub max_reduction_db: PrcFmt,
+}
+
+/// Write a mono 32-bit float wav file to memory.
+pub fn write_wav(samples: &[PrcFmt], samplerate: usize) -> Res<Vec<u8>> {
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate: samplerate as u32,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    let mut data = Vec::new();
+    let mut writer = hound::WavWriter::new(Cursor::new(&mut data), spec)?;
+    for sample in samples {
+        writer.write_sample(*sample as f32)?;
+    }
+    writer.finalize()?;
+    Ok(data)
+}
+
+/// Read the samples and samplerate of a mono 32-bit float wav file.
+pub fn read_wav(data: &[u8]) -> Res<(Vec<PrcFmt>, usize)> {
+    let reader = hound::WavReader::new(Cursor::new(data))?;
+    let samplerate = reader.spec().sample_rate as usize;
+    let samples = reader
+        .into_samples::<f32>()
+        .map(|s| s.map(|s| s as PrcFmt))
+        .collect::<Result<Vec<PrcFmt>, _>>()?;
+    Ok((samples, samplerate))
+}
+
+/// Render a wav file through a filter, and measure the output.
+/// The last chunk is shorter when the length is not a multiple of the chunksize.
+pub fn render_wav(
+    filter: &mut dyn Filter,
+    input: &[u8],
+    chunksize: usize,
+) -> Res<(Vec<u8>, RenderMetrics)> {
+    let (samples, samplerate) = read_wav(input)?;
+    let mut processed = samples.clone();
+    for chunk in processed.chunks_mut(chunksize) {
+        filter.process_waveform(chunk)?;
+    }
+    let output = write_wav(&processed, samplerate)?;
+
+    // Measure on what was written, to include the rounding of the file format
+    let (written, _) = read_wav(&output)?;
+    let peak = |wf: &[PrcFmt]| wf.iter().fold(0.0, |acc: PrcFmt, v| acc.max(v.abs()));
+    let max_reduction_db = samples
+        .chunks(chunksize)
+        .zip(written.chunks(chunksize))
+        .filter(|(input, _)| peak(input) > 0.0)
+        .map(|(input, output)| voltage_ratio_to_db(peak(input)) - voltage_ratio_to_db(peak(output)))
+        .fold(0.0, PrcFmt::max);
+    let metrics = RenderMetrics {
+        peak_db: voltage_ratio_to_db(peak(&written)),
+        max_reduction_db,
+    };
+    Ok((output, metrics))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config;
+    use crate::finalstage::FinalStage;
+    use crate::rms_limiter::RMSLimiter;
+    use crate::wavrender::{read_wav, render_wav, write_wav};
+    use crate::PrcFmt;
+
+    /// Tone bursts at 1 kHz with different levels, a quarter of a second each.
+    fn bursts() -> Vec<u8> {
+        let samples: Vec<PrcFmt> = [0.1, 0.9, 0.3, 1.0]
+            .iter()
+            .flat_map(|amplitude| {
+                (0..12000).map(move |n| {
+                    amplitude
+                        * (2.0 * std::f64::consts::PI as PrcFmt * 1000.0 * n as PrcFmt / 48000.0)
+                            .sin()
+                })
+            })
+            .collect();
+        write_wav(&samples, 48000).unwrap()
+    }
+
+    #[test]
+    fn wav_round_trip() {
+        let input = bursts();
+        let (samples, samplerate) = read_wav(&input).unwrap();
+        assert_eq!(samplerate, 48000);
+        assert_eq!(samples.len(), 48000);
+        assert_eq!(write_wav(&samples, 48000).unwrap(), input);
+    }
+
+    #[test]
+    fn render_rms_limiter_with_ceiling() {
+        let mut conf = config::RMSLimiterParameters::new(-12.0, 12.0);
+        conf.rms_samples = 256;
+        conf.ceiling_dbtp = Some(-1.0);
+        conf.strict_ceiling_tolerance_db = Some(0.0);
+        let mut limiter = RMSLimiter::from_config("test", conf, 1024, 48000);
+        let (output, metrics) = render_wav(&mut limiter, &bursts(), 1024).unwrap();
+        assert_eq!(read_wav(&output).unwrap().0.len(), 48000);
+        // The true peak is checked before the output is rounded to the file format
+        let max_over_db = limiter.max_output_over_ceiling_db().unwrap();
+        assert!(
+            max_over_db <= 0.0,
+            "true peak {} dB over ceiling",
+            max_over_db
+        );
+        assert!(metrics.peak_db <= -1.0, "peak {} dB", metrics.peak_db);
+        assert!(
+            metrics.max_reduction_db > 8.0 && metrics.max_reduction_db < 10.0,
+            "reduction {} dB",
+            metrics.max_reduction_db
+        );
+    }
+
+    #[test]
+    fn render_final_stage() {
+        let conf = config::FinalStageParameters {
+            decay: 12.0,
+            threshold: -6.0,
+            rms_samples: 256,
+            clip_ceiling: -3.0,
+        };
+        let mut limiter = FinalStage::from_config("test", conf, 1024, 48000);
+        let (_, metrics) = render_wav(&mut limiter, &bursts(), 1024).unwrap();
+        assert!(metrics.peak_db <= -3.0, "peak {} dB", metrics.peak_db);
+        assert!(
+            metrics.max_reduction_db > 2.5 && metrics.max_reduction_db < 4.0,
+            "reduction {} dB",
+            metrics.max_reduction_db
+        );
+    }
+}